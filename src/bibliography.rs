@@ -0,0 +1,606 @@
+use crate::entity::html::Html;
+use crate::entity::markdown::Citation;
+use crate::leaf;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BibliographyError(String);
+
+impl std::fmt::Display for BibliographyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl std::error::Error for BibliographyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+/// Citation to a key not in the bibliography
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownCitation {
+    pub key: String,
+    pub location: String,
+}
+
+impl std::fmt::Display for UnknownCitation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Unknown citation key: @{} in {}",
+            self.key, self.location
+        )
+    }
+}
+impl std::error::Error for UnknownCitation {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Style {
+    /// `(Knuth 1984, p. 33)`
+    #[default]
+    AuthorYear,
+    /// `[1, p. 33]`
+    Numeric,
+}
+
+impl FromStr for Style {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "author-year" => Ok(Style::AuthorYear),
+            "numeric" => Ok(Style::Numeric),
+            _ => Err(format!(
+                "Invalid citation style: {}; author-year or numeric",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Name {
+    pub family: String,
+    pub given: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Entry {
+    pub key: String,
+    pub authors: Vec<Name>,
+    pub title: String,
+    pub year: Option<String>,
+    /// Journal, book or publisher
+    pub container: Option<String>,
+    pub url: Option<String>,
+}
+
+impl Entry {
+    /// `Knuth`, `Knuth and Lamport` or `Knuth et al.`
+    pub fn author_label(&self) -> String {
+        match self.authors.as_slice() {
+            [] => self.title.to_string(),
+            [a] => a.family.to_string(),
+            [a, b] => format!("{} and {}", a.family, b.family),
+            [a, ..] => format!("{} et al.", a.family),
+        }
+    }
+
+    pub fn year_label(&self) -> String {
+        self.year.clone().unwrap_or_else(|| String::from("n.d."))
+    }
+}
+
+/// Entries loaded from a file, and the citations in the document
+#[derive(Debug, Default)]
+pub struct Bibliography {
+    entries: HashMap<String, Entry>,
+    style: Style,
+    /// Cited keys in the order of the first citation
+    cited: Vec<String>,
+    unknown: Vec<UnknownCitation>,
+}
+
+impl Bibliography {
+    pub fn new(entries: Vec<Entry>, style: Style) -> Self {
+        Self {
+            entries: entries
+                .into_iter()
+                .map(|e| (e.key.to_string(), e))
+                .collect(),
+            style,
+            ..Self::default()
+        }
+    }
+
+    /// Load a BibTeX (.bib) or CSL-JSON (.json) file
+    pub fn load(path: &str, style: Style) -> Result<Self, BibliographyError> {
+        Ok(Self::new(read(path)?, style))
+    }
+
+    pub fn style(&self) -> Style {
+        self.style
+    }
+
+    /// Mark the citations as cited without rendering them, e.g. for `\cite` of LaTeX
+    pub fn register(&mut self, citations: &[Citation]) {
+        for citation in citations.iter() {
+            if self.entries.contains_key(&citation.key) && !self.cited.contains(&citation.key) {
+                self.cited.push(citation.key.to_string());
+            }
+        }
+    }
+
+    /// HTML of a citation; `bracketed` for `[@key]`, or narrative for `@key`
+    pub fn cite(&mut self, citations: &[Citation], bracketed: bool, location: &str) -> String {
+        let mut items = vec![];
+        for citation in citations.iter() {
+            let entry = match self.entries.get(&citation.key) {
+                Some(entry) => entry.clone(),
+                None => {
+                    self.unknown.push(UnknownCitation {
+                        key: citation.key.to_string(),
+                        location: location.to_string(),
+                    });
+                    items.push(format!(
+                        "<span class=\"citation-missing\">@{}</span>",
+                        encode(&citation.key)
+                    ));
+                    continue;
+                }
+            };
+            if !self.cited.contains(&entry.key) {
+                self.cited.push(entry.key.to_string());
+            }
+            let number = self.cited.iter().position(|k| k == &entry.key).unwrap() + 1;
+            let locator = match &citation.locator {
+                Some(locator) => format!(", {}", encode(locator)),
+                None => String::new(),
+            };
+            let label = match (self.style, bracketed) {
+                (Style::AuthorYear, true) => {
+                    format!("{} {}{}", entry.author_label(), entry.year_label(), locator)
+                }
+                (Style::AuthorYear, false) => {
+                    format!(
+                        "{} ({}{})",
+                        entry.author_label(),
+                        entry.year_label(),
+                        locator
+                    )
+                }
+                (Style::Numeric, true) => format!("{}{}", number, locator),
+                (Style::Numeric, false) => {
+                    format!("{} [{}{}]", entry.author_label(), number, locator)
+                }
+            };
+            items.push(format!(
+                "<a href=\"#ref-{}\">{}</a>",
+                encode(&entry.key),
+                encode(&label)
+            ));
+        }
+        let items = items.join("; ");
+        match (self.style, bracketed) {
+            (Style::AuthorYear, true) => format!("<span class=\"citation\">({})</span>", items),
+            (Style::Numeric, true) => format!("<span class=\"citation\">[{}]</span>", items),
+            _ => format!("<span class=\"citation\">{}</span>", items),
+        }
+    }
+
+    pub fn unknown(&self) -> Vec<UnknownCitation> {
+        self.unknown.clone()
+    }
+
+    /// Cited entries; sorted by the authors (author-year) or in the order of the first citation
+    pub fn cited(&self) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self.cited.iter().map(|k| &self.entries[k]).collect();
+        if self.style == Style::AuthorYear {
+            entries.sort_by_key(|e| (e.author_label(), e.year_label()));
+        }
+        entries
+    }
+
+    /// List of the cited entries; `None` when nothing is cited
+    pub fn references(&self) -> Option<Html> {
+        if self.cited.is_empty() {
+            return None;
+        }
+        let list = match self.style {
+            Style::AuthorYear => "ul",
+            Style::Numeric => "ol",
+        };
+        let items: Vec<String> = self
+            .cited()
+            .iter()
+            .map(|entry| {
+                format!(
+                    "<li id=\"ref-{}\">{}</li>",
+                    encode(&entry.key),
+                    reference(entry)
+                )
+            })
+            .collect();
+        Some(leaf!(
+            "<div class=\"references\"><{}>{}</{}></div>",
+            list,
+            items.join(""),
+            list
+        ))
+    }
+}
+
+/// Entries of a BibTeX (.bib) or CSL-JSON (.json) file
+pub fn read(path: &str) -> Result<Vec<Entry>, BibliographyError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|err| BibliographyError(format!("Cannot read {}: {}", path, err)))?;
+    if path.ends_with(".json") {
+        from_csl_json(&content)
+    } else {
+        from_bibtex(&content)
+    }
+}
+
+/// Placeholder of the references list (`::: {#refs}`)
+pub fn marker() -> Html {
+    leaf!("<!--unidoc:references-->")
+}
+
+/// `Knuth, D. E. and Lamport, L. (1984). Title. <em>Container</em>. URL`
+fn reference(entry: &Entry) -> String {
+    let authors = entry
+        .authors
+        .iter()
+        .map(|name| {
+            if name.given.is_empty() {
+                encode(&name.family)
+            } else {
+                encode(&format!("{}, {}", name.family, name.given))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" and ");
+    let mut html = format!(
+        "{} ({}). {}.",
+        authors,
+        encode(&entry.year_label()),
+        encode(&entry.title)
+    );
+    if let Some(container) = &entry.container {
+        html += &format!(" <em>{}</em>.", encode(container));
+    }
+    if let Some(url) = &entry.url {
+        html += &format!(" <a href=\"{}\">{}</a>", encode(url), encode(url));
+    }
+    html.trim_start().to_string()
+}
+
+fn encode(text: &str) -> String {
+    html_escape::encode_safe(text).to_string()
+}
+
+/// `Family, Given` or `Given Family`
+fn parse_name(name: &str) -> Name {
+    match name.find(',') {
+        Some(i) => Name {
+            family: name[..i].trim().to_string(),
+            given: name[i + 1..].trim().to_string(),
+        },
+        None => match name.trim().rfind(' ') {
+            Some(i) => Name {
+                family: name.trim()[i + 1..].to_string(),
+                given: name.trim()[..i].to_string(),
+            },
+            None => Name {
+                family: name.trim().to_string(),
+                given: String::new(),
+            },
+        },
+    }
+}
+
+/// Entries of a BibTeX file
+pub fn from_bibtex(bibtex: &str) -> Result<Vec<Entry>, BibliographyError> {
+    let chars: Vec<char> = bibtex.chars().collect();
+    let error = |msg: &str, i: usize| {
+        let line = chars[..i.min(chars.len())]
+            .iter()
+            .filter(|&&c| c == '\n')
+            .count()
+            + 1;
+        BibliographyError(format!("Invalid BibTeX at line {}: {}", line, msg))
+    };
+    let skip_spaces = |i: &mut usize| {
+        while *i < chars.len() && chars[*i].is_whitespace() {
+            *i += 1;
+        }
+    };
+    // `{...}` with nested braces; returns the inside
+    let braced = |i: &mut usize| -> Result<String, BibliographyError> {
+        let begin = *i;
+        let mut depth = 0;
+        while *i < chars.len() {
+            match chars[*i] {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        *i += 1;
+                        return Ok(chars[begin + 1..*i - 1].iter().collect());
+                    }
+                }
+                _ => {}
+            }
+            *i += 1;
+        }
+        Err(error("unbalanced braces", begin))
+    };
+    let mut entries = vec![];
+    let mut i = 0;
+    while let Some(at) = chars[i..].iter().position(|&c| c == '@') {
+        i += at + 1;
+        let begin = i;
+        while i < chars.len() && chars[i].is_alphanumeric() {
+            i += 1;
+        }
+        let kind: String = chars[begin..i].iter().collect::<String>().to_lowercase();
+        skip_spaces(&mut i);
+        if i >= chars.len() || chars[i] != '{' {
+            return Err(error("`{` expected", i));
+        }
+        if kind == "comment" || kind == "preamble" || kind == "string" {
+            braced(&mut i)?;
+            continue;
+        }
+        i += 1;
+        let begin = i;
+        while i < chars.len() && chars[i] != ',' && chars[i] != '}' {
+            i += 1;
+        }
+        let key: String = chars[begin..i]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string();
+        let mut fields = HashMap::new();
+        while i < chars.len() && chars[i] == ',' {
+            i += 1;
+            skip_spaces(&mut i);
+            if i < chars.len() && chars[i] == '}' {
+                break;
+            }
+            let begin = i;
+            while i < chars.len() && chars[i] != '=' && chars[i] != '}' {
+                i += 1;
+            }
+            if i >= chars.len() || chars[i] != '=' {
+                return Err(error("`=` expected", i));
+            }
+            let name: String = chars[begin..i]
+                .iter()
+                .collect::<String>()
+                .trim()
+                .to_lowercase();
+            i += 1;
+            let mut value = String::new();
+            loop {
+                skip_spaces(&mut i);
+                match chars.get(i) {
+                    Some('{') => value += &braced(&mut i)?,
+                    Some('"') => {
+                        let begin = i;
+                        i += 1;
+                        let mut depth = 0;
+                        while i < chars.len() && !(chars[i] == '"' && depth == 0) {
+                            match chars[i] {
+                                '{' => depth += 1,
+                                '}' => depth -= 1,
+                                _ => {}
+                            }
+                            i += 1;
+                        }
+                        if i >= chars.len() {
+                            return Err(error("unterminated string", begin));
+                        }
+                        value += &chars[begin + 1..i].iter().collect::<String>();
+                        i += 1;
+                    }
+                    Some(_) => {
+                        let begin = i;
+                        while i < chars.len() && chars[i].is_alphanumeric() {
+                            i += 1;
+                        }
+                        value += &chars[begin..i].iter().collect::<String>();
+                    }
+                    None => return Err(error("value expected", i)),
+                }
+                skip_spaces(&mut i);
+                if chars.get(i) == Some(&'#') {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            fields.insert(name, clean(&value));
+        }
+        if i >= chars.len() || chars[i] != '}' {
+            return Err(error("`}` expected", i));
+        }
+        i += 1;
+        let authors = fields
+            .get("author")
+            .or_else(|| fields.get("editor"))
+            .map(|names| names.split(" and ").map(parse_name).collect())
+            .unwrap_or_default();
+        entries.push(Entry {
+            key,
+            authors,
+            title: fields.get("title").cloned().unwrap_or_default(),
+            year: fields.get("year").cloned(),
+            container: ["journal", "booktitle", "publisher", "school", "institution"]
+                .iter()
+                .find_map(|name| fields.get(*name).cloned()),
+            url: fields.get("url").cloned(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Strip the braces and escapes of a BibTeX value
+fn clean(value: &str) -> String {
+    let value = value
+        .replace(['{', '}'], "")
+        .replace("\\&", "&")
+        .replace("\\%", "%")
+        .replace("\\_", "_")
+        .replace("--", "–");
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Entries of a CSL-JSON file
+pub fn from_csl_json(json: &str) -> Result<Vec<Entry>, BibliographyError> {
+    let items: Vec<Value> = serde_json::from_str(json)
+        .map_err(|err| BibliographyError(format!("Invalid CSL-JSON: {}", err)))?;
+    let text = |item: &Value, key: &str| item.get(key).and_then(|v| v.as_str()).map(String::from);
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let key = text(item, "id")?;
+            let authors = item
+                .get("author")
+                .or_else(|| item.get("editor"))
+                .and_then(|names| names.as_array())
+                .map(|names| {
+                    names
+                        .iter()
+                        .map(|name| Name {
+                            family: text(name, "family")
+                                .or_else(|| text(name, "literal"))
+                                .unwrap_or_default(),
+                            given: text(name, "given").unwrap_or_default(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            let year = item
+                .pointer("/issued/date-parts/0/0")
+                .map(|year| match year {
+                    Value::String(year) => year.to_string(),
+                    year => year.to_string(),
+                });
+            Some(Entry {
+                key,
+                authors,
+                title: text(item, "title").unwrap_or_default(),
+                year,
+                container: text(item, "container-title").or_else(|| text(item, "publisher")),
+                url: text(item, "URL"),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test_bibliography {
+    use crate::bibliography::*;
+
+    fn citation(key: &str, locator: Option<&str>) -> Citation {
+        Citation {
+            key: key.to_string(),
+            locator: locator.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_from_bibtex() {
+        let bibtex = r#"
+            @comment{ ignored {nested} }
+            @book{knuth1984,
+                author = {Donald E. Knuth},
+                title = {The {\TeX}book},
+                publisher = "Addison-Wesley",
+                year = 1984,
+            }
+            @article{lamport, author = {Lamport, Leslie and Knuth, Donald}, title = "A" # " B"}
+        "#;
+        let entries = from_bibtex(bibtex).unwrap();
+        assert_eq!(
+            entries[0],
+            Entry {
+                key: String::from("knuth1984"),
+                authors: vec![Name {
+                    family: String::from("Knuth"),
+                    given: String::from("Donald E."),
+                }],
+                title: String::from("The \\TeXbook"),
+                year: Some(String::from("1984")),
+                container: Some(String::from("Addison-Wesley")),
+                url: None,
+            }
+        );
+        assert_eq!(entries[1].author_label(), "Lamport and Knuth");
+        assert_eq!(entries[1].title, "A B");
+        assert!(from_bibtex("@book{x, title = {A}").is_err());
+    }
+
+    #[test]
+    fn test_from_csl_json() {
+        let json = r#"[{"id": "a", "title": "T", "author": [{"family": "F", "given": "G"}],
+                        "issued": {"date-parts": [[2001, 2]]}, "container-title": "J"}]"#;
+        let entries = from_csl_json(json).unwrap();
+        assert_eq!(entries[0].author_label(), "F");
+        assert_eq!(entries[0].year, Some(String::from("2001")));
+        assert_eq!(entries[0].container, Some(String::from("J")));
+    }
+
+    #[test]
+    fn test_cite() {
+        let entries = from_bibtex(
+            "@book{k, author={Knuth, Donald}, title={T}, year={1984}}
+             @book{l, author={A and B and C}, title={U}}",
+        )
+        .unwrap();
+        let mut bib = Bibliography::new(entries.clone(), Style::AuthorYear);
+        assert_eq!(
+            bib.cite(&[citation("k", Some("p. 33")), citation("l", None)], true, "a.md"),
+            "<span class=\"citation\">(<a href=\"#ref-k\">Knuth 1984, p. 33</a>; <a href=\"#ref-l\">A et al. n.d.</a>)</span>"
+        );
+        assert_eq!(
+            bib.cite(&[citation("k", None)], false, "a.md"),
+            "<span class=\"citation\"><a href=\"#ref-k\">Knuth (1984)</a></span>"
+        );
+        assert_eq!(
+            bib.cite(&[citation("x", None)], true, "a.md"),
+            "<span class=\"citation\">(<span class=\"citation-missing\">@x</span>)</span>"
+        );
+        assert_eq!(
+            bib.unknown(),
+            vec![UnknownCitation {
+                key: String::from("x"),
+                location: String::from("a.md"),
+            }]
+        );
+        let mut bib = Bibliography::new(entries, Style::Numeric);
+        assert_eq!(bib.references(), None);
+        bib.cite(&[citation("l", None)], true, "a.md");
+        assert_eq!(
+            bib.cite(&[citation("k", Some("p. 1"))], true, "a.md"),
+            "<span class=\"citation\">[<a href=\"#ref-k\">2, p. 1</a>]</span>"
+        );
+        assert_eq!(
+            bib.references(),
+            Some(leaf!(concat!(
+                "<div class=\"references\"><ol>",
+                "<li id=\"ref-l\">A and B and C (n.d.). U.</li>",
+                "<li id=\"ref-k\">Knuth, Donald (1984). T.</li>",
+                "</ol></div>"
+            )))
+        );
+    }
+}
@@ -59,8 +59,8 @@ fn blogcard_general(url: String) -> Html {
     let image = webpage
         .meta("og:image")
         .map(|img| webpage.absolute_url(&img))
-        .unwrap_or(String::new());
-    let description = webpage.meta("og:description").unwrap_or(String::new());
+        .unwrap_or_default();
+    let description = webpage.meta("og:description").unwrap_or_default();
     const TEMPLATE: &str = r#"
 <div class="blogcard" style="width:auto;max-width:9999px;border:1px solid #E0E0E0;border-radius:3px;margin:10px 0;padding:15px;line-height:1.4;text-align:left;background:#FFFFFF;">
 <a href="{{url}}" target="_blank" style="display:block;text-decoration:none;">
@@ -94,12 +94,11 @@ fn blogcard_soundcloud(url: String, user_id: String) -> Html {
     }
     let sound_id = webpage
         .meta("al:ios:url")
-        .map(|link| {
+        .and_then(|link| {
             INTENT
                 .captures(&link)
                 .map(|caps| caps.get(1).unwrap().as_str().to_string())
         })
-        .flatten()
         .unwrap();
     const TEMPLATE: &str = r#"
 <iframe width="100%" height="300" scrolling="no" frameborder="no" allow="autoplay" src="https://w.soundcloud.com/player/?url=https%3A//api.soundcloud.com/tracks/{{sound_id}}&color=%23ff5500&auto_play=false&hide_related=false&show_comments=true&show_user=true&show_reposts=false&show_teaser=true&visual=true"></iframe><div style="font-size: 10px; color: #cccccc;line-break: anywhere;word-break: normal;overflow: hidden;white-space: nowrap;text-overflow: ellipsis; font-family: Interstate,Lucida Grande,Lucida Sans Unicode,Lucida Sans,Garuda,Verdana,Tahoma,sans-serif;font-weight: 100;"><a href="https://soundcloud.com/{{user_id}}" title="{{user_id}}" target="_blank" style="color: #cccccc; text-decoration: none;">{{user_id}}</a> · <a href="{{url}}" title="{{title}}" target="_blank" style="color: #cccccc; text-decoration: none;">{{title}}</a></div>
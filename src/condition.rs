@@ -0,0 +1,248 @@
+use crate::entity::markdown::{Block, Markdown};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_until, take_while1};
+use nom::character::complete::multispace0;
+use nom::combinator::{map, not, opt, peek};
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::IResult;
+use std::collections::HashMap;
+
+type ParseResult<'a, T> = IResult<&'a str, T>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionError(String);
+
+impl std::fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl std::error::Error for ConditionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+/// Condition expression for `::: {if="..."}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Condition {
+    Var(String),
+    Str(String),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Eq(Box<Condition>, Box<Condition>),
+    Ne(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    fn value(&self, variables: &HashMap<String, String>) -> Option<String> {
+        match self {
+            Condition::Var(name) => variables.get(name).cloned(),
+            Condition::Str(s) => Some(s.to_string()),
+            _ => {
+                if self.test(variables) {
+                    Some(String::from("true"))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Variables are true when defined and not in `"", "false", "no", "0"`
+    fn test(&self, variables: &HashMap<String, String>) -> bool {
+        match self {
+            Condition::Var(_) | Condition::Str(_) => match self.value(variables) {
+                Some(value) => !matches!(value.as_str(), "" | "false" | "no" | "0"),
+                None => false,
+            },
+            Condition::Not(x) => !x.test(variables),
+            Condition::And(x, y) => x.test(variables) && y.test(variables),
+            Condition::Or(x, y) => x.test(variables) || y.test(variables),
+            Condition::Eq(x, y) => x.value(variables) == y.value(variables),
+            Condition::Ne(x, y) => x.value(variables) != y.value(variables),
+        }
+    }
+}
+
+/// Evaluate a condition expression, e.g. `edition == 'internal' and not draft`
+pub fn eval(expr: &str, variables: &HashMap<String, String>) -> Result<bool, ConditionError> {
+    match terminated(parse_or, multispace0)(expr) {
+        Ok(("", cond)) => Ok(cond.test(variables)),
+        _ => Err(ConditionError(format!("Invalid condition: {}", expr))),
+    }
+}
+
+/// Drop the blocks whose conditions are false
+pub fn filter(
+    mkd: &Markdown,
+    variables: &HashMap<String, String>,
+) -> Result<Markdown, ConditionError> {
+    let mut blocks = vec![];
+    for block in mkd.iter() {
+        match block {
+            Block::Div(attributes, children) => {
+                if let Some(expr) = attributes.get("if") {
+                    if !eval(expr, variables)? {
+                        continue;
+                    }
+                }
+                blocks.push(Block::Div(attributes.clone(), filter(children, variables)?));
+            }
+            _ => blocks.push(block.clone()),
+        }
+    }
+    Ok(blocks)
+}
+
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> ParseResult<'a, &'a str> {
+    delimited(
+        multispace0,
+        terminated(tag(word), not(peek(take_while1(is_ident_char)))),
+        multispace0,
+    )
+}
+
+fn symbol<'a>(sym: &'static str) -> impl FnMut(&'a str) -> ParseResult<'a, &'a str> {
+    delimited(multispace0, tag(sym), multispace0)
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+fn parse_or(input: &str) -> ParseResult<'_, Condition> {
+    let (input, head) = parse_and(input)?;
+    let (input, tail) = many0(preceded(alt((keyword("or"), symbol("||"))), parse_and))(input)?;
+    let cond = tail
+        .into_iter()
+        .fold(head, |x, y| Condition::Or(Box::new(x), Box::new(y)));
+    Ok((input, cond))
+}
+
+fn parse_and(input: &str) -> ParseResult<'_, Condition> {
+    let (input, head) = parse_not(input)?;
+    let (input, tail) = many0(preceded(alt((keyword("and"), symbol("&&"))), parse_not))(input)?;
+    let cond = tail
+        .into_iter()
+        .fold(head, |x, y| Condition::And(Box::new(x), Box::new(y)));
+    Ok((input, cond))
+}
+
+fn parse_not(input: &str) -> ParseResult<'_, Condition> {
+    alt((
+        map(
+            preceded(alt((keyword("not"), symbol("!"))), parse_not),
+            |x| Condition::Not(Box::new(x)),
+        ),
+        parse_comparison,
+    ))(input)
+}
+
+fn parse_comparison(input: &str) -> ParseResult<'_, Condition> {
+    let op = delimited(multispace0, alt((tag("=="), tag("!="))), multispace0);
+    map(
+        pair(parse_atom, opt(pair(op, parse_atom))),
+        |(x, rhs)| match rhs {
+            Some(("==", y)) => Condition::Eq(Box::new(x), Box::new(y)),
+            Some((_, y)) => Condition::Ne(Box::new(x), Box::new(y)),
+            None => x,
+        },
+    )(input)
+}
+
+fn parse_atom(input: &str) -> ParseResult<'_, Condition> {
+    let parse_paren = delimited(
+        pair(tag("("), multispace0),
+        parse_or,
+        pair(multispace0, tag(")")),
+    );
+    let parse_str = map(
+        alt((
+            delimited(tag("'"), take_until("'"), tag("'")),
+            delimited(tag("\""), take_until("\""), tag("\"")),
+        )),
+        |s: &str| Condition::Str(s.to_string()),
+    );
+    let parse_var = map(take_while1(is_ident_char), |name: &str| {
+        Condition::Var(name.to_string())
+    });
+    preceded(multispace0, alt((parse_paren, parse_str, parse_var)))(input)
+}
+
+#[cfg(test)]
+mod test_condition {
+    use crate::condition::*;
+    use crate::entity::markdown::{Attributes, Inline};
+
+    fn variables() -> HashMap<String, String> {
+        vec![("edition", "internal"), ("draft", "false"), ("lang", "ja")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_eval() {
+        let vars = variables();
+        assert_eq!(eval("edition == 'internal'", &vars), Ok(true));
+        assert_eq!(eval("edition == \"public\"", &vars), Ok(false));
+        assert_eq!(eval("edition != 'public'", &vars), Ok(true));
+        assert_eq!(eval("edition", &vars), Ok(true));
+        assert_eq!(eval("draft", &vars), Ok(false));
+        assert_eq!(eval("undefined", &vars), Ok(false));
+        assert_eq!(eval("not draft", &vars), Ok(true));
+        assert_eq!(eval("!draft && edition", &vars), Ok(true));
+        assert_eq!(eval("notes", &vars), Ok(false));
+        assert_eq!(
+            eval("edition == 'internal' and lang == 'en'", &vars),
+            Ok(false)
+        );
+        assert_eq!(
+            eval(
+                "edition == 'internal' and (lang == 'en' or lang == 'ja')",
+                &vars
+            ),
+            Ok(true)
+        );
+        assert_eq!(
+            eval("draft or not undefined and lang == 'ja'", &vars),
+            Ok(true)
+        );
+        assert!(eval("edition ==", &vars).is_err());
+        assert!(eval("(edition", &vars).is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let vars = variables();
+        let div = |cond: &str, children: Markdown| {
+            Block::Div(
+                Attributes {
+                    pairs: vec![(String::from("if"), cond.to_string())],
+                    ..Attributes::default()
+                },
+                children,
+            )
+        };
+        let p = |s: &str| Block::Paragraph(vec![Inline::Plaintext(s.to_string())]);
+        let mkd = vec![
+            p("a"),
+            div("edition == 'public'", vec![p("public")]),
+            div(
+                "edition == 'internal'",
+                vec![p("internal"), div("draft", vec![p("draft")])],
+            ),
+        ];
+        assert_eq!(
+            filter(&mkd, &vars),
+            Ok(vec![
+                p("a"),
+                div("edition == 'internal'", vec![p("internal")]),
+            ])
+        );
+        assert!(filter(&vec![div("==", vec![])], &vars).is_err());
+    }
+}
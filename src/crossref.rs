@@ -0,0 +1,150 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+/// Kind of a labeled block; numbered separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Figure,
+    Table,
+    Listing,
+    Equation,
+    Section,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Figure => "Figure",
+            Kind::Table => "Table",
+            Kind::Listing => "Listing",
+            Kind::Equation => "Equation",
+            Kind::Section => "Section",
+        }
+    }
+}
+
+/// `@label` in the document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub label: String,
+    /// Where the reference is, e.g. `intro.md (after #2-Setup)`
+    pub location: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingReference(pub Reference);
+
+impl std::fmt::Display for DanglingReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Dangling reference: @{} in {}",
+            self.0.label, self.0.location
+        )
+    }
+}
+impl std::error::Error for DanglingReference {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+/// Labels and references in the document
+#[derive(Debug, Default)]
+pub struct CrossRef {
+    counters: HashMap<Kind, usize>,
+    /// label -> text of the references, e.g. `Figure 3`
+    targets: HashMap<String, String>,
+    references: Vec<Reference>,
+}
+
+impl CrossRef {
+    /// Number a labeled block in document order
+    pub fn define(&mut self, kind: Kind, label: &str) -> usize {
+        let counter = self.counters.entry(kind).or_insert(0);
+        *counter += 1;
+        self.targets
+            .insert(label.to_string(), format!("{} {}", kind.name(), counter));
+        *counter
+    }
+
+    /// A heading; referred by its section number (or the title when unnumbered)
+    pub fn section(&mut self, label: &str, number: Option<&str>, title: &str) {
+        let text = match number {
+            Some(number) => format!("{} {}", Kind::Section.name(), number),
+            None => title.to_string(),
+        };
+        self.targets.insert(label.to_string(), text);
+    }
+
+    /// Placeholder of `@label`; resolved after the whole document is translated
+    pub fn refer(&mut self, label: &str, location: String) -> String {
+        self.references.push(Reference {
+            label: label.to_string(),
+            location,
+        });
+        format!("<!--unidoc:ref:{}-->", label)
+    }
+
+    /// Replace the placeholders with links
+    pub fn resolve(&self, html: &str) -> String {
+        lazy_static! {
+            static ref MARKER: Regex = Regex::new(r"<!--unidoc:ref:([^>]*?)-->").unwrap();
+        }
+        MARKER
+            .replace_all(html, |caps: &Captures| {
+                let label = html_escape::encode_double_quoted_attribute(&caps[1]);
+                match self.targets.get(&caps[1]) {
+                    Some(text) => format!("<a class=\"xref\" href=\"#{}\">{}</a>", label, text),
+                    None => format!("<span class=\"xref-missing\">@{}</span>", label),
+                }
+            })
+            .to_string()
+    }
+
+    pub fn dangling(&self) -> Vec<DanglingReference> {
+        self.references
+            .iter()
+            .filter(|r| !self.targets.contains_key(&r.label))
+            .cloned()
+            .map(DanglingReference)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test_crossref {
+    use crate::crossref::*;
+
+    #[test]
+    fn test_resolve() {
+        let mut crossref = CrossRef::default();
+        let html = format!(
+            "See {} and {}, {}",
+            crossref.refer("fig:b", String::from("a.md")),
+            crossref.refer("sec:intro", String::from("a.md")),
+            crossref.refer("tbl:none", String::from("a.md (after #2-Setup)")),
+        );
+        assert_eq!(crossref.define(Kind::Figure, "fig:a"), 1);
+        assert_eq!(crossref.define(Kind::Table, "tbl:a"), 1);
+        assert_eq!(crossref.define(Kind::Figure, "fig:b"), 2);
+        crossref.section("sec:intro", Some("1.2"), "Intro");
+        assert_eq!(
+            crossref.resolve(&html),
+            concat!(
+                "See <a class=\"xref\" href=\"#fig:b\">Figure 2</a>",
+                " and <a class=\"xref\" href=\"#sec:intro\">Section 1.2</a>",
+                ", <span class=\"xref-missing\">@tbl:none</span>"
+            )
+        );
+        assert_eq!(
+            crossref
+                .dangling()
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>(),
+            vec!["Dangling reference: @tbl:none in a.md (after #2-Setup)"]
+        );
+    }
+}
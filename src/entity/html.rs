@@ -10,15 +10,55 @@ impl HtmlDoc {
         Self { title, doc }
     }
     pub fn as_html(self) -> Html {
+        self.wrap("<div>", "</div>")
+    }
+    pub fn wrap(self, begin: &str, end: &str) -> Html {
         Html::Node(
-            Box::new(Html::Leaf(String::from("<div>"))),
+            Box::new(Html::Leaf(String::from(begin))),
             self.doc,
-            Box::new(Html::Leaf(String::from("</div>"))),
+            Box::new(Html::Leaf(String::from(end))),
         )
     }
     pub fn append(&mut self, other: &mut HtmlDoc) {
         self.doc.append(&mut other.doc);
     }
+    pub fn prepend(&mut self, html: Html) {
+        self.doc.insert(0, html);
+    }
+    /// Replace all `from` (in any depth) with `to`; returns whether replaced
+    pub fn replace(&mut self, from: &Html, to: &Html) -> bool {
+        let mut replaced = false;
+        for html in self.doc.iter_mut() {
+            replaced |= html.replace(from, to);
+        }
+        replaced
+    }
+    /// Apply `f` to all leaves
+    pub fn map_leaves<F: Fn(&str) -> String>(&mut self, f: &F) {
+        for html in self.doc.iter_mut() {
+            html.map_leaves(f);
+        }
+    }
+}
+
+/// `<` of a raw HTML tag, e.g. `<a`, `</a` or `<!--`
+pub fn is_tag_start(c: char, next: Option<char>) -> bool {
+    c == '<' && next.is_some_and(|n| n.is_ascii_alphabetic() || n == '/' || n == '!')
+}
+
+/// Whether a raw HTML tag is open after the text, e.g. `<a` in `<a href="x" title="y">`;
+/// `in_tag` is the state before it (tags continue over the plaintexts)
+pub fn in_tag(text: &str, in_tag: bool) -> bool {
+    let mut in_tag = in_tag;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_tag {
+            in_tag = c != '>';
+        } else {
+            in_tag = is_tag_start(c, chars.peek().copied());
+        }
+    }
+    in_tag
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -86,19 +126,19 @@ impl HtmlDoc {
         }
         let mut queue = VecDeque::new();
         for block in self.doc.iter() {
-            queue.push_back(Q::Push(&block));
+            queue.push_back(Q::Push(block));
         }
         while let Some(block) = queue.pop_front() {
             match block {
                 Q::Push(Html::Leaf(leaf)) => lines.push(format!("{}{}", tab, leaf)),
                 Q::Push(Html::Node(begin, doc, end)) => {
-                    queue.push_front(Q::Push(&end));
+                    queue.push_front(Q::Push(end));
                     queue.push_front(Q::Deindent);
                     for child in doc.iter().rev() {
-                        queue.push_front(Q::Push(&child));
+                        queue.push_front(Q::Push(child));
                     }
                     queue.push_front(Q::Indent);
-                    queue.push_front(Q::Push(&begin));
+                    queue.push_front(Q::Push(begin));
                 }
                 Q::Indent => {
                     indent += indentshift;
@@ -120,12 +160,46 @@ impl Html {
             v.push(child);
         }
     }
+    pub fn replace(&mut self, from: &Html, to: &Html) -> bool {
+        if self == from {
+            *self = to.clone();
+            true
+        } else if let Html::Node(_, children, _) = self {
+            let mut replaced = false;
+            for child in children.iter_mut() {
+                replaced |= child.replace(from, to);
+            }
+            replaced
+        } else {
+            false
+        }
+    }
+    pub fn map_leaves<F: Fn(&str) -> String>(&mut self, f: &F) {
+        match self {
+            Html::Leaf(leaf) => *leaf = f(leaf),
+            Html::Node(begin, children, end) => {
+                begin.map_leaves(f);
+                for child in children.iter_mut() {
+                    child.map_leaves(f);
+                }
+                end.map_leaves(f);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod test_main {
     use crate::entity::html::*;
-    use crate::{leaf, node};
+
+    #[test]
+    fn test_in_tag() {
+        assert!(in_tag("<a", false));
+        assert!(in_tag("href=\"x\"", true));
+        assert!(!in_tag("title=\"t\">text", true));
+        assert!(!in_tag("1 < 2", false));
+        assert!(!in_tag("<b>x</b>", false));
+    }
 
     #[test]
     fn test_html_push() {
@@ -141,6 +215,20 @@ mod test_main {
         );
     }
 
+    #[test]
+    fn test_replace() {
+        let mut doc = HtmlDoc::new(
+            String::new(),
+            vec![
+                leaf!("<x>"),
+                node!(leaf!("<div>"), leaf!("</div>"); [leaf!("<x>"), leaf!("<y>")]),
+            ],
+        );
+        assert!(doc.replace(&leaf!("<x>"), &node!(leaf!("<p>"), leaf!("</p>"))));
+        assert_eq!(doc.show_compact(), "<p></p><div><p></p><y></div>\n");
+        assert!(!doc.replace(&leaf!("<x>"), &leaf!("<z>")));
+    }
+
     #[test]
     fn test_show() {
         let html = node!(
@@ -1,10 +1,14 @@
+use serde::{Deserialize, Serialize};
+
 pub type Markdown = Vec<Block>;
 type Url = String;
 type FilePath = String;
 
-#[derive(Clone, Debug, PartialEq)]
+/// Blocks and inlines are `{"t": TAG, "c": CONTENT}` in the JSON AST (`--to json`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "t", content = "c")]
 pub enum Block {
-    Heading(usize, Text),
+    Heading(usize, Text, Attributes),
     ListBlock(List),
     Paragraph(Text),
     Quoted(Text),
@@ -15,16 +19,42 @@ pub enum Block {
     Import(FilePath),
     HyperLink(Url),
     MathJax(String),
+    Div(Attributes, Markdown),
+    Toc,
+    /// Figure, table, code or math with a caption and a label, e.g. `{#fig:arch}`
+    Labeled(Box<Block>, Text, Attributes),
+    Abbreviation(String, String), // *[TERM]: DEFINITION
+    Index,                        // [INDEX]
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// Metadata in the front matter
+pub type FrontMatter = Vec<(String, String)>;
+
+/// `{#id .class key=value}`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Attributes {
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    pub pairs: Vec<(String, String)>,
+}
+impl Attributes {
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes.iter().any(|c| c == class)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Align {
     Left,
     Center,
     Right,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct List {
     pub order_type: ListOrderType,
     pub items: Vec<ListItem>,
@@ -35,14 +65,14 @@ impl List {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ListOrderType {
     Unordered,
     OrderedNumbers,
     OrderedAlphabets,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ListItem {
     pub checked: Option<bool>,
     pub label: Text,
@@ -60,7 +90,8 @@ impl ListItem {
 
 pub type Text = Vec<Inline>;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "t", content = "c")]
 pub enum Inline {
     Link(Text, Url),
     HyperLink(Url),
@@ -75,4 +106,75 @@ pub enum Inline {
     Comment(String),
     MathJax(String),
     Emoji(String),
+    Variable(String, Option<String>), // ${name}, ${name:-default}
+    Reference(String),                // @fig:label
+    Citation(Vec<Citation>, bool),    // [@key, p. 33; @key2] (bracketed) or @key
+    Glossary(String),                 // {term}
+    Index(Text, Option<String>),      // [text]{.idx term="a!b"} or {.index term="a!b"}
+}
+
+/// Inline pieces are separated by spaces, except punctuations after markups, e.g. `*a*,`
+pub fn spaced(prev: Option<&Inline>, inline: &Inline) -> bool {
+    match (prev, inline) {
+        (Some(Inline::Plaintext(_)) | None, _) => true,
+        (_, Inline::Plaintext(text)) => !text.starts_with([')', ']', '.', ',', ':', ';', '!', '?']),
+        _ => true,
+    }
+}
+
+/// `@key, LOCATOR`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    pub key: String,
+    /// e.g. `p. 33`
+    pub locator: Option<String>,
+}
+
+/// Rebuild the blocks applying `f` to every text (headings, paragraphs, list items, table cells...)
+pub fn try_map_text<E, F>(mkd: &Markdown, f: &mut F) -> Result<Markdown, E>
+where
+    F: FnMut(&Text) -> Result<Text, E>,
+{
+    fn map_list<E, F>(list: &List, f: &mut F) -> Result<List, E>
+    where
+        F: FnMut(&Text) -> Result<Text, E>,
+    {
+        let mut items = vec![];
+        for item in list.items.iter() {
+            let children = match &item.children {
+                Some(children) => Some(map_list(children, f)?),
+                None => None,
+            };
+            items.push(ListItem::new(item.checked, f(&item.label)?, children));
+        }
+        Ok(List::new(list.order_type, items))
+    }
+    let mut blocks = vec![];
+    for block in mkd.iter() {
+        let block = match block {
+            Block::Heading(level, text, attributes) => {
+                Block::Heading(*level, f(text)?, attributes.clone())
+            }
+            Block::ListBlock(list) => Block::ListBlock(map_list(list, f)?),
+            Block::Paragraph(text) => Block::Paragraph(f(text)?),
+            Block::Quoted(text) => Block::Quoted(f(text)?),
+            Block::Table(aligns, rows, has_header) => {
+                let mut content = vec![];
+                for row in rows.iter() {
+                    content.push(row.iter().map(&mut *f).collect::<Result<_, _>>()?);
+                }
+                Block::Table(aligns.clone(), content, *has_header)
+            }
+            Block::Div(attributes, children) => {
+                Block::Div(attributes.clone(), try_map_text(children, f)?)
+            }
+            Block::Labeled(inner, caption, attributes) => {
+                let inner = try_map_text(&vec![(**inner).clone()], f)?.remove(0);
+                Block::Labeled(Box::new(inner), f(caption)?, attributes.clone())
+            }
+            _ => block.clone(),
+        };
+        blocks.push(block);
+    }
+    Ok(blocks)
 }
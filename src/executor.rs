@@ -12,11 +12,7 @@ pub enum ExecuteResult {
 }
 impl ExecuteResult {
     pub fn is_ok(&self) -> bool {
-        if let Self::Ok(_) = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Self::Ok(_))
     }
     pub fn unwrap(&self) -> &String {
         match self {
@@ -0,0 +1,259 @@
+use crate::entity::html::{is_tag_start, Html};
+use crate::entity::markdown::{Block, Markdown};
+use crate::leaf;
+use crate::slug::Slug;
+use std::collections::BTreeMap;
+
+/// `{term}` not in the glossary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownTerm {
+    pub term: String,
+    pub location: String,
+}
+
+impl std::fmt::Display for UnknownTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Unknown glossary term: {{{}}} in {}",
+            self.term, self.location
+        )
+    }
+}
+impl std::error::Error for UnknownTerm {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+/// Abbreviations (`*[HTML]: HyperText Markup Language`) and glossary terms
+#[derive(Debug, Default)]
+pub struct Glossary {
+    /// Defined in the documents; marked up everywhere
+    abbreviations: BTreeMap<String, String>,
+    /// From the glossary file
+    terms: BTreeMap<String, String>,
+    /// Referred terms with `{term}`
+    used: Vec<String>,
+    unknown: Vec<UnknownTerm>,
+}
+
+/// `*[TERM]: DEFINITION` in the markdown (in any depth)
+pub fn definitions(mkd: &Markdown) -> Vec<(String, String)> {
+    let mut defs = vec![];
+    for block in mkd.iter() {
+        match block {
+            Block::Abbreviation(term, definition) => {
+                defs.push((term.to_string(), definition.to_string()))
+            }
+            Block::Div(_, children) => defs.extend(definitions(children)),
+            _ => {}
+        }
+    }
+    defs
+}
+
+impl Glossary {
+    /// Terms of a glossary file (`*[TERM]: DEFINITION` lines)
+    pub fn set_terms(&mut self, terms: Vec<(String, String)>) {
+        self.terms = terms.into_iter().collect();
+    }
+
+    pub fn has_terms(&self) -> bool {
+        !self.terms.is_empty()
+    }
+
+    pub fn define(&mut self, abbreviations: Vec<(String, String)>) {
+        self.abbreviations.extend(abbreviations);
+    }
+
+    /// Mark up the abbreviations in a plaintext (respecting word boundaries; raw HTML tags are skipped).
+    /// `in_tag` is whether a tag is open before the text.
+    pub fn abbreviate(&self, text: &str, in_tag: bool) -> String {
+        if self.abbreviations.is_empty() {
+            return text.to_string();
+        }
+        let mut html = String::new();
+        let mut in_tag = in_tag;
+        let mut i = 0;
+        while let Some(c) = text[i..].chars().next() {
+            let at_boundary = !text[..i]
+                .chars()
+                .last()
+                .is_some_and(|c| c.is_alphanumeric());
+            let next = text[i + c.len_utf8()..].chars().next();
+            if !in_tag && is_tag_start(c, next) {
+                in_tag = true;
+            } else if c == '>' {
+                in_tag = false;
+            } else if !in_tag && at_boundary {
+                let found = self
+                    .abbreviations
+                    .iter()
+                    .filter(|(term, _)| {
+                        text[i..].starts_with(term.as_str())
+                            && !text[i + term.len()..]
+                                .chars()
+                                .next()
+                                .is_some_and(|c| c.is_alphanumeric())
+                    })
+                    .max_by_key(|(term, _)| term.len());
+                if let Some((term, definition)) = found {
+                    html += &format!("<abbr title=\"{}\">{}</abbr>", encode(definition), term);
+                    i += term.len();
+                    continue;
+                }
+            }
+            html.push(c);
+            i += c.len_utf8();
+        }
+        html
+    }
+
+    /// Link to the glossary entry of `{term}`
+    pub fn refer(&mut self, term: &str, location: &str) -> String {
+        if self.terms.contains_key(term) || self.abbreviations.contains_key(term) {
+            if !self.used.iter().any(|t| t == term) {
+                self.used.push(term.to_string());
+            }
+            format!(
+                "<a class=\"glossary\" href=\"#{}\">{}</a>",
+                id(term),
+                encode(term)
+            )
+        } else {
+            if self.has_terms() {
+                self.unknown.push(UnknownTerm {
+                    term: term.to_string(),
+                    location: location.to_string(),
+                });
+            }
+            format!("{{{}}}", encode(term))
+        }
+    }
+
+    pub fn unknown(&self) -> Vec<UnknownTerm> {
+        self.unknown.clone()
+    }
+
+    /// `<dl>` of the referred terms in alphabetical order; `None` when nothing is referred
+    pub fn render(&self) -> Option<Html> {
+        if self.used.is_empty() {
+            return None;
+        }
+        let mut used = self.used.clone();
+        used.sort_by_key(|term| term.to_lowercase());
+        let items: Vec<String> = used
+            .iter()
+            .map(|term| {
+                let definition = self
+                    .terms
+                    .get(term)
+                    .or_else(|| self.abbreviations.get(term))
+                    .unwrap();
+                format!(
+                    "<dt id=\"{}\">{}</dt><dd>{}</dd>",
+                    id(term),
+                    encode(term),
+                    encode(definition)
+                )
+            })
+            .collect();
+        Some(leaf!("<dl class=\"glossary\">{}</dl>", items.join("")))
+    }
+}
+
+/// Placeholder of the glossary (`::: {#glossary}`)
+pub fn marker() -> Html {
+    leaf!("<!--unidoc:glossary-->")
+}
+
+fn id(term: &str) -> String {
+    format!("glossary-{}", Slug::Github.make(0, term, term))
+}
+
+fn encode(text: &str) -> String {
+    html_escape::encode_double_quoted_attribute(text).to_string()
+}
+
+#[cfg(test)]
+mod test_glossary {
+    use crate::glossary::*;
+
+    fn glossary() -> Glossary {
+        let mut glossary = Glossary::default();
+        glossary.define(vec![
+            (
+                String::from("HTML"),
+                String::from("HyperText Markup Language"),
+            ),
+            (String::from("HTML5"), String::from("HTML version 5")),
+            (String::from("C++"), String::from("A \"language\"")),
+        ]);
+        glossary.set_terms(vec![(
+            String::from("Slug"),
+            String::from("Id of a heading"),
+        )]);
+        glossary
+    }
+
+    #[test]
+    fn test_abbreviate() {
+        let glossary = glossary();
+        assert_eq!(
+            glossary.abbreviate("HTML,", false),
+            "<abbr title=\"HyperText Markup Language\">HTML</abbr>,"
+        );
+        assert_eq!(
+            glossary.abbreviate("HTML5", false),
+            "<abbr title=\"HTML version 5\">HTML5</abbr>"
+        );
+        assert_eq!(
+            glossary.abbreviate("C++", false),
+            "<abbr title=\"A &quot;language&quot;\">C++</abbr>"
+        );
+        assert_eq!(glossary.abbreviate("XHTML", false), "XHTML");
+        assert_eq!(
+            glossary.abbreviate("1<2 HTML", false),
+            "1<2 <abbr title=\"HyperText Markup Language\">HTML</abbr>"
+        );
+        assert_eq!(
+            glossary.abbreviate("<a title=\"HTML\">", false),
+            "<a title=\"HTML\">"
+        );
+        // in a tag of the preceding plaintexts, e.g. `<a title="HTML page">`
+        assert_eq!(
+            glossary.abbreviate("page\">HTML", true),
+            "page\"><abbr title=\"HyperText Markup Language\">HTML</abbr>"
+        );
+    }
+
+    #[test]
+    fn test_refer() {
+        let mut glossary = glossary();
+        assert_eq!(glossary.render(), None);
+        assert_eq!(
+            glossary.refer("Slug", "a.md"),
+            "<a class=\"glossary\" href=\"#glossary-slug\">Slug</a>"
+        );
+        glossary.refer("HTML", "a.md");
+        glossary.refer("Slug", "a.md");
+        assert_eq!(glossary.refer("none", "a.md"), "{none}");
+        assert_eq!(
+            glossary.unknown(),
+            vec![UnknownTerm {
+                term: String::from("none"),
+                location: String::from("a.md"),
+            }]
+        );
+        assert_eq!(
+            glossary.render(),
+            Some(leaf!(concat!(
+                "<dl class=\"glossary\">",
+                "<dt id=\"glossary-html\">HTML</dt><dd>HyperText Markup Language</dd>",
+                "<dt id=\"glossary-slug\">Slug</dt><dd>Id of a heading</dd>",
+                "</dl>"
+            )))
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use crate::entity::html::Html;
+use crate::{leaf, node};
+use std::collections::BTreeMap;
+use unicode_normalization::UnicodeNormalization;
+
+/// Placeholder of `[INDEX]`; replaced after all entries are collected
+pub fn marker() -> Html {
+    leaf!("<!--unidoc:index-->")
+}
+
+/// An occurrence of an index term, e.g. `parser!nom` at `#idx-3`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub term: String,
+    pub anchor: String,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    anchors: Vec<String>,
+    children: BTreeMap<(String, String), Node>,
+}
+
+/// Key for sorting; case and accents are ignored (`é` as `e`)
+fn sort_key(term: &str) -> String {
+    term.nfkd()
+        .filter(|c| !('\u{300}'..='\u{36F}').contains(c))
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Group of a term by its first letter; `#` for digits and symbols
+fn group(term: &str) -> String {
+    match sort_key(term).chars().next() {
+        Some(c) if c.is_alphabetic() => c.to_uppercase().collect(),
+        _ => String::from("#"),
+    }
+}
+
+/// Alphabetized index grouped by the first letters; `!` separates sub-entries
+pub fn render(entries: &[Entry]) -> Html {
+    let mut groups: BTreeMap<(String, String), BTreeMap<(String, String), Node>> = BTreeMap::new();
+    for entry in entries.iter() {
+        let path: Vec<&str> = entry
+            .term
+            .split('!')
+            .map(|t| t.trim())
+            .filter(|t| !t.is_empty())
+            .collect();
+        let Some((last, parents)) = path.split_last() else {
+            continue;
+        };
+        let letter = group(path[0]);
+        let mut children = groups.entry((sort_key(&letter), letter)).or_default();
+        for term in parents.iter() {
+            children = &mut children
+                .entry((sort_key(term), term.to_string()))
+                .or_default()
+                .children;
+        }
+        children
+            .entry((sort_key(last), last.to_string()))
+            .or_default()
+            .anchors
+            .push(entry.anchor.to_string());
+    }
+
+    fn items(nodes: &BTreeMap<(String, String), Node>) -> Html {
+        let mut ul = node!(leaf!("<ul>"), leaf!("</ul>"));
+        for ((_, term), node) in nodes.iter() {
+            let links: Vec<String> = node
+                .anchors
+                .iter()
+                .enumerate()
+                .map(|(i, anchor)| format!("<a href=\"#{}\">{}</a>", anchor, i + 1))
+                .collect();
+            let term = html_escape::encode_safe(term);
+            let label = if links.is_empty() {
+                term.to_string()
+            } else {
+                format!("{}, {}", term, links.join(", "))
+            };
+            if node.children.is_empty() {
+                ul.push(leaf!("<li>{}</li>", label));
+            } else {
+                ul.push(node!(leaf!("<li>{}", label), leaf!("</li>"); [items(&node.children)]));
+            }
+        }
+        ul
+    }
+
+    let mut html = node!(leaf!("<div class=\"index\">"), leaf!("</div>"));
+    for ((_, letter), nodes) in groups.iter() {
+        html.push(node!(
+            leaf!("<div class=\"index-group\">"),
+            leaf!("</div>");
+            [leaf!("<div class=\"index-letter\">{}</div>", letter), items(nodes)]
+        ));
+    }
+    html
+}
+
+#[cfg(test)]
+mod test_index {
+    use crate::entity::html::HtmlDoc;
+    use crate::index::*;
+
+    #[test]
+    fn test_render() {
+        let entry = |term: &str, anchor: &str| Entry {
+            term: term.to_string(),
+            anchor: anchor.to_string(),
+        };
+        let entries = vec![
+            entry("parser!nom", "idx-1"),
+            entry("Zebra", "idx-2"),
+            entry("éclair", "idx-3"),
+            entry("parser", "idx-4"),
+            entry("Eagle", "idx-5"),
+            entry("parser!nom", "idx-6"),
+            entry("2FA", "idx-7"),
+        ];
+        let html = HtmlDoc::new(String::new(), vec![render(&entries)]).show(true, 0);
+        assert_eq!(
+            html,
+            concat!(
+                "<div class=\"index\">",
+                "<div class=\"index-group\"><div class=\"index-letter\">#</div>",
+                "<ul><li>2FA, <a href=\"#idx-7\">1</a></li></ul></div>",
+                "<div class=\"index-group\"><div class=\"index-letter\">E</div>",
+                "<ul><li>Eagle, <a href=\"#idx-5\">1</a></li>",
+                "<li>éclair, <a href=\"#idx-3\">1</a></li></ul></div>",
+                "<div class=\"index-group\"><div class=\"index-letter\">P</div>",
+                "<ul><li>parser, <a href=\"#idx-4\">1</a>",
+                "<ul><li>nom, <a href=\"#idx-1\">1</a>, <a href=\"#idx-6\">2</a></li></ul>",
+                "</li></ul></div>",
+                "<div class=\"index-group\"><div class=\"index-letter\">Z</div>",
+                "<ul><li>Zebra, <a href=\"#idx-2\">1</a></li></ul></div>",
+                "</div>\n"
+            )
+        );
+    }
+}
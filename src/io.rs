@@ -9,7 +9,7 @@ pub fn read(input: &String) -> io::Result<String> {
         let mut handle = stdin.lock();
         handle.read_to_string(&mut content)?;
     } else {
-        let file = File::open(&input).unwrap();
+        let file = File::open(input).unwrap();
         let mut buf_reader = BufReader::new(file);
         buf_reader.read_to_string(&mut content)?;
     }
@@ -19,19 +19,26 @@ pub fn read(input: &String) -> io::Result<String> {
     Ok(content)
 }
 
-pub fn reads(inputs: &Vec<String>) -> io::Result<Vec<String>> {
-    inputs
-        .iter()
-        .map(|path| read(&path))
-        .collect::<Result<Vec<_>, _>>()
+pub fn reads(inputs: &[String]) -> io::Result<Vec<String>> {
+    inputs.iter().map(read).collect::<Result<Vec<_>, _>>()
 }
 
 pub fn write(output: &Option<String>, buf: &String) -> io::Result<()> {
     if let Some(output) = &output {
-        let mut file = File::create(&output)?;
+        let mut file = File::create(output)?;
         write!(file, "{}", buf)?;
     } else {
         print!("{}", buf);
     }
     Ok(())
 }
+
+pub fn write_bytes(output: &Option<String>, buf: &[u8]) -> io::Result<()> {
+    if let Some(output) = &output {
+        let mut file = File::create(output)?;
+        file.write_all(buf)?;
+    } else {
+        io::stdout().write_all(buf)?;
+    }
+    Ok(())
+}
@@ -1,12 +1,26 @@
+pub mod bibliography;
 pub mod blogcard;
+pub mod condition;
+pub mod crossref;
 pub mod entity;
 pub mod executor;
+pub mod glossary;
+pub mod index;
 pub mod io;
+pub mod notebook;
 pub mod parser;
+pub mod slides;
+pub mod slug;
+pub mod smart;
 pub mod template;
+pub mod toc;
 pub mod translator;
+pub mod variable;
 pub mod webpage;
+pub mod wikilink;
+pub mod writer;
 
+use crate::bibliography::Bibliography;
 use crate::template::Context;
 use crate::translator::Translator;
 use std::collections::HashMap;
@@ -15,6 +29,8 @@ use std::path::Path;
 use structopt::StructOpt;
 
 use crate::entity::html::HtmlDoc;
+use crate::entity::markdown::{Block, FrontMatter, Markdown};
+use crate::writer::Writer;
 
 #[derive(Debug, StructOpt)]
 struct Opt {
@@ -44,40 +60,394 @@ struct Opt {
     pub template: Option<String>,
     #[structopt(short = "V", long = "variable", help = "-V KEY:VALUE")]
     pub variable: Vec<String>,
+    #[structopt(long = "toc", help = "Insert the table of contents")]
+    pub toc: bool,
+    #[structopt(
+        long = "toc-depth",
+        default_value = "1-3",
+        help = "Heading levels in the table of contents (MIN-MAX or MAX)"
+    )]
+    pub toc_depth: String,
+    #[structopt(long = "number-sections", help = "Number the sections (h2, h3, ...)")]
+    pub number_sections: bool,
+    #[structopt(
+        long = "slug",
+        default_value = "unidoc-legacy",
+        possible_values = &["github", "unidoc-legacy", "ascii"],
+        help = "How heading ids are made"
+    )]
+    pub slug: slug::Slug,
+    #[structopt(long = "smart", help = "Typographic quotes, dashes and ellipses")]
+    pub smart: bool,
+    #[structopt(
+        long = "glossary",
+        help = "Glossary file of `*[TERM]: DEFINITION` lines"
+    )]
+    pub glossary: Option<String>,
+    #[structopt(
+        long = "abbr-skip",
+        use_delimiter = true,
+        possible_values = &["links", "headings"],
+        help = "Abbreviations are not marked up in links and/or headings"
+    )]
+    pub abbr_skip: Vec<String>,
+    #[structopt(long = "bibliography", help = "BibTeX (.bib) or CSL-JSON (.json) file")]
+    pub bibliography: Option<String>,
+    #[structopt(
+        long = "citation-style",
+        possible_values = &["author-year", "numeric"],
+        help = "Citation style (default: author-year)"
+    )]
+    pub citation_style: Option<bibliography::Style>,
+    #[structopt(
+        long = "strict",
+        help = "Undefined ${variables}, dangling @references, unknown citations and glossary terms are errors"
+    )]
+    pub strict: bool,
+    #[structopt(
+        long = "to",
+        default_value = "html",
+        possible_values = &["docx", "epub", "gemtext", "html", "ipynb", "json", "latex", "man", "markdown", "plain", "slides"],
+        help = "Output format"
+    )]
+    pub to: writer::Format,
+    #[structopt(
+        long = "from",
+        default_value = "markdown",
+        possible_values = &["markdown", "json"],
+        help = "Input format"
+    )]
+    pub from: writer::Format,
+    #[structopt(
+        long = "columns",
+        default_value = "72",
+        help = "Line width of the plain text"
+    )]
+    pub columns: usize,
+    #[structopt(long = "ascii", help = "ASCII tables in the plain text and gemtext")]
+    pub ascii: bool,
+    #[structopt(
+        long = "slide-level",
+        default_value = "2",
+        help = "Headings of this level (or upper) start slides, as well as `---`"
+    )]
+    pub slide_level: usize,
+    #[structopt(
+        long = "epub-chapter-level",
+        default_value = "2",
+        help = "Headings of this level (or upper) start the chapters of the EPUB"
+    )]
+    pub epub_chapter_level: usize,
+    #[structopt(long = "epub-cover-image", help = "Cover image of the EPUB")]
+    pub epub_cover_image: Option<String>,
+    #[structopt(
+        long = "reference-doc",
+        help = "DOCX of which the styles are used for --to docx"
+    )]
+    pub reference_doc: Option<String>,
+    #[structopt(
+        long = "ipynb-language",
+        help = "Code blocks of this language are the code cells (default: `kernel-language` or python)"
+    )]
+    pub ipynb_language: Option<String>,
+    #[structopt(
+        long = "ipynb-outputs",
+        help = "Run the executors (@bash etc.) of the code cells and store the outputs"
+    )]
+    pub ipynb_outputs: bool,
     #[structopt(name = "input", default_value = "-")]
     pub input: Vec<String>,
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
 }
 
-fn eval(input: &String, debug: bool) -> Result<HtmlDoc, Box<dyn Error>> {
-    if debug {
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Reformat the markdown files in place (or stdin to stdout)
+    Fmt {
+        #[structopt(long = "check", help = "Exit with 1 if any files would be reformatted")]
+        check: bool,
+        #[structopt(name = "input", default_value = "-")]
+        input: Vec<String>,
+    },
+}
+
+/// Reformat the files in place (or stdin to stdout)
+fn fmt(input: &[String], check: bool) -> Result<(), Box<dyn Error>> {
+    let mut unformatted = false;
+    for input in input.iter() {
+        let content = io::read(input)?;
+        let formatted =
+            writer::markdown::format(&content).map_err(|err| format!("{} in {}", err, input))?;
+        if check {
+            if formatted != content {
+                eprintln!("Would reformat: {}", input);
+                unformatted = true;
+            }
+        } else if input == "-" {
+            io::write(&None, &formatted)?;
+        } else if formatted != content {
+            io::write(&Some(input.to_string()), &formatted)?;
+        }
+    }
+    if unformatted {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+type Variables = HashMap<String, String>;
+
+/// Markdown of an input with the conditional blocks and variables evaluated
+fn read(input: &String, opt: &Opt) -> Result<(Markdown, FrontMatter, Variables), Box<dyn Error>> {
+    if opt.debug {
         eprintln!(">>> Reading {:?}", input);
     }
-    let filedir: Option<String> = {
-        Path::new(&input)
-            .parent()
-            .map(|path| String::from(path.to_str().unwrap()))
+    let content = io::read(input)?;
+    // -V overrides the front matter
+    let variables_of = |front_matter: &FrontMatter| {
+        let mut variable: Variables = front_matter.iter().cloned().collect();
+        variable.extend(variables(opt));
+        variable
     };
-    if debug {
-        eprintln!(">>> filedir = {:?}", &filedir);
+    let (front_matter, mkd, variable) = match opt.from {
+        writer::Format::Json => {
+            let doc =
+                writer::json::read(&content).map_err(|err| format!("{} in {}", err, input))?;
+            let variable = variables_of(&doc.meta);
+            (doc.meta, doc.blocks, variable)
+        }
+        _ => {
+            let (front_matter, content) = parser::front_matter(&content);
+            let variable = variables_of(&front_matter);
+            let content = variable::substitute(content, &variable);
+            (front_matter, parser::markdown(&content)?, variable)
+        }
+    };
+    if opt.debug {
+        eprintln!(">>> front_matter = {:?}", &front_matter);
     }
-    let content = io::read(&input)?;
-    let mkd = parser::markdown(&content)?;
-    if debug {
+    let mkd = condition::filter(&mkd, &variable)?;
+    let mkd = variable::interpolate(&mkd, &variable, opt.strict)?;
+    if opt.debug {
         eprintln!(">>> markdown = {:?}", &mkd);
     }
-    let tr = Translator::new(filedir);
+    Ok((mkd, front_matter, variable))
+}
+
+fn filedir(input: &str) -> Option<String> {
+    Path::new(input)
+        .parent()
+        .map(|path| String::from(path.to_str().unwrap()))
+}
+
+fn eval(
+    input: &String,
+    opt: &Opt,
+    root: &Translator,
+) -> Result<(HtmlDoc, FrontMatter), Box<dyn Error>> {
+    let (mkd, front_matter, variable) = read(input, opt)?;
+    let filedir = filedir(input);
+    if opt.debug {
+        eprintln!(">>> filedir = {:?}", &filedir);
+    }
+    // --bibliography overrides the front matter
+    if !root.has_bibliography() {
+        if let Some((_, path)) = front_matter.iter().find(|(k, _)| k == "bibliography") {
+            let path = Path::new(filedir.as_deref().unwrap_or("")).join(path);
+            let style = match front_matter.iter().find(|(k, _)| k == "citation-style") {
+                Some((_, style)) if opt.citation_style.is_none() => style.parse()?,
+                _ => opt.citation_style.unwrap_or_default(),
+            };
+            root.set_bibliography(Bibliography::load(path.to_str().unwrap(), style)?);
+        }
+    }
+    // --glossary overrides the front matter
+    if !root.has_glossary_terms() {
+        if let Some((_, path)) = front_matter.iter().find(|(k, _)| k == "glossary") {
+            let path = Path::new(filedir.as_deref().unwrap_or("")).join(path);
+            root.set_glossary_terms(glossary_terms(path.to_str().unwrap())?);
+        }
+    }
+    let tr = root
+        .for_file(filedir)
+        .with_source(input)
+        .with_variables(variable, opt.strict);
+    let mkd = match opt.to {
+        writer::Format::Slides => slides::split(&mkd, opt.slide_level),
+        _ => mkd,
+    };
     let doc = tr.markdown(&mkd);
-    if debug {
+    if opt.debug {
         eprintln!(">>> htmldoc = {:?}", &doc);
     }
-    Ok(doc)
+    Ok((doc, front_matter))
 }
 
-fn context(title: String, body: String, opt: &Opt) -> Result<Context, Box<dyn Error>> {
-    let headers = io::reads(&opt.include_in_header)?;
-    let befores = io::reads(&opt.include_before_body)?;
-    let afters = io::reads(&opt.include_after_body)?;
+/// `--to json`; all inputs are one document, and the imports are kept as they are
+fn write_json(opt: &Opt) -> Result<(), Box<dyn Error>> {
+    let mut doc = writer::json::Document::new(vec![], vec![]);
+    for input in opt.input.iter() {
+        let (mut mkd, mut front_matter, _) = read(input, opt)?;
+        doc.blocks.append(&mut mkd);
+        doc.meta.append(&mut front_matter);
+    }
+    io::write(&opt.output, &writer::json::write(&doc))?;
+    Ok(())
+}
+
+/// All inputs as one document whose local images are embedded (EPUB and DOCX);
+/// the imports are expanded, and the image paths are rebased to the current directory
+fn read_embedded(opt: &Opt, root: &Translator) -> Result<(Markdown, Variables), Box<dyn Error>> {
+    let mut mkd = vec![];
+    let mut front_matter = vec![];
+    for input in opt.input.iter() {
+        let (m, mut f, variable) = read(input, opt)?;
+        let dir = filedir(input);
+        // imported images are rebased to the input, then to the current directory
+        let tr = root
+            .for_file(dir.clone())
+            .with_outdir(dir.clone())
+            .with_source(input)
+            .with_variables(variable, opt.strict);
+        let m = tr.expand_imports(&m);
+        mkd.append(&mut writer::package::rebase(&m, dir.as_deref()));
+        front_matter.append(&mut f);
+    }
+    // the front matter of the first input has priority
+    let mut variable: Variables = front_matter.iter().rev().cloned().collect();
+    variable.extend(variables(opt));
+    Ok((mkd, variable))
+}
+
+/// `--to epub`; local images and `--css` files are embedded
+fn write_epub(opt: &Opt, root: &Translator) -> Result<(), Box<dyn Error>> {
+    let (mkd, variable) = read_embedded(opt, root)?;
+    let mut book = writer::epub::Book::new(&variable, &mkd);
+    book.chapter_level = opt.epub_chapter_level;
+    if let Some(path) = &opt.epub_cover_image {
+        book.cover = Some(path.to_string());
+    }
+    book.css = opt
+        .css
+        .iter()
+        .filter(|css| !css.contains("://"))
+        .cloned()
+        .collect();
+    let tr = root.clone().with_execute(false);
+    io::write_bytes(&opt.output, &writer::epub::build(&book, &mkd, &tr)?)?;
+    warnings(&tr, opt)
+}
+
+/// Unknown citations and terms, dangling references and broken links; errors with `--strict`
+fn warnings(root: &Translator, opt: &Opt) -> Result<(), Box<dyn Error>> {
+    let warnings: Vec<Box<dyn Error>> = root
+        .unknown_citations()
+        .into_iter()
+        .map(|e| Box::new(e) as Box<dyn Error>)
+        .chain(root.unknown_terms().into_iter().map(|e| Box::new(e) as _))
+        .chain(
+            root.dangling_references()
+                .into_iter()
+                .map(|e| Box::new(e) as _),
+        )
+        .chain(root.broken_links().into_iter().map(|e| Box::new(e) as _))
+        .collect();
+    for warning in warnings {
+        if opt.strict {
+            return Err(warning);
+        }
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(())
+}
+
+/// `--to docx`; local images are embedded
+fn write_docx(opt: &Opt, root: &Translator) -> Result<(), Box<dyn Error>> {
+    let (mkd, variable) = read_embedded(opt, root)?;
+    let mut docx = writer::docx::Docx::new(&variable, &mkd);
+    docx.reference = opt.reference_doc.clone();
+    if let Some(path) = &opt.glossary {
+        docx.glossary = glossary_terms(path)?;
+    }
+    io::write_bytes(&opt.output, &writer::docx::build(&docx, &mkd)?)?;
+    Ok(())
+}
+
+/// `--to ipynb`; all inputs are one notebook
+fn write_ipynb(opt: &Opt, root: &Translator) -> Result<(), Box<dyn Error>> {
+    let mut mkd = vec![];
+    let mut front_matter = vec![];
+    for input in opt.input.iter() {
+        let (m, mut f, variable) = read(input, opt)?;
+        let tr = root
+            .for_file(filedir(input))
+            .with_source(input)
+            .with_variables(variable, opt.strict);
+        mkd.append(&mut tr.expand_imports(&m));
+        front_matter.append(&mut f);
+    }
+    // the front matter of the first input has priority
+    let mut variable: Variables = front_matter.iter().rev().cloned().collect();
+    variable.extend(variables(opt));
+    let kernel = match &opt.ipynb_language {
+        Some(language) => writer::ipynb::Kernel::with_language(&variable, language),
+        None => writer::ipynb::Kernel::new(&variable),
+    };
+    let notebook = writer::ipynb::build(&kernel, &mkd, opt.ipynb_outputs);
+    io::write(&opt.output, &writer::ipynb::write(&notebook))?;
+    Ok(())
+}
 
+/// Documents other than HTML (`--to`)
+fn write(opt: &Opt, root: &Translator, writer: &dyn Writer) -> Result<(), Box<dyn Error>> {
+    let mut title = None;
+    let mut bodies = vec![];
+    let mut front_matter = vec![];
+    for input in opt.input.iter() {
+        let (mkd, mut f, variable) = read(input, opt)?;
+        let tr = root
+            .for_file(filedir(input))
+            .with_source(input)
+            .with_variables(variable.clone(), opt.strict);
+        let mkd = tr.expand_imports(&mkd);
+        if title.is_none() {
+            title = mkd.iter().find_map(|block| match block {
+                Block::Heading(_, text, _) => Some(writer::plain(text)),
+                _ => None,
+            });
+        }
+        bodies.push(writer.write_with_variables(&mkd, &variable));
+        front_matter.append(&mut f);
+    }
+    let last = writer.finish();
+    if !last.is_empty() {
+        bodies.push(last);
+    }
+    let body = bodies.join("\n");
+    let output = if opt.standalone || opt.template.is_some() {
+        let title = title.unwrap_or_default();
+        let ctx = context(title, body, String::new(), vec![], &front_matter, opt)?;
+        match &opt.template {
+            Some(path) => template::custom(&io::read(path)?, ctx)?,
+            None => writer.standalone(ctx)?,
+        }
+    } else {
+        body
+    };
+    io::write(&opt.output, &output)?;
+    Ok(())
+}
+
+/// `*[TERM]: DEFINITION` in a glossary file
+fn glossary_terms(path: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let content = io::read(&path.to_string())?;
+    let mkd = parser::markdown(&content)?;
+    Ok(glossary::definitions(&mkd))
+}
+
+/// -V KEY:VALUE
+fn variables(opt: &Opt) -> HashMap<String, String> {
     fn split(s: &String) -> (String, String) {
         if let Some(i) = s.find(':') {
             (s[0..i].to_string(), s[i + 1..].to_string())
@@ -85,7 +455,24 @@ fn context(title: String, body: String, opt: &Opt) -> Result<Context, Box<dyn Er
             (s.to_string(), s.to_string())
         }
     }
-    let variable: HashMap<String, String> = opt.variable.iter().map(|s| split(s)).collect();
+    opt.variable.iter().map(split).collect()
+}
+
+fn context(
+    title: String,
+    body: String,
+    toc: String,
+    headings: Vec<toc::Heading>,
+    front_matter: &FrontMatter,
+    opt: &Opt,
+) -> Result<Context, Box<dyn Error>> {
+    let headers = io::reads(&opt.include_in_header)?;
+    let befores = io::reads(&opt.include_before_body)?;
+    let afters = io::reads(&opt.include_after_body)?;
+
+    // the front matter of the first input has priority
+    let mut variable: HashMap<String, String> = front_matter.iter().rev().cloned().collect();
+    variable.extend(variables(opt));
     if opt.debug {
         eprintln!(">>> variable = {:?}", &variable);
     }
@@ -102,6 +489,8 @@ fn context(title: String, body: String, opt: &Opt) -> Result<Context, Box<dyn Er
         befores,
         afters,
         variable,
+        toc,
+        headings,
     );
     Ok(ctx)
 }
@@ -121,6 +510,10 @@ fn option_check(opt: &Opt) -> Result<(), Box<dyn Error>> {
             "Invalid --mathjax; See https://docs.mathjax.org/en/latest/web/components/combined.html"
         );
     }
+    assert!(
+        toc::parse_depth(&opt.toc_depth).is_some(),
+        "Invalid --toc-depth; MIN-MAX or MAX (1 <= MIN <= MAX <= 6)"
+    );
     Ok(())
 }
 
@@ -129,26 +522,108 @@ fn main() -> Result<(), Box<dyn Error>> {
     if opt.debug {
         eprintln!(">>> opt = {:?}", &opt);
     }
+    if let Some(Command::Fmt { input, check }) = &opt.command {
+        return fmt(input, *check);
+    }
 
     option_check(&opt)?;
 
+    // relative urls in imported documents are rebased to the output
+    let outdir: Option<String> = opt.output.as_ref().map(|output| {
+        Path::new(output)
+            .parent()
+            .map(|path| String::from(path.to_str().unwrap()))
+            .unwrap_or_default()
+    });
+    let root = Translator::new(None)
+        .with_outdir(outdir)
+        .with_number_sections(opt.number_sections)
+        .with_slug(opt.slug)
+        .with_smart(opt.smart)
+        .with_abbr_skip(opt.abbr_skip.clone());
+    if let Some(path) = &opt.glossary {
+        root.set_glossary_terms(glossary_terms(path)?);
+    }
+    if let Some(path) = &opt.bibliography {
+        let style = opt.citation_style.unwrap_or_default();
+        root.set_bibliography(Bibliography::load(path, style)?);
+    }
+
+    let options = writer::Options {
+        number_sections: opt.number_sections,
+        columns: opt.columns,
+        ascii: opt.ascii,
+        bibliography: match &opt.bibliography {
+            Some(path) => bibliography::read(path)?,
+            None => vec![],
+        },
+        citation_style: opt.citation_style.unwrap_or_default(),
+    };
+    match opt.to {
+        writer::Format::Json => return write_json(&opt),
+        writer::Format::Docx => return write_docx(&opt, &root),
+        writer::Format::Epub => return write_epub(&opt, &root),
+        writer::Format::Ipynb => return write_ipynb(&opt, &root),
+        _ => {}
+    }
+    if let Some(writer) = writer::new(opt.to, &options) {
+        return write(&opt, &root, writer.as_ref());
+    }
+
     // evaluating & flatten markdowns
-    let mut doc = eval(&opt.input[0], opt.debug)?;
+    let (mut doc, mut front_matter) = eval(&opt.input[0], &opt, &root)?;
     for i in 1..opt.input.len() {
-        let mut d = eval(&opt.input[i], opt.debug)?;
+        let (mut d, mut f) = eval(&opt.input[i], &opt, &root)?;
         doc.append(&mut d);
+        front_matter.append(&mut f);
+    }
+
+    // references at `::: {#refs}` or the end
+    if let Some(references) = root.references() {
+        if !doc.replace(&bibliography::marker(), &references) {
+            doc.append(&mut HtmlDoc::new(String::new(), vec![references]));
+        }
     }
 
+    // glossary at `::: {#glossary}` or the end
+    if let Some(glossary) = root.glossary() {
+        if !doc.replace(&glossary::marker(), &glossary) {
+            doc.append(&mut HtmlDoc::new(String::new(), vec![glossary]));
+        }
+    }
+
+    // index at `[INDEX]`
+    doc.replace(&index::marker(), &root.index());
+
+    // table of contents
+    let headings = root.headings();
+    let toc = toc::render(&headings, toc::parse_depth(&opt.toc_depth).unwrap());
+    doc.replace(&toc::marker(), &toc);
+    if opt.toc {
+        doc.prepend(toc.clone());
+    }
+    let mut toc = HtmlDoc::new(String::new(), vec![toc]);
+
+    // cross-references
+    root.resolve(&mut doc);
+    root.resolve(&mut toc);
+    let toc = toc.show(opt.compact, opt.indent);
+    warnings(&root, &opt)?;
+
     // show
     let body = doc.show(opt.compact, opt.indent);
-    let html = if opt.standalone || opt.template.is_some() {
-        let ctx = context(doc.title, body, &opt)?;
+    // slides are always standalone
+    let slides = opt.to == writer::Format::Slides;
+    let html = if opt.standalone || opt.template.is_some() || slides {
+        let ctx = context(doc.title, body, toc, headings, &front_matter, &opt)?;
         if let Some(template_file_path) = opt.template {
             if opt.debug {
                 eprintln!("Standalone with custom template: {:?}", template_file_path);
             }
             let htmltemplate = io::read(&template_file_path)?;
             template::custom(&htmltemplate, ctx)?
+        } else if slides {
+            slides::render(ctx)?
         } else {
             if opt.debug {
                 eprintln!("Standalone with simple template");
@@ -241,6 +716,15 @@ mod test_main {
         );
     }
 
+    #[test]
+    fn test_div() {
+        assert_convert!(compact;
+            "::: {#n .note if=x}\na\n:::\n",
+            "",
+            "<div id=\"n\" class=\"note\"><p>a</p></div>\n"
+        );
+    }
+
     #[test]
     fn test_emoji() {
         assert_convert!(compact; "# :joy:\n", "joy", "<h1 class=\"title\" id=\"1-%F0%9F%98%82\">\u{1f602}</h1>\n");
@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Jupyter Notebook (nbformat 4)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notebook {
+    pub cells: Vec<Cell>,
+    #[serde(default)]
+    pub metadata: Value,
+    #[serde(default = "default_nbformat")]
+    pub nbformat: u32,
+    #[serde(default)]
+    pub nbformat_minor: u32,
+}
+
+fn default_nbformat() -> u32 {
+    4
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "cell_type", rename_all = "lowercase")]
+pub enum Cell {
+    Markdown {
+        #[serde(default)]
+        metadata: Value,
+        source: Source,
+    },
+    Code {
+        #[serde(default)]
+        metadata: Value,
+        source: Source,
+        #[serde(default)]
+        outputs: Vec<Output>,
+        #[serde(default)]
+        execution_count: Option<u64>,
+    },
+    Raw {
+        #[serde(default)]
+        metadata: Value,
+        source: Source,
+    },
+}
+
+/// Multiline string; a string or an array of lines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Source {
+    Text(String),
+    Lines(Vec<String>),
+}
+
+impl Source {
+    pub fn text(&self) -> String {
+        match self {
+            Source::Text(text) => text.to_string(),
+            Source::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "output_type", rename_all = "snake_case")]
+pub enum Output {
+    Stream {
+        name: String,
+        text: Source,
+    },
+    DisplayData {
+        data: BTreeMap<String, Value>,
+        #[serde(default)]
+        metadata: Value,
+    },
+    ExecuteResult {
+        data: BTreeMap<String, Value>,
+        #[serde(default)]
+        metadata: Value,
+        #[serde(default)]
+        execution_count: Option<u64>,
+    },
+    Error {
+        ename: String,
+        evalue: String,
+        #[serde(default)]
+        traceback: Vec<String>,
+    },
+}
+
+impl Notebook {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// The programming language of the kernel
+    pub fn language(&self) -> Option<String> {
+        let kernelspec = self.metadata.pointer("/kernelspec/language");
+        let language_info = self.metadata.pointer("/language_info/name");
+        kernelspec
+            .or(language_info)
+            .and_then(|lang| lang.as_str())
+            .map(|lang| lang.to_string())
+    }
+}
+
+/// Value of a mime-bundle; a string or an array of lines
+pub fn mime_text(data: &BTreeMap<String, Value>, mime: &str) -> Option<String> {
+    match data.get(mime)? {
+        Value::String(text) => Some(text.to_string()),
+        Value::Array(lines) => Some(
+            lines
+                .iter()
+                .filter_map(|line| line.as_str())
+                .collect::<Vec<_>>()
+                .concat(),
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test_notebook {
+    use crate::notebook::*;
+
+    #[test]
+    fn test_parse() {
+        let json = r##"{
+            "cells": [
+                {"cell_type": "markdown", "metadata": {}, "source": ["# Title\n", "text"]},
+                {"cell_type": "code", "execution_count": 1, "metadata": {}, "source": "print(1)",
+                 "outputs": [{"output_type": "stream", "name": "stdout", "text": ["1\n"]}]}
+            ],
+            "metadata": {"kernelspec": {"name": "python3", "language": "python"}},
+            "nbformat": 4,
+            "nbformat_minor": 5
+        }"##;
+        let nb = Notebook::from_json(json).unwrap();
+        assert_eq!(nb.language(), Some(String::from("python")));
+        assert_eq!(nb.cells.len(), 2);
+        assert_eq!(
+            nb.cells[0],
+            Cell::Markdown {
+                metadata: serde_json::json!({}),
+                source: Source::Lines(vec![String::from("# Title\n"), String::from("text")]),
+            }
+        );
+        if let Cell::Code {
+            source, outputs, ..
+        } = &nb.cells[1]
+        {
+            assert_eq!(source.text(), "print(1)");
+            assert_eq!(
+                outputs[0],
+                Output::Stream {
+                    name: String::from("stdout"),
+                    text: Source::Lines(vec![String::from("1\n")]),
+                }
+            );
+        } else {
+            panic!("code cell expected");
+        }
+    }
+
+    #[test]
+    fn test_mime_text() {
+        let mut data = BTreeMap::new();
+        data.insert(String::from("text/plain"), serde_json::json!(["a\n", "b"]));
+        data.insert(String::from("image/png"), serde_json::json!("iVBOR"));
+        assert_eq!(mime_text(&data, "text/plain"), Some(String::from("a\nb")));
+        assert_eq!(mime_text(&data, "image/png"), Some(String::from("iVBOR")));
+        assert_eq!(mime_text(&data, "text/html"), None);
+    }
+}
@@ -1,15 +1,17 @@
 use crate::entity::markdown::{
-    Align, Block, Inline, List, ListItem, ListOrderType, Markdown, Text,
+    Align, Attributes, Block, Citation, FrontMatter, Inline, List, ListItem, ListOrderType,
+    Markdown, Text,
 };
+use crate::translator::is_cjk;
 use nom::branch::alt;
 use nom::bytes::complete::{
     is_not, tag, take, take_until, take_while, take_while1, take_while_m_n,
 };
 use nom::character::complete::{
-    alpha1, digit1, line_ending, multispace0, not_line_ending, space0, space1,
+    alpha1, digit1, line_ending, multispace0, not_line_ending, one_of, space0, space1,
 };
-use nom::combinator::{map, map_parser, not, opt, peek, success};
-use nom::multi::{many0, many1};
+use nom::combinator::{all_consuming, map, map_parser, not, opt, peek, recognize, success, verify};
+use nom::multi::{many0, many1, separated_list1};
 use nom::sequence::{delimited, pair, preceded, terminated, tuple};
 use nom::IResult;
 
@@ -45,21 +47,68 @@ pub fn markdown(input: &str) -> Result<Markdown, ParseError> {
     }
 }
 
-fn parse_markdown(input: &str) -> ParseResult<Markdown> {
+/// Split the front matter (`key: value` lines between `---`) at the beginning of the document.
+/// Any other line, e.g. a heading, makes the `---` a horizontal rule.
+pub fn front_matter(input: &str) -> (FrontMatter, &str) {
+    let mut meta = vec![];
+    let mut lines = input.split_inclusive('\n');
+    let mut offset = match lines.next() {
+        Some(line) if line.trim_end() == "---" => line.len(),
+        _ => return (vec![], input),
+    };
+    for line in lines {
+        offset += line.len();
+        let line = line.trim_end();
+        if (line == "---" || line == "...") && !meta.is_empty() {
+            return (meta, &input[offset..]);
+        }
+        if line.is_empty() {
+            continue;
+        }
+        match line.find(':') {
+            Some(i)
+                if !line[..i].is_empty()
+                    && !line.starts_with('#')
+                    && !line[..i].contains(char::is_whitespace) =>
+            {
+                let key = line[..i].to_string();
+                let value = line[i + 1..].trim();
+                let value = if value.len() >= 2
+                    && ((value.starts_with('"') && value.ends_with('"'))
+                        || (value.starts_with('\'') && value.ends_with('\'')))
+                {
+                    &value[1..value.len() - 1]
+                } else {
+                    value
+                };
+                meta.push((key, value.to_string()));
+            }
+            _ => return (vec![], input),
+        }
+    }
+    (vec![], input)
+}
+
+fn parse_markdown(input: &str) -> ParseResult<'_, Markdown> {
     let parse_blocks = many0(preceded(multispace0, parse_block));
     let mut parse_all = terminated(parse_blocks, multispace0);
     parse_all(input)
 }
 
-fn parse_block(input: &str) -> ParseResult<Block> {
+fn parse_block(input: &str) -> ParseResult<'_, Block> {
     let parse_hr = map(preceded(tag("---"), line_ending), |_| Block::HorizontalRule);
 
-    let parse_pagetitle = map(preceded(pair(tag("%"), space0), parse_text_line), |text| {
-        Block::Heading(1, text)
-    });
+    let parse_pagetitle = map(
+        preceded(pair(tag("%"), space0), parse_heading_line),
+        |(text, attributes)| Block::Heading(1, text, attributes),
+    );
     let parse_heading = map(
-        tuple((take_while_m_n(1, 6, |c| c == '#'), space1, parse_text_line)),
-        |(hashes, _, text)| Block::Heading(hashes.len(), text),
+        tuple((
+            take_while_m_n(1, 6, |c| c == '#'),
+            space1,
+            parse_heading_line,
+        )),
+        |(hashes, _, (text, attributes))| Block::Heading(hashes.len(), text, attributes),
     );
 
     let parse_code = map(
@@ -76,11 +125,11 @@ fn parse_block(input: &str) -> ParseResult<Block> {
         },
     );
 
-    let parse_listblock = map(parse_list(0), |list| Block::ListBlock(list));
+    let parse_listblock = map(parse_list(0), Block::ListBlock);
 
     let parse_paragraph = map(
         terminated(many1(parse_paragraph_line), opt(line_ending)),
-        |texts: Vec<Vec<Inline>>| Block::Paragraph(texts.into_iter().flatten().collect()),
+        |texts: Vec<Vec<Inline>>| Block::Paragraph(join_lines(texts)),
     );
 
     let quoting = pair(tag(">"), space0);
@@ -89,7 +138,7 @@ fn parse_block(input: &str) -> ParseResult<Block> {
             many1(preceded(quoting, parse_text_line)),
             alt((line_ending, success(""))),
         ),
-        |texts: Vec<Vec<Inline>>| Block::Quoted(texts.into_iter().flatten().collect()),
+        |texts: Vec<Vec<Inline>>| Block::Quoted(join_lines(texts)),
     );
 
     let parse_import = map(
@@ -123,29 +172,170 @@ fn parse_block(input: &str) -> ParseResult<Block> {
     );
 
     let parse_mathjax = map(
-        terminated(delimited(tag("$$"), parse_tex, tag("$$")), line_ending),
-        |tex| Block::MathJax(tex.to_string()),
+        terminated(
+            pair(
+                delimited(tag("$$"), parse_tex, tag("$$")),
+                opt(preceded(space0, parse_attributes)),
+            ),
+            pair(space0, line_ending),
+        ),
+        |(tex, attributes)| match attributes {
+            Some(attributes) => Block::Labeled(
+                Box::new(Block::MathJax(tex.to_string())),
+                vec![],
+                attributes,
+            ),
+            None => Block::MathJax(tex.to_string()),
+        },
+    );
+
+    let parse_toc = map(tuple((tag("[TOC]"), space0, line_ending)), |_| Block::Toc);
+    let parse_index = map(tuple((tag("[INDEX]"), space0, line_ending)), |_| {
+        Block::Index
+    });
+
+    let parse_abbreviation = map(
+        terminated(
+            pair(
+                delimited(tag("*["), is_not("]\n"), pair(tag("]:"), space0)),
+                not_line_ending,
+            ),
+            line_ending,
+        ),
+        |(term, definition): (&str, &str)| {
+            Block::Abbreviation(term.to_string(), definition.trim().to_string())
+        },
     );
 
     alt((
         parse_hr,
+        parse_div,
         parse_pagetitle,
         parse_heading,
-        parse_code,
+        parse_captioned(parse_code),
+        parse_abbreviation,
         parse_listblock,
-        parse_table,
+        parse_captioned(parse_table),
         parse_quoted,
         parse_import,
         parse_code_import,
         parse_hyperlink,
         parse_mathjax,
+        parse_toc,
+        parse_index,
+        parse_figure,
         parse_paragraph,
     ))(input)
 }
 
-fn parse_table(input: &str) -> ParseResult<Block> {
+/// BLOCK NEWLINE `: CAPTION {#label}` NEWLINE
+fn parse_captioned<'a, P>(parse: P) -> impl FnMut(&'a str) -> ParseResult<'a, Block>
+where
+    P: FnMut(&'a str) -> ParseResult<'a, Block>,
+{
+    map(
+        pair(
+            parse,
+            opt(preceded(pair(tag(":"), space1), parse_heading_line)),
+        ),
+        |(block, caption)| match caption {
+            Some((caption, attributes)) => Block::Labeled(Box::new(block), caption, attributes),
+            None => block,
+        },
+    )
+}
+
+/// `![CAPTION](URL){#fig:label}` NEWLINE
+fn parse_figure(input: &str) -> ParseResult<'_, Block> {
+    map(
+        terminated(
+            tuple((
+                delimited(tag("!["), take_until("]"), tag("]")),
+                delimited(tag("("), is_not(")"), tag(")")),
+                parse_attributes,
+            )),
+            pair(space0, line_ending),
+        ),
+        |(caption, url, attributes): (&str, &str, Attributes)| {
+            let image = Inline::Image(caption.to_string(), url.to_string());
+            let caption = parse_text(caption).map(|(_, t)| t).unwrap_or_default();
+            Block::Labeled(Box::new(Block::Paragraph(vec![image])), caption, attributes)
+        },
+    )(input)
+}
+
+/// ::: {ATTRIBUTES} NEWLINE BLOCKS ::: NEWLINE
+fn parse_div(input: &str) -> ParseResult<'_, Block> {
+    let class = map(take_while1(is_name_char), |class: &str| Attributes {
+        classes: vec![class.to_string()],
+        ..Attributes::default()
+    });
+    let open = delimited(
+        pair(parse_fence, space0),
+        alt((parse_attributes, class)),
+        pair(space0, line_ending),
+    );
+    let close = tuple((multispace0, parse_fence, space0, line_ending));
+    map(
+        tuple((open, many0(preceded(multispace0, parse_block)), close)),
+        |(attributes, blocks, _)| Block::Div(attributes, blocks),
+    )(input)
+}
+
+/// ::: (three or more colons)
+fn parse_fence(input: &str) -> ParseResult<'_, ()> {
+    map(pair(tag(":::"), take_while(|c| c == ':')), |_| ())(input)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || c == ':'
+}
+
+/// {#id .class key=value key="value"}
+fn parse_attributes(input: &str) -> ParseResult<'_, Attributes> {
+    enum Attribute {
+        Id(String),
+        Class(String),
+        Pair(String, String),
+    }
+    let parse_id = map(preceded(tag("#"), take_while1(is_name_char)), |id: &str| {
+        Attribute::Id(id.to_string())
+    });
+    let parse_class = map(preceded(tag("."), take_while1(is_name_char)), |c: &str| {
+        Attribute::Class(c.to_string())
+    });
+    let parse_value = alt((
+        delimited(tag("\""), take_until("\""), tag("\"")),
+        delimited(tag("'"), take_until("'"), tag("'")),
+        is_not(" \t\r\n}"),
+    ));
+    let parse_pair = map(
+        tuple((take_while1(is_name_char), tag("="), parse_value)),
+        |(key, _, value): (&str, &str, &str)| Attribute::Pair(key.to_string(), value.to_string()),
+    );
+    map(
+        delimited(
+            pair(tag("{"), space0),
+            many0(terminated(alt((parse_id, parse_class, parse_pair)), space0)),
+            tag("}"),
+        ),
+        |attrs| {
+            let mut attributes = Attributes::default();
+            for attr in attrs {
+                match attr {
+                    Attribute::Id(id) => attributes.id = Some(id),
+                    Attribute::Class(class) => attributes.classes.push(class),
+                    Attribute::Pair(key, value) => attributes.pairs.push((key, value)),
+                }
+            }
+            attributes
+        },
+    )(input)
+}
+
+fn parse_table(input: &str) -> ParseResult<'_, Block> {
     /// | VALUE | VALUE | ... | VALUE | NEWLINE
-    fn parse_row(input: &str) -> ParseResult<Vec<Text>> {
+    fn parse_row(input: &str) -> ParseResult<'_, Vec<Text>> {
         let parse_a_value = terminated(
             alt((
                 delimited(space0, parse_text, space0),
@@ -157,7 +347,7 @@ fn parse_table(input: &str) -> ParseResult<Block> {
     }
 
     /// | --- | :---- |:---:| ---:| NEWLINE
-    fn parse_rules(input: &str) -> ParseResult<Vec<Align>> {
+    fn parse_rules(input: &str) -> ParseResult<'_, Vec<Align>> {
         let is_hyphen = |c: char| c == '-';
         // RULE ::= "---" | ":---:" | ":---" | "---:"
         let parse_rule = alt((
@@ -188,7 +378,7 @@ fn parse_table(input: &str) -> ParseResult<Block> {
     );
     let parse_table_without_header = map(many1(parse_row), |body| {
         let m = body[0].len();
-        let aligns = (0..m).map(|_| Align::Left).into_iter().collect();
+        let aligns = (0..m).map(|_| Align::Left).collect();
         Block::Table(aligns, body, false)
     });
 
@@ -196,7 +386,7 @@ fn parse_table(input: &str) -> ParseResult<Block> {
 }
 
 /// Parse text without newline
-fn parse_text(input: &str) -> ParseResult<Text> {
+fn parse_text(input: &str) -> ParseResult<'_, Text> {
     let parse_emphasis_and_strong = map(
         map_parser(
             delimited(tag("***"), take_until("***"), tag("***")),
@@ -263,11 +453,99 @@ fn parse_text(input: &str) -> ParseResult<Text> {
         delimited(tag(":"), is_not(": \t\r\n"), tag(":")),
         |shortcode: &str| Inline::Emoji(shortcode.to_string()),
     );
+    let parse_variable = map(
+        delimited(
+            tag("${"),
+            pair(
+                take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '-' || c == '.'),
+                opt(preceded(tag(":-"), take_while(|c| c != '}' && c != '\n'))),
+            ),
+            tag("}"),
+        ),
+        |(name, default): (&str, Option<&str>)| {
+            Inline::Variable(name.to_string(), default.map(|s| s.to_string()))
+        },
+    );
+    let parse_citation_key = || {
+        recognize(separated_list1(
+            one_of(".:"),
+            take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_'),
+        ))
+    };
+    let parse_citation_item = map(
+        pair(
+            preceded(tag("@"), parse_citation_key()),
+            opt(preceded(pair(tag(","), space0), is_not(";]"))),
+        ),
+        |(key, locator): (&str, Option<&str>)| Citation {
+            key: key.to_string(),
+            locator: locator.map(|l| l.trim().to_string()),
+        },
+    );
+    let parse_bracketed_citation = map(
+        delimited(
+            pair(tag("["), space0),
+            separated_list1(tuple((space0, tag(";"), space0)), parse_citation_item),
+            pair(space0, tag("]")),
+        ),
+        |citations| Inline::Citation(citations, true),
+    );
+    let parse_citation = map(preceded(tag("@"), parse_citation_key()), |key: &str| {
+        Inline::Citation(
+            vec![Citation {
+                key: key.to_string(),
+                locator: None,
+            }],
+            false,
+        )
+    });
+    let is_index =
+        |attributes: &Attributes| attributes.has_class("idx") || attributes.has_class("index");
+    let parse_index_span = map(
+        pair(
+            delimited(tag("["), parse_text, tag("]")),
+            verify(parse_attributes, is_index),
+        ),
+        |(text, attributes)| Inline::Index(text, attributes.get("term").cloned()),
+    );
+    let parse_index_marker = map(
+        verify(parse_attributes, move |attributes: &Attributes| {
+            is_index(attributes) && attributes.get("term").is_some()
+        }),
+        |attributes| Inline::Index(vec![], attributes.get("term").cloned()),
+    );
+    let parse_glossary = map(
+        verify(
+            delimited(tag("{"), is_not("{}\n"), tag("}")),
+            // words of a term; other braces, e.g. `{"a": 1}`, are plaintexts
+            |term: &str| {
+                !term.starts_with([' ', '#', '.'])
+                    && !term.ends_with(' ')
+                    && term
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || " -_.+/'".contains(c))
+            },
+        ),
+        |term: &str| Inline::Glossary(term.to_string()),
+    );
+    let parse_reference = map(
+        preceded(
+            tag("@"),
+            recognize(tuple((
+                alt((tag("fig"), tag("tbl"), tag("lst"), tag("eq"), tag("sec"))),
+                tag(":"),
+                take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_'),
+            ))),
+        ),
+        |label: &str| Inline::Reference(label.to_string()),
+    );
 
     many1(preceded(
         space0,
         alt((
             parse_hyperlink,
+            parse_bracketed_citation,
+            parse_index_span,
             parse_link,
             parse_image,
             parse_emphasis_and_strong,
@@ -276,6 +554,11 @@ fn parse_text(input: &str) -> ParseResult<Text> {
             parse_deleted,
             parse_code,
             parse_comment,
+            parse_variable,
+            parse_reference,
+            parse_citation,
+            parse_index_marker,
+            parse_glossary,
             parse_mathjax,
             parse_emoji,
             parse_plaintext,
@@ -285,7 +568,7 @@ fn parse_text(input: &str) -> ParseResult<Text> {
 }
 
 /// Check if the line starts with a list marker
-fn parse_list_start(input: &str) -> ParseResult<()> {
+fn parse_list_start(input: &str) -> ParseResult<'_, ()> {
     map(
         tuple((
             space0, // indent (0 or more spaces)
@@ -303,23 +586,59 @@ fn parse_list_start(input: &str) -> ParseResult<()> {
 }
 
 /// Parse one-line text ending with a newline
-fn parse_text_line(input: &str) -> ParseResult<Text> {
+fn parse_text_line(input: &str) -> ParseResult<'_, Text> {
     let (input, (text, newline)) = pair(parse_text, opt(tag("  ")))(input)?;
     let (input, _) = line_ending(input)?;
     let mut text = text;
-    if let Some(_) = newline {
+    if newline.is_some() {
         text.push(Inline::Newline);
     }
     Ok((input, text))
 }
 
-/// Parse one-line text for paragraph (with list start check)
-fn parse_paragraph_line(input: &str) -> ParseResult<Text> {
-    let (input, _) = peek(not(parse_list_start))(input)?;
+/// Heading text with optional trailing attributes, e.g. `Title {#id .unnumbered}`
+fn parse_heading_line(input: &str) -> ParseResult<'_, (Text, Attributes)> {
+    let (input, line) = terminated(not_line_ending, line_ending)(input)?;
+    let line = line.trim_end();
+    let (line, attributes) = match line.rfind('{') {
+        Some(i) if !line[..i].ends_with('$') => match all_consuming(parse_attributes)(&line[i..]) {
+            // at least one `#id`, `.class` or `key=value`; `{}` is a plaintext
+            Ok((_, attributes)) if attributes != Attributes::default() => {
+                (line[..i].trim_end(), attributes)
+            }
+            _ => (line, Attributes::default()),
+        },
+        _ => (line, Attributes::default()),
+    };
+    let (_, text) = parse_text(line)?;
+    Ok((input, (text, attributes)))
+}
+
+/// Join the lines of a paragraph; a line break between CJK characters is no space
+fn join_lines(lines: Vec<Text>) -> Text {
+    let mut text: Text = vec![];
+    for line in lines {
+        let mut line = line.into_iter().peekable();
+        if let (Some(Inline::Plaintext(last)), Some(Inline::Plaintext(first))) =
+            (text.last_mut(), line.peek())
+        {
+            if last.ends_with(is_cjk) && first.starts_with(is_cjk) {
+                last.push_str(first);
+                line.next();
+            }
+        }
+        text.extend(line);
+    }
+    text
+}
+
+/// Parse one-line text for paragraph (with list start and fence check)
+fn parse_paragraph_line(input: &str) -> ParseResult<'_, Text> {
+    let (input, _) = peek(not(alt((parse_list_start, parse_fence))))(input)?;
     parse_text_line(input)
 }
 
-fn parse_plaintext(input: &str) -> ParseResult<Inline> {
+fn parse_plaintext(input: &str) -> ParseResult<'_, Inline> {
     let safe_one_char = preceded(
         not(alt((
             tag(" "),
@@ -338,31 +657,14 @@ fn parse_plaintext(input: &str) -> ParseResult<Inline> {
         ))),
         take(1u8),
     );
-    let escaped_char = map(
-        alt((
-            tag("\\ "),
-            tag("\\!"),
-            tag("\\$"),
-            tag("\\*"),
-            tag("\\:"),
-            tag("\\<"),
-            tag("\\>"),
-            tag("\\["),
-            tag("\\\\"),
-            tag("\\]"),
-            tag("\\`"),
-            tag("\\|"),
-            tag("\\~"),
-        )),
-        |e: &str| &e[1..2],
-    );
+    let escaped_char = preceded(tag("\\"), recognize(one_of(" !#$%*+-.:<>@[\\]_`{|}~")));
     map(many1(alt((safe_one_char, escaped_char))), |v| {
         Inline::Plaintext(v.join(""))
     })(input)
 }
 
 /// partially special syntax are considered as plaintexts
-fn parse_plaintext_failover(input: &str) -> ParseResult<Inline> {
+fn parse_plaintext_failover(input: &str) -> ParseResult<'_, Inline> {
     let tagged = map(
         delimited(tag("["), take_until("]"), tag("]")),
         |inner: &str| Inline::Plaintext(format!("[{}]", inner)),
@@ -371,11 +673,19 @@ fn parse_plaintext_failover(input: &str) -> ParseResult<Inline> {
         delimited(tag("!["), take_until("]"), tag("]")),
         |inner: &str| Inline::Plaintext(format!("![{}]", inner)),
     );
-    alt((tagged, bang_tagged))(input)
+    // a dollar which does not start math, e.g. `$5`
+    let dollar = map(
+        pair(tag("$"), opt(parse_plaintext)),
+        |(_, rest)| match rest {
+            Some(Inline::Plaintext(rest)) => Inline::Plaintext(format!("${}", rest)),
+            _ => Inline::Plaintext(String::from("$")),
+        },
+    );
+    alt((tagged, bang_tagged, dollar))(input)
 }
 
 fn parse_list<'r>(indent: usize) -> impl FnMut(&'r str) -> ParseResult<'r, List> {
-    fn parse_bullet(input: &str) -> ParseResult<ListOrderType> {
+    fn parse_bullet(input: &str) -> ParseResult<'_, ListOrderType> {
         terminated(
             alt((
                 map(alt((tag("-"), tag("*"), tag("+"))), |_| {
@@ -437,7 +747,7 @@ fn parse_list<'r>(indent: usize) -> impl FnMut(&'r str) -> ParseResult<'r, List>
 }
 
 /// Inner of $...$, $$...$$
-fn parse_tex(input: &str) -> ParseResult<String> {
+fn parse_tex(input: &str) -> ParseResult<'_, String> {
     let safe_one_char = preceded(not(alt((tag("$"), tag("\\$")))), take(1u8));
     map(many1(alt((safe_one_char, tag("\\$")))), |v| v.join(""))(input)
 }
@@ -520,14 +830,18 @@ mod test_parser {
         assert_parse!(
             "# title\n\n## subtitle\n---\n",
             vec![
-                Block::Heading(1, vec![text!("title")]),
-                Block::Heading(2, vec![text!("subtitle")]),
+                Block::Heading(1, vec![text!("title")], Attributes::default()),
+                Block::Heading(2, vec![text!("subtitle")], Attributes::default()),
                 Block::HorizontalRule
             ]
         );
         assert_parse!(
             "### h3 title\n",
-            vec![Block::Heading(3, vec![text!("h3"), text!("title")]),]
+            vec![Block::Heading(
+                3,
+                vec![text!("h3"), text!("title")],
+                Attributes::default()
+            ),]
         );
         assert_parse!(
             "### **bold title** ![icon](icon.png)\n",
@@ -536,7 +850,8 @@ mod test_parser {
                 vec![
                     Inline::Strong(vec![text!("bold"), text!("title"),]),
                     Inline::Image(String::from("icon"), String::from("icon.png")),
-                ]
+                ],
+                Attributes::default()
             )]
         );
     }
@@ -659,6 +974,18 @@ mod test_parser {
                 text!("[]<>~*!|\\"),
             }]
         );
+        assert_parse!(
+            "\\#\\_a\\_ \\@fig:a 1\\.\n",
+            vec![p! {
+                text!("#_a_"),
+                text!("@fig:a"),
+                text!("1."),
+            }]
+        );
+        assert_eq!(
+            markdown("\\{b\\}\n"),
+            Ok(vec![Block::Paragraph(vec![text!("{b}")])])
+        );
     }
 
     #[test]
@@ -750,12 +1077,20 @@ int main(){{}}
 fn main(){{}}```
 "#,
             vec![
-                Block::Heading(1, vec![text!("Code")]),
-                Block::Heading(2, vec![text!("Haskell"), text!("code")]),
+                Block::Heading(1, vec![text!("Code")], Attributes::default()),
+                Block::Heading(
+                    2,
+                    vec![text!("Haskell"), text!("code")],
+                    Attributes::default()
+                ),
                 codeblock!("haskell", "main: IO ()\nmain = do\n    Hello\n"),
-                Block::Heading(2, vec![text!("C"), text!("code")]),
+                Block::Heading(2, vec![text!("C"), text!("code")], Attributes::default()),
                 codeblock!("c", "int main(){{}}\n"),
-                Block::Heading(2, vec![text!("something"), text!("code")]),
+                Block::Heading(
+                    2,
+                    vec![text!("something"), text!("code")],
+                    Attributes::default()
+                ),
                 codeblock!("fn main(){{}}"),
             ]
         );
@@ -1022,7 +1357,7 @@ fn main(){{}}```
         assert_parse!(
             "# h1\n@(another.md)\n",
             vec![
-                Block::Heading(1, vec![text!("h1")]),
+                Block::Heading(1, vec![text!("h1")], Attributes::default()),
                 Block::Import(String::from("another.md"))
             ]
         );
@@ -1074,6 +1409,42 @@ fn main(){{}}```
         );
     }
 
+    #[test]
+    fn test_variable() {
+        assert_eq!(
+            markdown("v${version} ${name:-unidoc doc} ${empty:-}\n"),
+            Ok(vec![p! {
+                text!("v"),
+                Inline::Variable(String::from("version"), None),
+                Inline::Variable(String::from("name"), Some(String::from("unidoc doc"))),
+                Inline::Variable(String::from("empty"), Some(String::new())),
+            }])
+        );
+        assert_eq!(
+            markdown("\\${version}\n"),
+            Ok(vec![p! { text!("${version}") }])
+        );
+        assert_eq!(
+            markdown("$x$ ${y}\n"),
+            Ok(vec![p! {
+                Inline::MathJax(String::from("x")),
+                Inline::Variable(String::from("y"), None),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_toc() {
+        assert_parse!(
+            "# h1\n[TOC]\n\n[TOC] is here\n",
+            vec![
+                Block::Heading(1, vec![text!("h1")], Attributes::default()),
+                Block::Toc,
+                p! { text!("[TOC]"), text!("is"), text!("here") },
+            ]
+        );
+    }
+
     #[test]
     fn test_plaintext_failover() {
         assert_parse!(
@@ -1092,6 +1463,10 @@ fn main(){{}}```
                 text!("![x]"),
             }]
         );
+        assert_parse!(
+            "Cost $5 here\n",
+            vec![p! { text!("Cost"), text!("$5"), text!("here"), }]
+        );
     }
 
     #[test]
@@ -1133,11 +1508,243 @@ fn main(){{}}```
         );
     }
 
+    #[test]
+    fn test_div() {
+        assert_eq!(
+            markdown("::: {#x .note if=\"edition == 'internal'\"}\nsecret\n:::\n"),
+            Ok(vec![Block::Div(
+                Attributes {
+                    id: Some(String::from("x")),
+                    classes: vec![String::from("note")],
+                    pairs: vec![(String::from("if"), String::from("edition == 'internal'"))],
+                },
+                vec![p! { text!("secret") }]
+            )])
+        );
+        assert_eq!(
+            markdown("::: notes\n# h1\n- a\n\n:::: {.inner}\nb\n::::\n:::\nc\n"),
+            Ok(vec![
+                Block::Div(
+                    Attributes {
+                        classes: vec![String::from("notes")],
+                        ..Attributes::default()
+                    },
+                    vec![
+                        Block::Heading(1, vec![text!("h1")], Attributes::default()),
+                        listblock! {
+                            ListOrderType::Unordered;
+                            (None, vec![text!("a")], None),
+                        },
+                        Block::Div(
+                            Attributes {
+                                classes: vec![String::from("inner")],
+                                ..Attributes::default()
+                            },
+                            vec![p! { text!("b") }]
+                        ),
+                    ]
+                ),
+                p! { text!("c") },
+            ])
+        );
+        cannot_parse!("::: {.unclosed}\na\n", "Div must be closed");
+    }
+
+    #[test]
+    fn test_front_matter() {
+        use crate::parser::front_matter;
+        assert_eq!(
+            front_matter("---\ntitle: \"A: B\"\n\nedition: internal\n---\n# h1\n"),
+            (
+                vec![
+                    (String::from("title"), String::from("A: B")),
+                    (String::from("edition"), String::from("internal")),
+                ],
+                "# h1\n"
+            )
+        );
+        assert_eq!(front_matter("# h1\n"), (vec![], "# h1\n"));
+        assert_eq!(
+            front_matter("---\nnot front matter\n---\n"),
+            (vec![], "---\nnot front matter\n---\n")
+        );
+        assert_eq!(front_matter("---\na: b\n"), (vec![], "---\na: b\n"));
+        // a slide, not front matter
+        assert_eq!(
+            front_matter("---\n# Slide 1\n---\n"),
+            (vec![], "---\n# Slide 1\n---\n")
+        );
+        assert_eq!(
+            front_matter("---\n#key: value\n---\n"),
+            (vec![], "---\n#key: value\n---\n")
+        );
+        assert_eq!(front_matter("---\n---\n"), (vec![], "---\n---\n"));
+    }
+
     #[test]
     fn test_heading_with_number() {
         assert_parse!(
             "## 1. hoge\n",
-            vec![Block::Heading(2, vec![text!("1."), text!("hoge")]),]
+            vec![Block::Heading(
+                2,
+                vec![text!("1."), text!("hoge")],
+                Attributes::default()
+            ),]
+        );
+    }
+
+    #[test]
+    fn test_heading_attributes() {
+        assert_eq!(
+            markdown(
+                "## Preface {#pre .unnumbered}
+"
+            ),
+            Ok(vec![Block::Heading(
+                2,
+                vec![text!("Preface")],
+                Attributes {
+                    id: Some(String::from("pre")),
+                    classes: vec![String::from("unnumbered")],
+                    pairs: vec![],
+                }
+            )])
+        );
+        assert_eq!(
+            markdown(
+                "## ${name:-x}
+"
+            ),
+            Ok(vec![Block::Heading(
+                2,
+                vec![Inline::Variable(
+                    String::from("name"),
+                    Some(String::from("x"))
+                )],
+                Attributes::default()
+            )])
+        );
+        // empty braces are not attributes
+        assert_eq!(
+            markdown("## Struct Foo {}\n"),
+            Ok(vec![Block::Heading(
+                2,
+                vec![text!("Struct"), text!("Foo"), text!("{}")],
+                Attributes::default()
+            )])
+        );
+    }
+
+    #[test]
+    fn test_labeled() {
+        let label = |id: &str| Attributes {
+            id: Some(id.to_string()),
+            ..Attributes::default()
+        };
+        assert_eq!(
+            markdown("![A *b*](a.png){#fig:a}\n"),
+            Ok(vec![Block::Labeled(
+                Box::new(Block::Paragraph(vec![Inline::Image(
+                    String::from("A *b*"),
+                    String::from("a.png")
+                )])),
+                vec![text!("A"), Inline::Emphasis(vec![text!("b")])],
+                label("fig:a")
+            )])
+        );
+        assert_eq!(
+            markdown("```\nx\n```\n: Code {#lst:x}\n"),
+            Ok(vec![Block::Labeled(
+                Box::new(Block::Code(None, String::from("x\n"))),
+                vec![text!("Code")],
+                label("lst:x")
+            )])
+        );
+        assert_eq!(
+            markdown("$$x$$ {#eq:x}\n"),
+            Ok(vec![Block::Labeled(
+                Box::new(Block::MathJax(String::from("x"))),
+                vec![],
+                label("eq:x")
+            )])
+        );
+        assert_eq!(
+            markdown("See @fig:a, a@fig:b\n"),
+            Ok(vec![Block::Paragraph(vec![
+                text!("See"),
+                Inline::Reference(String::from("fig:a")),
+                text!(","),
+                text!("a@fig:b"),
+            ])])
+        );
+    }
+
+    #[test]
+    fn test_citation() {
+        let cite = |key: &str, locator: Option<&str>| Citation {
+            key: key.to_string(),
+            locator: locator.map(String::from),
+        };
+        assert_eq!(
+            markdown("[@knuth1984, p. 33; @lamport] and @knuth1984.\n"),
+            Ok(vec![Block::Paragraph(vec![
+                Inline::Citation(
+                    vec![cite("knuth1984", Some("p. 33")), cite("lamport", None)],
+                    true
+                ),
+                text!("and"),
+                Inline::Citation(vec![cite("knuth1984", None)], false),
+                text!("."),
+            ])])
+        );
+        assert_eq!(
+            markdown("mail@example.com\n"),
+            Ok(vec![Block::Paragraph(vec![text!("mail@example.com")])])
+        );
+    }
+
+    #[test]
+    fn test_abbreviation() {
+        assert_eq!(
+            markdown("*[HTML]: HyperText Markup Language\n\n{HTML} and {World Wide Web} {#x}\n"),
+            Ok(vec![
+                Block::Abbreviation(
+                    String::from("HTML"),
+                    String::from("HyperText Markup Language")
+                ),
+                Block::Paragraph(vec![
+                    Inline::Glossary(String::from("HTML")),
+                    text!("and"),
+                    Inline::Glossary(String::from("World Wide Web")),
+                    text!("{#x}"),
+                ]),
+            ])
+        );
+        assert_eq!(
+            markdown("{<b>x</b>} {\"a\": 1}\n"),
+            Ok(vec![Block::Paragraph(vec![
+                text!("{<b>x</b>}"),
+                text!("{\"a\":"),
+                text!("1}"),
+            ])])
+        );
+    }
+
+    #[test]
+    fn test_index() {
+        assert_eq!(
+            markdown(
+                "[nom]{.idx term=\"parser!nom\"} is [fast]{.idx}{.index term=\"speed\"}\n\n[INDEX]\n"
+            ),
+            Ok(vec![
+                Block::Paragraph(vec![
+                    Inline::Index(vec![text!("nom")], Some(String::from("parser!nom"))),
+                    text!("is"),
+                    Inline::Index(vec![text!("fast")], None),
+                    Inline::Index(vec![], Some(String::from("speed"))),
+                ]),
+                Block::Index,
+            ])
         );
     }
 }
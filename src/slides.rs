@@ -0,0 +1,210 @@
+use crate::entity::markdown::{Attributes, Block, Markdown};
+use crate::template::{self, Context};
+use handlebars::RenderError;
+
+/// Slides (`<div class="slide">`) split at `---` and the headings of the level (or upper);
+/// the classes of the headings are the classes of the slides
+pub fn split(mkd: &Markdown, level: usize) -> Markdown {
+    let mut slides = vec![];
+    let mut slide: Markdown = vec![];
+    let mut classes: Vec<String> = vec![];
+    let mut flush = |slide: &mut Markdown, classes: &mut Vec<String>| {
+        if !slide.is_empty() {
+            let mut attributes = Attributes {
+                classes: vec![String::from("slide")],
+                ..Attributes::default()
+            };
+            attributes.classes.append(classes);
+            slides.push(Block::Div(attributes, std::mem::take(slide)));
+        }
+        classes.clear();
+    };
+    for block in mkd.iter() {
+        match block {
+            Block::HorizontalRule => flush(&mut slide, &mut classes),
+            Block::Heading(l, _, attributes) if *l <= level => {
+                flush(&mut slide, &mut classes);
+                if *l < level {
+                    classes.push(String::from("title-slide"));
+                }
+                classes.extend(attributes.classes.iter().cloned());
+                slide.push(block.clone());
+            }
+            _ => slide.push(block.clone()),
+        }
+    }
+    flush(&mut slide, &mut classes);
+    slides
+}
+
+/// Self-contained HTML presentation; no scripts nor styles from CDNs
+pub fn render(context: Context) -> Result<String, RenderError> {
+    const TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8" />
+  <meta name="generator" content="unidoc" />
+  <meta name="viewport" content="width=device-width, initial-scale=1.0" />
+  <title>{{title}}</title>
+  <style>
+    html, body { margin: 0; height: 100%; background: #222; }
+    .deck > .slide {
+      display: none; box-sizing: border-box; position: absolute; top: 0; left: 0;
+      width: 100vw; height: 100vh; padding: 5vh 8vw; overflow: hidden;
+      background: #fff; color: #222; font: 3.2vh/1.5 sans-serif;
+    }
+    .deck > .slide.current { display: block; }
+    .deck > .slide.title-slide.current {
+      display: flex; flex-direction: column; justify-content: center; text-align: center;
+    }
+    .slide h1 { font-size: 2.2em; }
+    .slide h2 { font-size: 1.6em; margin-top: 0; }
+    .slide pre { font-size: 0.8em; background: #f4f4f4; padding: 0.5em; overflow: auto; }
+    .slide img { max-width: 100%; max-height: 60vh; }
+    .incremental li.hidden { visibility: hidden; }
+    .slide .notes { display: none; }
+    body.show-notes .slide .notes {
+      display: block; position: absolute; left: 0; right: 0; bottom: 0;
+      padding: 1em 8vw; background: #ffd; font-size: 0.7em;
+    }
+    .progress { position: fixed; left: 0; right: 0; bottom: 0; height: 4px; }
+    .progress .bar { width: 0; height: 100%; background: #4a90d9; transition: width 0.2s; }
+    .counter { position: fixed; right: 12px; bottom: 8px; color: #888; font: 14px sans-serif; }
+    @media print {
+      @page { size: landscape; margin: 1cm; }
+      html, body { height: auto; background: #fff; }
+      .deck > .slide, .deck > .slide.title-slide.current {
+        display: block; position: static; width: auto; height: auto; padding: 1cm;
+        border: 1px solid #ccc; font-size: 14pt; break-after: page; page-break-after: always;
+      }
+      .incremental li.hidden { visibility: visible; }
+      .slide .notes, body.show-notes .slide .notes {
+        display: block; position: static; margin-top: 1em; padding: 0.5em 0 0;
+        border-top: 1px dashed #999; background: none; font-size: 11pt;
+      }
+      .progress, .counter { display: none; }
+    }
+  </style>
+{{#each css}}
+  <link rel="stylesheet" href="{{this}}" />
+{{/each}}
+{{#each headers}}
+  {{{this}}}
+{{/each}}
+</head>
+<body>
+{{#each befores}}
+  {{{this}}}
+{{/each}}
+<div class="deck">
+{{{body}}}
+</div>
+<div class="progress"><div class="bar"></div></div>
+<div class="counter"></div>
+<script>
+(function () {
+  var deck = document.querySelector('.deck');
+  // blocks out of the slides (e.g. the references) are slides too
+  var stray = null;
+  Array.prototype.slice.call(deck.children).forEach(function (el) {
+    if (el.classList.contains('slide')) {
+      stray = null;
+      return;
+    }
+    if (!stray) {
+      stray = document.createElement('div');
+      stray.className = 'slide';
+      deck.insertBefore(stray, el);
+    }
+    stray.appendChild(el);
+  });
+  var slides = Array.prototype.slice.call(deck.children);
+  var steps = slides.map(function (slide) {
+    return Array.prototype.slice.call(
+      slide.querySelectorAll('.incremental > ul > li, .incremental > ol > li'));
+  });
+  var bar = document.querySelector('.progress .bar');
+  var counter = document.querySelector('.counter');
+  var current = 0, step = 0;
+  function show(n, k) {
+    if (slides.length === 0) return;
+    current = Math.max(0, Math.min(slides.length - 1, n));
+    step = k;
+    slides.forEach(function (slide, i) { slide.classList.toggle('current', i === current); });
+    steps[current].forEach(function (li, i) { li.classList.toggle('hidden', i >= step); });
+    bar.style.width = (100 * (current + 1) / slides.length) + '%';
+    counter.textContent = (current + 1) + ' / ' + slides.length;
+    history.replaceState(null, '', '#' + (current + 1));
+  }
+  function next() {
+    if (step < steps[current].length) show(current, step + 1);
+    else if (current < slides.length - 1) show(current + 1, 0);
+  }
+  function prev() {
+    if (step > 0) show(current, step - 1);
+    else if (current > 0) show(current - 1, steps[current - 1].length);
+  }
+  document.addEventListener('keydown', function (e) {
+    switch (e.key) {
+      case 'ArrowRight': case 'ArrowDown': case 'PageDown': case ' ': case 'Enter': next(); break;
+      case 'ArrowLeft': case 'ArrowUp': case 'PageUp': case 'Backspace': prev(); break;
+      case 'Home': show(0, 0); break;
+      case 'End': show(slides.length - 1, steps[slides.length - 1].length); break;
+      case 'n': document.body.classList.toggle('show-notes'); break;
+      default: return;
+    }
+    e.preventDefault();
+  });
+  var n = parseInt(location.hash.slice(1), 10);
+  show(isNaN(n) ? 0 : n - 1, 0);
+})();
+</script>
+{{#each js}}
+<script src="{{this}}"></script>
+{{/each}}
+{{#each afters}}
+  {{{this}}}
+{{/each}}
+</body>
+</html>
+"#;
+    template::custom(TEMPLATE, context)
+}
+
+#[cfg(test)]
+mod test_slides {
+    use crate::entity::markdown::{Attributes, Block};
+    use crate::parser;
+    use crate::slides::*;
+
+    fn classes(block: &Block) -> Vec<String> {
+        match block {
+            Block::Div(Attributes { classes, .. }, _) => classes.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let mkd = parser::markdown(concat!(
+            "# Talk\n\nby me\n\n",
+            "## Agenda {.inverse}\n\n",
+            "::: incremental\n- a\n- b\n:::\n\n",
+            "::: notes\nsay hello\n:::\n\n",
+            "---\n\n",
+            "no heading\n\n",
+            "### Detail\n\nin the slide\n"
+        ))
+        .unwrap();
+        let slides = split(&mkd, 2);
+        assert_eq!(slides.len(), 3);
+        assert_eq!(classes(&slides[0]), vec!["slide", "title-slide"]);
+        assert_eq!(classes(&slides[1]), vec!["slide", "inverse"]);
+        assert_eq!(classes(&slides[2]), vec!["slide"]);
+        match &slides[2] {
+            Block::Div(_, blocks) => assert_eq!(blocks.len(), 3),
+            _ => unreachable!(),
+        }
+        assert_eq!(split(&mkd, 0).len(), 2);
+    }
+}
@@ -0,0 +1,119 @@
+use lazy_static::lazy_static;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use regex::Regex;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// How heading ids are made from the headings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Slug {
+    /// Same as the anchors on GitHub, e.g. `a-b-c--_-あ`
+    Github,
+    /// Level and percent-encoded HTML, e.g. `1-a%20b%20c`
+    #[default]
+    Legacy,
+    /// ASCII letters and digits only, e.g. `a-b-c`
+    Ascii,
+}
+
+impl FromStr for Slug {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Slug::Github),
+            "unidoc-legacy" => Ok(Slug::Legacy),
+            "ascii" => Ok(Slug::Ascii),
+            _ => Err(format!(
+                "Invalid slug: {}; github, unidoc-legacy or ascii",
+                s
+            )),
+        }
+    }
+}
+
+impl Slug {
+    /// `text` is the plain text of the heading, `innerhtml` is its HTML
+    pub fn make(&self, level: usize, text: &str, innerhtml: &str) -> String {
+        lazy_static! {
+            static ref PUNCTUATION: Regex = Regex::new(r"[^\p{L}\p{M}\p{N}\p{Pc} -]").unwrap();
+            static ref NON_ASCII: Regex = Regex::new(r"[^a-z0-9]+").unwrap();
+        }
+        match self {
+            Slug::Github => PUNCTUATION
+                .replace_all(&text.trim().to_lowercase(), "")
+                .replace(' ', "-"),
+            Slug::Legacy => format!("{}-{}", level, percent_encode(innerhtml)),
+            Slug::Ascii => {
+                let slug = NON_ASCII
+                    .replace_all(&text.to_lowercase(), "-")
+                    .trim_matches('-')
+                    .to_string();
+                if slug.is_empty() {
+                    String::from("section")
+                } else {
+                    slug
+                }
+            }
+        }
+    }
+}
+
+/// Ids used in the document
+#[derive(Debug, Default)]
+pub struct Ids {
+    used: HashSet<String>,
+}
+
+impl Ids {
+    /// `id`, or `id-1`, `id-2`, ... when already used
+    pub fn unique(&mut self, id: &str) -> String {
+        let mut candidate = id.to_string();
+        let mut n = 0;
+        while self.used.contains(&candidate) {
+            n += 1;
+            candidate = format!("{}-{}", id, n);
+        }
+        self.used.insert(candidate.to_string());
+        candidate
+    }
+
+    /// Mark an explicit id (`{#id}`) as used
+    pub fn reserve(&mut self, id: &str) {
+        self.used.insert(id.to_string());
+    }
+}
+
+fn percent_encode(input: &str) -> String {
+    const CUSTOM_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_');
+    utf8_percent_encode(input, CUSTOM_ENCODE_SET).to_string()
+}
+
+#[cfg(test)]
+mod test_slug {
+    use crate::slug::*;
+
+    #[test]
+    fn test_make() {
+        let text = "a b c -_.~ あ";
+        assert_eq!(
+            Slug::Legacy.make(1, text, text),
+            "1-a%20b%20c%20-_%2E%7E%20%E3%81%82"
+        );
+        assert_eq!(Slug::Github.make(1, text, text), "a-b-c--_-あ");
+        assert_eq!(Slug::Ascii.make(1, text, text), "a-b-c");
+        assert_eq!(Slug::Github.make(2, "What's New?", ""), "whats-new");
+        assert_eq!(Slug::Ascii.make(2, "日本語", ""), "section");
+        assert_eq!("github".parse(), Ok(Slug::Github));
+        assert!("markdown".parse::<Slug>().is_err());
+    }
+
+    #[test]
+    fn test_unique() {
+        let mut ids = Ids::default();
+        assert_eq!(ids.unique("a"), "a");
+        assert_eq!(ids.unique("a"), "a-1");
+        ids.reserve("a-2");
+        assert_eq!(ids.unique("a"), "a-3");
+        assert_eq!(ids.unique("b"), "b");
+    }
+}
@@ -0,0 +1,190 @@
+use crate::entity::html::is_tag_start;
+
+/// Opening and closing quotes of a language
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quotes {
+    pub double: (&'static str, &'static str),
+    pub single: (&'static str, &'static str),
+}
+
+/// Quotes by `lang`, e.g. `ja`, `fr`, `de-AT`; English by default
+pub fn quotes(lang: Option<&str>) -> Quotes {
+    let lang = lang.unwrap_or("en").to_lowercase();
+    let lang = lang.split(['-', '_']).next().unwrap_or("");
+    match lang {
+        "ja" | "zh" => Quotes {
+            double: ("「", "」"),
+            single: ("『", "』"),
+        },
+        "fr" => Quotes {
+            double: ("«\u{a0}", "\u{a0}»"),
+            single: ("‹\u{a0}", "\u{a0}›"),
+        },
+        "ru" | "uk" | "es" | "it" => Quotes {
+            double: ("«", "»"),
+            single: ("“", "”"),
+        },
+        "de" | "cs" | "pl" => Quotes {
+            double: ("„", "“"),
+            single: ("‚", "‘"),
+        },
+        _ => Quotes {
+            double: ("“", "”"),
+            single: ("‘", "’"),
+        },
+    }
+}
+
+/// Whether the next single quote in the text is a closing one (`word'`) rather than an opening one;
+/// `None` without quotes (apostrophes and elisions such as `don't` or `'90s` are skipped)
+pub fn closes_single(text: &str) -> Option<bool> {
+    let chars: Vec<char> = text.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        if c != '\'' {
+            continue;
+        }
+        let prev = i.checked_sub(1).map(|j| chars[j]);
+        let next = chars.get(i + 1).copied();
+        if prev.is_none_or(|p| p.is_whitespace() || "([{".contains(p)) {
+            if !next.is_some_and(|n| n.is_ascii_digit()) {
+                return Some(false);
+            }
+        } else if !next.is_some_and(|n| n.is_alphanumeric()) {
+            return Some(true);
+        }
+    }
+    None
+}
+
+/// Typographic quotes, dashes and ellipses in a plaintext.
+/// Raw HTML tags and URLs are left as they are; `in_tag` is whether a tag is open before the text.
+/// `closing` is whether the next single quote after the text is a closing one; otherwise `'tis` is an elision.
+pub fn convert(text: &str, quotes: &Quotes, in_tag: bool, closing: bool) -> String {
+    if text.contains("://") || text.starts_with("www.") {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut in_tag = in_tag;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if in_tag {
+            in_tag = c != '>';
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        // opening when at the beginning or after spaces, brackets and tags
+        let opening = match out.chars().last() {
+            None => true,
+            Some(prev) => prev.is_whitespace() || "([{-–—>\u{a0}".contains(prev),
+        };
+        match c {
+            '<' if is_tag_start(c, next) => {
+                in_tag = true;
+                out.push(c);
+            }
+            '-' if chars[i..].starts_with(&['-', '-', '-']) => {
+                out.push('—');
+                i += 2;
+            }
+            '-' if next == Some('-') => {
+                out.push('–');
+                i += 1;
+            }
+            '.' if chars[i..].starts_with(&['.', '.', '.']) => {
+                out.push('…');
+                i += 2;
+            }
+            '"' => {
+                out += if opening {
+                    quotes.double.0
+                } else {
+                    quotes.double.1
+                }
+            }
+            // elision, e.g. `'90s` or `'tis`
+            '\'' if opening
+                && next.is_some_and(|n| {
+                    n.is_ascii_digit()
+                        || n.is_alphabetic()
+                            && !closes_single(&chars[i + 1..].iter().collect::<String>())
+                                .unwrap_or(closing)
+                }) =>
+            {
+                out.push('’')
+            }
+            '\'' if opening => out += quotes.single.0,
+            // apostrophe
+            '\'' if next.is_some_and(|n| n.is_alphanumeric()) => out.push('’'),
+            '\'' => out += quotes.single.1,
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod test_smart {
+    use crate::smart::*;
+
+    #[test]
+    fn test_convert() {
+        let en = quotes(None);
+        assert_eq!(convert("\"Hello", &en, false, false), "“Hello");
+        assert_eq!(convert("world.\"", &en, false, false), "world.”");
+        assert_eq!(convert("'quoted'", &en, false, false), "‘quoted’");
+        assert_eq!(convert("don't", &en, false, false), "don’t");
+        assert_eq!(convert("1--2", &en, false, false), "1–2");
+        assert_eq!(convert("a---b", &en, false, false), "a—b");
+        assert_eq!(convert("wait...", &en, false, false), "wait…");
+        assert_eq!(
+            convert("<a href=\"x--y\">\"link\"</a>", &en, false, false),
+            "<a href=\"x--y\">“link”</a>"
+        );
+        assert_eq!(convert("1 < 2 \"a\"", &en, false, false), "1 < 2 “a”");
+        assert_eq!(
+            convert("https://example.com/a--b...", &en, false, false),
+            "https://example.com/a--b..."
+        );
+        assert_eq!(
+            convert("\"こんにちは\"", &quotes(Some("ja")), false, false),
+            "「こんにちは」"
+        );
+        assert_eq!(
+            convert("\"Bonjour\"", &quotes(Some("fr-CA")), false, false),
+            "«\u{a0}Bonjour\u{a0}»"
+        );
+        assert_eq!(
+            convert("\"Hallo\"", &quotes(Some("de")), false, false),
+            "„Hallo“"
+        );
+        assert_eq!(convert("l'été", &quotes(Some("fr")), false, false), "l’été");
+        // in a tag of the preceding plaintexts
+        assert_eq!(
+            convert("title=\"t\">\"a\"", &en, true, false),
+            "title=\"t\">“a”"
+        );
+        assert_eq!(convert("'tis", &en, false, false), "’tis");
+        assert_eq!(convert("'hello", &en, false, true), "‘hello");
+    }
+
+    #[test]
+    fn test_elision() {
+        for lang in ["en", "ja", "fr"] {
+            let quotes = quotes(Some(lang));
+            assert_eq!(convert("'90s", &quotes, false, false), "’90s", "{}", lang);
+            assert_eq!(convert("'90s", &quotes, false, true), "’90s", "{}", lang);
+        }
+        assert_eq!(
+            convert("'a'", &quotes(Some("fr")), false, false),
+            "‹\u{a0}a\u{a0}›"
+        );
+        assert_eq!(closes_single("world'."), Some(true));
+        assert_eq!(closes_single("don't 'tis"), Some(false));
+        assert_eq!(closes_single("'90s"), None);
+    }
+}
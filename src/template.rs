@@ -1,3 +1,4 @@
+use crate::toc::Heading;
 use handlebars::{Handlebars, RenderError};
 use serde::Serialize;
 use std::collections::HashMap;
@@ -13,9 +14,12 @@ pub struct Context {
     befores: Vec<String>,
     afters: Vec<String>,
     variable: HashMap<String, String>,
+    toc: String,
+    headings: Vec<Heading>,
 }
 
 impl Context {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         title: String,
         body: String,
@@ -26,6 +30,8 @@ impl Context {
         befores: Vec<String>,
         afters: Vec<String>,
         variable: HashMap<String, String>,
+        toc: String,
+        headings: Vec<Heading>,
     ) -> Self {
         Self {
             title,
@@ -37,6 +43,8 @@ impl Context {
             befores,
             afters,
             variable,
+            toc,
+            headings,
         }
     }
 }
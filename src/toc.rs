@@ -0,0 +1,161 @@
+use crate::entity::html::Html;
+use crate::{leaf, node};
+use serde::Serialize;
+
+/// A heading in the document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Heading {
+    pub level: usize,
+    pub id: String,
+    /// Inner HTML
+    pub title: String,
+    /// Section number with `--number-sections`, e.g. `3.2.1`
+    pub number: Option<String>,
+}
+
+impl Heading {
+    /// Inner HTML prefixed with the section number
+    pub fn numbered_title(&self) -> String {
+        match &self.number {
+            Some(number) => format!(
+                "<span class=\"section-number\">{}</span> {}",
+                number, self.title
+            ),
+            None => self.title.to_string(),
+        }
+    }
+}
+
+/// Placeholder of `[TOC]`; replaced after all headings are collected
+pub fn marker() -> Html {
+    leaf!("<!--unidoc:toc-->")
+}
+
+/// `MIN-MAX` or `MAX`
+pub fn parse_depth(depth: &str) -> Option<(usize, usize)> {
+    let (min, max) = match depth.find('-') {
+        Some(i) => (depth[..i].parse().ok()?, depth[i + 1..].parse().ok()?),
+        None => (1, depth.parse().ok()?),
+    };
+    if 1 <= min && min <= max && max <= 6 {
+        Some((min, max))
+    } else {
+        None
+    }
+}
+
+/// Nested `<nav><ol>` of the headings in the level range
+pub fn render(headings: &[Heading], (min, max): (usize, usize)) -> Html {
+    fn items(headings: &[Heading], min: usize, max: usize) -> Vec<Html> {
+        let mut list = vec![];
+        let mut i = 0;
+        while i < headings.len() {
+            let heading = &headings[i];
+            let n = headings[i + 1..]
+                .iter()
+                .take_while(|h| h.level > heading.level)
+                .count();
+            let children = items(&headings[i + 1..i + 1 + n], min, max);
+            if heading.level < min {
+                list.extend(children);
+            } else if heading.level <= max {
+                let link = format!(
+                    "<a href=\"#{}\">{}</a>",
+                    heading.id,
+                    heading.numbered_title()
+                );
+                if children.is_empty() {
+                    list.push(leaf!("<li>{}</li>", link));
+                } else {
+                    let mut li = node!(leaf!("<li>"), leaf!("</li>"));
+                    li.push(leaf!(link));
+                    li.push(Html::Node(
+                        Box::new(leaf!("<ol>")),
+                        children,
+                        Box::new(leaf!("</ol>")),
+                    ));
+                    list.push(li);
+                }
+            }
+            i += 1 + n;
+        }
+        list
+    }
+    let mut nav = node!(leaf!("<nav class=\"toc\">"), leaf!("</nav>"));
+    let items = items(headings, min, max);
+    if !items.is_empty() {
+        nav.push(Html::Node(
+            Box::new(leaf!("<ol>")),
+            items,
+            Box::new(leaf!("</ol>")),
+        ));
+    }
+    nav
+}
+
+#[cfg(test)]
+mod test_toc {
+    use crate::entity::html::HtmlDoc;
+    use crate::toc::*;
+
+    fn heading(level: usize, id: &str) -> Heading {
+        Heading {
+            level,
+            id: id.to_string(),
+            title: id.to_uppercase(),
+            number: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_depth() {
+        assert_eq!(parse_depth("2-3"), Some((2, 3)));
+        assert_eq!(parse_depth("4"), Some((1, 4)));
+        assert_eq!(parse_depth("3-2"), None);
+        assert_eq!(parse_depth("0-7"), None);
+        assert_eq!(parse_depth("x"), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let headings = vec![
+            heading(1, "a"),
+            heading(2, "b"),
+            heading(3, "c"),
+            heading(2, "d"),
+            heading(4, "e"),
+            heading(1, "f"),
+        ];
+        let show = |html: Html| HtmlDoc::new(String::new(), vec![html]).show(true, 0);
+        assert_eq!(
+            show(render(&headings, (1, 3))),
+            concat!(
+                "<nav class=\"toc\"><ol>",
+                "<li><a href=\"#a\">A</a><ol>",
+                "<li><a href=\"#b\">B</a><ol><li><a href=\"#c\">C</a></li></ol></li>",
+                "<li><a href=\"#d\">D</a></li>",
+                "</ol></li>",
+                "<li><a href=\"#f\">F</a></li>",
+                "</ol></nav>\n"
+            )
+        );
+        assert_eq!(
+            show(render(&headings, (3, 4))),
+            concat!(
+                "<nav class=\"toc\"><ol>",
+                "<li><a href=\"#c\">C</a></li>",
+                "<li><a href=\"#e\">E</a></li>",
+                "</ol></nav>\n"
+            )
+        );
+        assert_eq!(show(render(&[], (1, 3))), "<nav class=\"toc\"></nav>\n");
+        let numbered = Heading {
+            number: Some(String::from("1.2")),
+            ..heading(3, "g")
+        };
+        assert_eq!(
+            show(render(&[numbered], (1, 3))),
+            "<nav class=\"toc\"><ol><li><a href=\"#g\"><span class=\"section-number\">1.2</span> G</a></li></ol></nav>\n"
+        );
+    }
+}
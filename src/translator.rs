@@ -1,54 +1,499 @@
-use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use base64::prelude::*;
+use lazy_static::lazy_static;
+use regex::Regex;
 
+use crate::bibliography::{self, Bibliography, UnknownCitation};
 use crate::blogcard::blogcard;
-use crate::entity::html::{Html, HtmlDoc};
+use crate::condition;
+use crate::crossref::{CrossRef, DanglingReference, Kind};
+use crate::entity::html::{self, Html, HtmlDoc};
+use crate::entity::markdown::spaced;
 use crate::entity::markdown::{
-    Align, Block, Inline, List, ListItem, ListOrderType, Markdown, Text,
+    try_map_text, Align, Attributes, Block, Citation, Inline, List, ListItem, ListOrderType,
+    Markdown, Text,
 };
 use crate::executor::Executor;
+use crate::glossary::{self, Glossary, UnknownTerm};
+use crate::index;
 use crate::io;
+use crate::notebook::{mime_text, Cell, Notebook, Output};
 use crate::parser;
+use crate::slug::{Ids, Slug};
+use crate::smart;
+use crate::toc;
+use crate::variable;
 use crate::webpage::WebPage;
+use crate::wikilink::{self, BrokenLink, WikiLink};
 use crate::{leaf, node};
-use std::path::Path;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
 
+#[derive(Clone)]
 pub struct Translator {
+    /// Path of the document (for error reports)
+    source: Option<String>,
     filedir: Option<String>,
+    /// Directory of the output document
+    outdir: Option<String>,
+    /// Relative path from `outdir` to `filedir` (only for imported documents)
+    urlbase: Option<PathBuf>,
+    /// Variables for conditional blocks and `${name}`
+    variables: HashMap<String, String>,
+    /// Undefined variables are errors
+    strict: bool,
+    /// `--number-sections`
+    number_sections: bool,
+    /// `--slug`
+    slug: Slug,
+    /// `--smart`
+    smart: bool,
+    /// `--abbr-skip`; `links` and/or `headings`
+    abbr_skip: Vec<String>,
+    /// Run the executors (`@bash` etc.); not in the cells of imported notebooks
+    execute: bool,
+    state: Rc<RefCell<State>>,
+}
+
+/// Document-wide state shared by the translators of all inputs and imported documents
+#[derive(Default)]
+struct State {
+    headings: Vec<toc::Heading>,
+    /// Section counters of h1, h2, ...
+    sections: Vec<usize>,
+    /// Ids of the headings
+    ids: Ids,
+    crossref: CrossRef,
+    bibliography: Option<Bibliography>,
+    glossary: Glossary,
+    /// Abbreviations are not marked up while positive
+    abbr_skipped: usize,
+    /// In a raw HTML tag, which may continue over the plaintexts of a text
+    in_tag: bool,
+    /// The next single quote in the text is a closing one (`--smart`)
+    closing_single: bool,
+    index: Vec<index::Entry>,
+    broken_links: Vec<BrokenLink>,
+}
+
+impl State {
+    /// Next section number of the heading level, e.g. `3.2.1` for h4 (or h3 without h1);
+    /// the counters of skipped levels are not shown
+    fn section_number(&mut self, level: usize) -> String {
+        self.sections.resize(level, 0);
+        self.sections[level - 1] += 1;
+        self.sections
+            .iter()
+            .filter(|&&n| n > 0)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
 }
 
 impl Translator {
     pub fn new(filedir: Option<String>) -> Self {
-        Self { filedir }
+        Self {
+            source: None,
+            outdir: filedir.clone(),
+            filedir,
+            urlbase: None,
+            variables: HashMap::new(),
+            strict: false,
+            number_sections: false,
+            slug: Slug::default(),
+            smart: false,
+            abbr_skip: vec![],
+            execute: true,
+            state: Rc::new(RefCell::new(State::default())),
+        }
+    }
+
+    /// Translator for another input file in the same document
+    pub fn for_file(&self, filedir: Option<String>) -> Self {
+        Self {
+            source: None,
+            outdir: self.outdir.clone().or(filedir.clone()),
+            filedir,
+            urlbase: None,
+            variables: self.variables.clone(),
+            strict: self.strict,
+            number_sections: self.number_sections,
+            slug: self.slug,
+            smart: self.smart,
+            abbr_skip: self.abbr_skip.clone(),
+            execute: self.execute,
+            state: Rc::clone(&self.state),
+        }
+    }
+
+    /// All headings translated so far
+    pub fn headings(&self) -> Vec<toc::Heading> {
+        self.state.borrow().headings.clone()
+    }
+
+    /// Replace the placeholders of the cross-references in `doc`
+    pub fn resolve(&self, doc: &mut HtmlDoc) {
+        let state = self.state.borrow();
+        doc.map_leaves(&|leaf| state.crossref.resolve(leaf));
+    }
+
+    /// Bibliography for the citations in the whole document
+    pub fn set_bibliography(&self, bibliography: Bibliography) {
+        self.state.borrow_mut().bibliography = Some(bibliography);
+    }
+
+    pub fn has_bibliography(&self) -> bool {
+        self.state.borrow().bibliography.is_some()
+    }
+
+    /// List of the cited entries
+    pub fn references(&self) -> Option<Html> {
+        self.state
+            .borrow()
+            .bibliography
+            .as_ref()
+            .and_then(|bibliography| bibliography.references())
+    }
+
+    pub fn unknown_citations(&self) -> Vec<UnknownCitation> {
+        self.state
+            .borrow()
+            .bibliography
+            .as_ref()
+            .map(|bibliography| bibliography.unknown())
+            .unwrap_or_default()
+    }
+
+    /// Terms of the glossary file
+    pub fn set_glossary_terms(&self, terms: Vec<(String, String)>) {
+        self.state.borrow_mut().glossary.set_terms(terms);
+    }
+
+    pub fn has_glossary_terms(&self) -> bool {
+        self.state.borrow().glossary.has_terms()
+    }
+
+    /// Glossary of the referred terms
+    pub fn glossary(&self) -> Option<Html> {
+        self.state.borrow().glossary.render()
+    }
+
+    pub fn unknown_terms(&self) -> Vec<UnknownTerm> {
+        self.state.borrow().glossary.unknown()
+    }
+
+    /// Index of the marked terms
+    pub fn index(&self) -> Html {
+        index::render(&self.state.borrow().index)
+    }
+
+    /// References to undefined labels
+    pub fn dangling_references(&self) -> Vec<DanglingReference> {
+        self.state.borrow().crossref.dangling()
+    }
+
+    /// Wiki links to no documents or sections
+    pub fn broken_links(&self) -> Vec<BrokenLink> {
+        self.state.borrow().broken_links.clone()
+    }
+
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    pub fn with_outdir(mut self, outdir: Option<String>) -> Self {
+        self.outdir = outdir;
+        self
+    }
+
+    pub fn with_variables(mut self, variables: HashMap<String, String>, strict: bool) -> Self {
+        self.variables = variables;
+        self.strict = strict;
+        self
+    }
+
+    pub fn with_number_sections(mut self, number_sections: bool) -> Self {
+        self.number_sections = number_sections;
+        self
+    }
+
+    pub fn with_slug(mut self, slug: Slug) -> Self {
+        self.slug = slug;
+        self
+    }
+
+    pub fn with_abbr_skip(mut self, abbr_skip: Vec<String>) -> Self {
+        self.abbr_skip = abbr_skip;
+        self
+    }
+
+    pub fn with_smart(mut self, smart: bool) -> Self {
+        self.smart = smart;
+        self
+    }
+
+    /// Run the executors (`@bash` etc.); not for EPUB
+    pub fn with_execute(mut self, execute: bool) -> Self {
+        self.execute = execute;
+        self
+    }
+
+    /// Translator for an imported document at `path`
+    fn imported(&self, path: &str) -> Self {
+        let filedir = Path::new(path)
+            .parent()
+            .map(|dir| String::from(dir.to_str().unwrap()));
+        let from = absolute(Path::new(self.outdir.as_deref().unwrap_or("")));
+        let to = absolute(Path::new(filedir.as_deref().unwrap_or("")));
+        Self {
+            source: Some(path.to_string()),
+            filedir,
+            outdir: self.outdir.clone(),
+            urlbase: Some(relative_path(&from, &to)),
+            variables: self.variables.clone(),
+            strict: self.strict,
+            number_sections: self.number_sections,
+            slug: self.slug,
+            smart: self.smart,
+            abbr_skip: self.abbr_skip.clone(),
+            execute: self.execute,
+            state: Rc::clone(&self.state),
+        }
+    }
+
+    /// Rebase a relative URL in an imported document to the output document's location
+    fn url(&self, url: &str) -> String {
+        match &self.urlbase {
+            Some(base) if is_relative_url(url) => {
+                let i = url.find(['?', '#']).unwrap_or(url.len());
+                let path = normalize(&base.join(&url[..i]));
+                let path: Vec<_> = path
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect();
+                format!("{}{}", path.join("/"), &url[i..])
+            }
+            _ => url.to_string(),
+        }
+    }
+
+    /// Link to the `.html` of a local document; the link text defaults to its title
+    fn wikilink(&self, target: &str, link: &WikiLink) -> String {
+        let broken = |label: &String| {
+            let location = self.location();
+            self.state.borrow_mut().broken_links.push(BrokenLink {
+                target: target.to_string(),
+                location,
+            });
+            format!("<span class=\"wikilink-broken\">{}</span>", encode(label))
+        };
+        let label = link.label.as_ref().unwrap_or(&link.page);
+        let found = link
+            .candidates()
+            .into_iter()
+            .find_map(|file| find(&file, &self.filedir).map(|path| (file, path)));
+        let Some((file, path)) = found else {
+            return broken(label);
+        };
+        let content = io::read(&path).unwrap();
+        let (front_matter, content) = parser::front_matter(&content);
+        let mkd = parser::markdown(content).unwrap();
+        let tr = self.imported(&path);
+        let fragment = match &link.section {
+            Some(section) => match tr.find_heading(&mkd, section) {
+                Some(id) => format!("#{}", id),
+                None => return broken(label),
+            },
+            None => String::new(),
+        };
+        let text = match &link.label {
+            Some(label) => encode(label),
+            None => front_matter
+                .iter()
+                .find(|(k, _)| k == "title")
+                .map(|(_, title)| encode(title))
+                .or_else(|| {
+                    mkd.iter()
+                        .find(|block| matches!(block, Block::Heading(..)))
+                        .map(inner_text)
+                })
+                .unwrap_or_else(|| encode(&link.page)),
+        };
+        let href = format!("{}{}", wikilink::href(&file), fragment);
+        format!(
+            "<a class=\"wikilink\" href=\"{}\">{}</a>",
+            self.url(&href),
+            text
+        )
+    }
+
+    /// Id of the heading by its id or text (case-insensitive)
+    fn find_heading(&self, mkd: &Markdown, section: &str) -> Option<String> {
+        self.heading_ids(mkd)
+            .into_iter()
+            .find_map(|(i, _, id)| match &mkd[i] {
+                Block::Heading(_, label, _) => {
+                    let text = html_escape::decode_html_entities(&text_content(label)).to_string();
+                    (id == section || text.to_lowercase() == section.to_lowercase()).then_some(id)
+                }
+                _ => None,
+            })
+    }
+
+    /// (index, level, id) of the headings in a document, de-duplicated as in the document alone
+    fn heading_ids(&self, mkd: &Markdown) -> Vec<(usize, usize, String)> {
+        let mut ids = Ids::default();
+        mkd.iter()
+            .enumerate()
+            .filter_map(|(i, block)| match block {
+                Block::Heading(level, label, attributes) => {
+                    let id = match &attributes.id {
+                        Some(id) => {
+                            ids.reserve(id);
+                            id.to_string()
+                        }
+                        None => ids.unique(&self.heading_id(*level, label)),
+                    };
+                    Some((i, *level, id))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Current position in the document, e.g. `intro.md (after #2-Setup)`
+    fn location(&self) -> String {
+        let source = self.source.as_deref().unwrap_or("-");
+        match self.state.borrow().headings.last() {
+            Some(heading) => format!("{} (after #{})", source, heading.id),
+            None => source.to_string(),
+        }
+    }
+
+    /// Heading id by the slug strategy (not de-duplicated)
+    fn heading_id(&self, level: usize, label: &Text) -> String {
+        let text = html_escape::decode_html_entities(&text_content(label)).to_string();
+        self.slug.make(level, &text, &id_html(label))
+    }
+
+    /// Translate without marking up the abbreviations when `skip`
+    fn without_abbreviations<T, F: FnOnce() -> T>(&self, skip: bool, f: F) -> T {
+        if !skip {
+            return f();
+        }
+        self.state.borrow_mut().abbr_skipped += 1;
+        let result = f();
+        self.state.borrow_mut().abbr_skipped -= 1;
+        result
+    }
+
+    fn skips(&self, kind: &str) -> bool {
+        self.abbr_skip.iter().any(|k| k == kind)
     }
 
     /// Returns: (title, body)
     pub fn markdown(&self, mkd: &Markdown) -> HtmlDoc {
-        let title = inner_text(&mkd[0]);
-        HtmlDoc::new(title, mkd.iter().map(|md| self.block(md)).collect())
+        let title = mkd.first().map(inner_text).unwrap_or_default();
+        self.define_abbreviations(mkd);
+        HtmlDoc::new(title, self.blocks(mkd))
+    }
+
+    /// Abbreviations apply to the whole document, e.g. to all the chapters of an EPUB
+    pub fn define_abbreviations(&self, mkd: &Markdown) {
+        self.state
+            .borrow_mut()
+            .glossary
+            .define(glossary::definitions(mkd));
+    }
+
+    fn blocks(&self, mkd: &Markdown) -> Vec<Html> {
+        mkd.iter()
+            .filter(|block| !matches!(block, Block::Abbreviation(..)))
+            .map(|block| self.block(block))
+            .collect()
     }
 
     fn block(&self, block: &Block) -> Html {
         match block {
-            Block::Heading(1, label) => {
-                let innerhtml = self.text(&label);
-                let id = format!("{}-{}", 1, percent_encode(&innerhtml));
-                leaf!("<h1 class=\"title\" id=\"{}\">{}</h1>", id, innerhtml)
-            }
-            Block::Heading(level, label) => {
-                let innerhtml = self.text(&label);
-                let id = format!("{}-{}", level, percent_encode(&innerhtml));
-                leaf!("<h{} id=\"{}\">{}</h{}>", level, id, innerhtml, level)
+            Block::Heading(level, label, attributes) => {
+                let innerhtml =
+                    self.without_abbreviations(self.skips("headings"), || self.text(label));
+                let id = match &attributes.id {
+                    Some(id) => {
+                        self.state.borrow_mut().ids.reserve(id);
+                        encode(id)
+                    }
+                    None => {
+                        let id = self.heading_id(*level, label);
+                        self.state.borrow_mut().ids.unique(&id)
+                    }
+                };
+                // the title (the h1 at the beginning of the document) is never numbered
+                let is_title = *level == 1 && self.state.borrow().headings.is_empty();
+                let number =
+                    if self.number_sections && !is_title && !attributes.has_class("unnumbered") {
+                        Some(self.state.borrow_mut().section_number(*level))
+                    } else {
+                        None
+                    };
+                let heading = toc::Heading {
+                    level: *level,
+                    id: id.to_string(),
+                    title: innerhtml,
+                    number,
+                };
+                let title = heading.numbered_title();
+                if attributes.id.is_some() {
+                    self.state.borrow_mut().crossref.section(
+                        &heading.id,
+                        heading.number.as_deref(),
+                        &heading.title,
+                    );
+                }
+                self.state.borrow_mut().headings.push(heading);
+                let mut classes = attributes.classes.clone();
+                if *level == 1 {
+                    classes.insert(0, String::from("title"));
+                }
+                let attributes = attributes_html(&Attributes {
+                    id: None,
+                    classes: vec![],
+                    pairs: attributes.pairs.clone(),
+                });
+                if classes.is_empty() {
+                    leaf!(
+                        "<h{} id=\"{}\"{}>{}</h{}>",
+                        level,
+                        id,
+                        attributes,
+                        title,
+                        level
+                    )
+                } else {
+                    leaf!(
+                        "<h{} class=\"{}\" id=\"{}\"{}>{}</h{}>",
+                        level,
+                        encode(&classes.join(" ")),
+                        id,
+                        attributes,
+                        title,
+                        level
+                    )
+                }
             }
             Block::Paragraph(text) => {
-                leaf!("<p>{}</p>", self.text(&text))
+                leaf!("<p>{}</p>", self.text(text))
             }
             Block::Quoted(text) => {
-                leaf!("<blockquote>{}</blockquote>", self.text(&text))
+                leaf!("<blockquote>{}</blockquote>", self.text(text))
             }
             Block::Code(language, code) => {
                 // executor check
                 match language.clone() {
-                    Some(x) if x == String::from("@bash") => {
+                    Some(x) if self.execute && x == "@bash" => {
                         let res = Executor::bash(code);
                         if res.is_ok() {
                             leaf!("<pre><code>{}</code></pre>", res.unwrap())
@@ -56,7 +501,7 @@ impl Translator {
                             leaf!("<pre><samp class=error>{}</samp></pre>", res.unwrap())
                         }
                     }
-                    Some(x) if x == String::from("@dot") || x == String::from("@graphviz") => {
+                    Some(x) if self.execute && (x == "@dot" || x == "@graphviz") => {
                         let res = Executor::dot(code);
                         if res.is_ok() {
                             leaf!("<img src=\"{}\">", res.unwrap())
@@ -64,7 +509,7 @@ impl Translator {
                             leaf!("<pre><samp class=error>{}</samp></pre>", res.unwrap())
                         }
                     }
-                    Some(x) if x == String::from("@gnuplot") => {
+                    Some(x) if self.execute && x == "@gnuplot" => {
                         let res = Executor::gnuplot(code);
                         if res.is_ok() {
                             leaf!("{}", res.unwrap())
@@ -77,32 +522,41 @@ impl Translator {
                         let class = if let Some(lang) = language {
                             format!("code language-{}", lang)
                         } else {
-                            format!("code")
+                            "code".to_string()
                         };
                         leaf!(
                             "<pre><code class=\"{}\">{}</code></pre>",
                             class,
-                            encode(&code)
+                            encode(code)
                         )
                     }
                 }
             }
             Block::HorizontalRule => leaf!("<hr />"),
-            Block::ListBlock(list) => self.list(&list),
-            Block::Table(aligns, content, has_header) => self.table(&aligns, content, *has_header),
-            Block::Import(path) => {
-                if let Some(path) = find(&path, &self.filedir) {
+            Block::ListBlock(list) => self.list(list),
+            Block::Table(aligns, content, has_header) => self.table(aligns, content, *has_header),
+            Block::Import(target) => {
+                let target = ImportTarget::new(target);
+                if let Some(path) = find(&target.path, &self.filedir) {
                     let content = io::read(&path.to_string()).unwrap();
-                    let mkd = parser::markdown(&content).unwrap();
-                    let doc = self.markdown(&mkd);
+                    let tr = self.imported(&path);
+                    if path.ends_with(".ipynb") {
+                        let notebook = Notebook::from_json(&content)
+                            .unwrap_or_else(|err| panic!("Cannot read {}: {}", path, err));
+                        let hide_code = target.hides("code");
+                        let hide_output = target.hides("output");
+                        return tr.notebook(&notebook, hide_code, hide_output);
+                    }
+                    let mkd = tr.import(&path, &content, &target);
+                    let doc = tr.markdown(&mkd);
                     doc.as_html()
                 } else {
-                    panic!("Cannot find {}", path);
+                    panic!("Cannot find {}", target.path);
                 }
             }
             Block::HyperLink(url) => blogcard(url.to_string()),
             Block::CodeImport(language, path) => {
-                if let Some(path) = find(&path, &self.filedir) {
+                if let Some(path) = find(path, &self.filedir) {
                     let content = io::read(&path.to_string()).unwrap();
                     let codeblock = Block::Code(language.clone(), content.to_string());
                     self.block(&codeblock)
@@ -113,6 +567,247 @@ impl Translator {
             Block::MathJax(tex) => {
                 leaf!("\\[{}\\]", encode(tex))
             }
+            Block::Labeled(block, caption, attributes) => self.labeled(block, caption, attributes),
+            Block::Div(attributes, blocks) => {
+                let mut html = node!(
+                    leaf!("<div{}>", attributes_html(attributes)),
+                    leaf!("</div>")
+                );
+                for block in self.blocks(blocks) {
+                    html.push(block);
+                }
+                if attributes.id.as_deref() == Some("refs") {
+                    html.push(bibliography::marker());
+                }
+                if attributes.id.as_deref() == Some("glossary") {
+                    html.push(glossary::marker());
+                }
+                html
+            }
+            Block::Toc => toc::marker(),
+            Block::Index => index::marker(),
+            Block::Abbreviation(..) => unreachable!(),
+        }
+    }
+
+    /// The imported markdown (or its section); `self` is the translator of the imported document
+    fn import(&self, path: &str, content: &str, target: &ImportTarget) -> Markdown {
+        let (_, content) = parser::front_matter(content);
+        let content = variable::substitute(content, &self.variables);
+        let mkd = parser::markdown(&content).unwrap();
+        let mkd = match &target.section {
+            Some(id) => self
+                .section(&mkd, id, target.content_only)
+                .unwrap_or_else(|ids| {
+                    panic!(
+                        "Cannot find section #{} in {}; available ids: {}",
+                        id,
+                        path,
+                        ids.join(", ")
+                    )
+                }),
+            None => mkd,
+        };
+        let mkd = condition::filter(&mkd, &self.variables)
+            .unwrap_or_else(|err| panic!("{} in {}", err, path));
+        variable::interpolate(&mkd, &self.variables, self.strict)
+            .unwrap_or_else(|err| panic!("{} in {}", err, path))
+    }
+
+    /// Relative image and link URLs rebased as `url` (for the writers other than HTML)
+    fn rebase(&self, mkd: &Markdown) -> Markdown {
+        fn rebase_text(tr: &Translator, text: &Text) -> Text {
+            text.iter()
+                .map(|inline| match inline {
+                    Inline::Image(alt, url) => Inline::Image(alt.to_string(), tr.url(url)),
+                    Inline::Link(text, url) => Inline::Link(rebase_text(tr, text), tr.url(url)),
+                    _ => inline.clone(),
+                })
+                .collect()
+        }
+        try_map_text::<(), _>(mkd, &mut |text| Ok(rebase_text(self, text))).unwrap()
+    }
+
+    /// Inline the imported markdowns (for the writers other than HTML) with the URLs rebased;
+    /// notebooks are left as they are
+    pub fn expand_imports(&self, mkd: &Markdown) -> Markdown {
+        mkd.iter()
+            .flat_map(|block| match block {
+                Block::Import(target) => {
+                    let target = ImportTarget::new(target);
+                    match find(&target.path, &self.filedir) {
+                        Some(path) if path.ends_with(".ipynb") => vec![block.clone()],
+                        Some(path) => {
+                            let content = io::read(&path.to_string()).unwrap();
+                            let tr = self.imported(&path);
+                            tr.expand_imports(&tr.rebase(&tr.import(&path, &content, &target)))
+                        }
+                        None => panic!("Cannot find {}", target.path),
+                    }
+                }
+                Block::Div(attributes, children) => {
+                    vec![Block::Div(
+                        attributes.clone(),
+                        self.expand_imports(children),
+                    )]
+                }
+                _ => vec![block.clone()],
+            })
+            .collect()
+    }
+
+    /// The section headed by the heading `id`, up to the next heading of the same or higher level.
+    /// Returns all heading ids when not found.
+    fn section(
+        &self,
+        mkd: &Markdown,
+        id: &str,
+        content_only: bool,
+    ) -> Result<Markdown, Vec<String>> {
+        let headings = self.heading_ids(mkd);
+        if let Some(&(begin, level, _)) = headings.iter().find(|(_, _, hid)| hid == id) {
+            let end = headings
+                .iter()
+                .find(|&&(i, l, _)| i > begin && l <= level)
+                .map(|&(i, _, _)| i)
+                .unwrap_or(mkd.len());
+            let begin = if content_only { begin + 1 } else { begin };
+            Ok(mkd[begin..end].to_vec())
+        } else {
+            Err(headings.into_iter().map(|(_, _, hid)| hid).collect())
+        }
+    }
+
+    /// Numbered figure, table, listing or equation with the caption
+    fn labeled(&self, block: &Block, caption: &Text, attributes: &Attributes) -> Html {
+        let kind = match block {
+            Block::Table(..) => Kind::Table,
+            Block::Code(..) | Block::CodeImport(..) => Kind::Listing,
+            Block::MathJax(..) => Kind::Equation,
+            _ => Kind::Figure,
+        };
+        let number = attributes
+            .id
+            .as_ref()
+            .map(|label| self.state.borrow_mut().crossref.define(kind, label));
+        let caption = match (number, caption.is_empty()) {
+            (Some(number), true) => format!("{} {}", kind.name(), number),
+            (Some(number), false) => format!("{} {}: {}", kind.name(), number, self.text(caption)),
+            (None, _) => self.text(caption),
+        };
+        let attributes = attributes_html(attributes);
+        match (kind, block) {
+            (Kind::Equation, Block::MathJax(tex)) => match number {
+                Some(number) => leaf!(
+                    "<div{} class=\"equation\">\\[{} \\tag{{{}}}\\]</div>",
+                    attributes,
+                    encode(tex),
+                    number
+                ),
+                None => leaf!(
+                    "<div{} class=\"equation\">\\[{}\\]</div>",
+                    attributes,
+                    encode(tex)
+                ),
+            },
+            (Kind::Figure, Block::Paragraph(text)) => {
+                node!(leaf!("<figure{}>", attributes), leaf!("</figure>"); [
+                    leaf!(self.text(text)),
+                    leaf!("<figcaption>{}</figcaption>", caption)
+                ])
+            }
+            _ => {
+                // captions of tables and listings are above
+                node!(leaf!("<figure{}>", attributes), leaf!("</figure>"); [
+                    leaf!("<figcaption>{}</figcaption>", caption),
+                    self.block(block)
+                ])
+            }
+        }
+    }
+
+    /// Jupyter Notebook as a document; cells are never executed, nor the executors in markdown cells
+    fn notebook(&self, notebook: &Notebook, hide_code: bool, hide_output: bool) -> Html {
+        let tr = Self {
+            execute: false,
+            ..self.clone()
+        };
+        let language = notebook
+            .language()
+            .map(|lang| lang.trim_start_matches('@').to_string());
+        let mut html = node!(leaf!("<div class=\"notebook\">"), leaf!("</div>"));
+        for cell in notebook.cells.iter() {
+            match cell {
+                Cell::Markdown { source, .. } => {
+                    let mkd = parser::markdown(&(source.text() + "\n")).unwrap();
+                    let doc = tr.markdown(&mkd);
+                    html.push(doc.wrap("<div class=\"cell markdown\">", "</div>"));
+                }
+                Cell::Code {
+                    source, outputs, ..
+                } => {
+                    let mut div = node!(leaf!("<div class=\"cell code\">"), leaf!("</div>"));
+                    if !hide_code {
+                        let codeblock = Block::Code(language.clone(), source.text());
+                        div.push(tr.block(&codeblock));
+                    }
+                    if !hide_output && !outputs.is_empty() {
+                        let mut out = node!(leaf!("<div class=\"output\">"), leaf!("</div>"));
+                        for output in outputs.iter() {
+                            out.push(tr.notebook_output(output));
+                        }
+                        div.push(out);
+                    }
+                    html.push(div);
+                }
+                Cell::Raw { .. } => {}
+            }
+        }
+        html
+    }
+
+    fn notebook_output(&self, output: &Output) -> Html {
+        match output {
+            Output::Stream { name, text } => {
+                leaf!(
+                    "<pre><samp class=\"{}\">{}</samp></pre>",
+                    name,
+                    encode(&text.text())
+                )
+            }
+            Output::DisplayData { data, .. } | Output::ExecuteResult { data, .. } => {
+                if let Some(html) = mime_text(data, "text/html") {
+                    leaf!("{}", html)
+                } else if let Some(svg) = mime_text(data, "image/svg+xml") {
+                    let encoded = BASE64_STANDARD.encode(svg);
+                    leaf!("<img src=\"data:image/svg+xml;base64,{}\">", encoded)
+                } else if let Some(png) = mime_text(data, "image/png") {
+                    let encoded: String = png.split_whitespace().collect();
+                    leaf!("<img src=\"data:image/png;base64,{}\">", encoded)
+                } else if let Some(jpeg) = mime_text(data, "image/jpeg") {
+                    let encoded: String = jpeg.split_whitespace().collect();
+                    leaf!("<img src=\"data:image/jpeg;base64,{}\">", encoded)
+                } else if let Some(text) = mime_text(data, "text/plain") {
+                    leaf!("<pre><samp>{}</samp></pre>", encode(&text))
+                } else {
+                    leaf!("")
+                }
+            }
+            Output::Error {
+                ename,
+                evalue,
+                traceback,
+            } => {
+                lazy_static! {
+                    static ref ANSI: Regex = Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap();
+                }
+                let message = if traceback.is_empty() {
+                    format!("{}: {}", ename, evalue)
+                } else {
+                    ANSI.replace_all(&traceback.join("\n"), "").to_string()
+                };
+                leaf!("<pre><samp class=error>{}</samp></pre>", encode(&message))
+            }
         }
     }
 
@@ -158,7 +853,7 @@ impl Translator {
         }
     }
 
-    fn table(&self, aligns: &Vec<Align>, content: &Vec<Vec<Text>>, has_header: bool) -> Html {
+    fn table(&self, aligns: &[Align], content: &[Vec<Text>], has_header: bool) -> Html {
         let mut html = node!(leaf!("<table>"), leaf!("</table>"));
         // thead
         if has_header {
@@ -173,10 +868,10 @@ impl Translator {
         // tbody
         let start = if has_header { 1 } else { 0 };
         let mut tbody = node!(leaf!("<tbody>"), leaf!("</tbody>"));
-        for i in start..content.len() {
+        for (i, row) in content.iter().enumerate().skip(start) {
             let class = if (i - start) % 2 == 0 { "odd" } else { "even" };
             let mut tr = node![leaf!("<tr class={}>", class), leaf!("</tr>")];
-            for (t, align) in content[i].iter().zip(aligns.iter()) {
+            for (t, align) in row.iter().zip(aligns.iter()) {
                 tr.push(leaf!(self.cell(t, align, false)));
             }
             tbody.push(tr);
@@ -195,15 +890,39 @@ impl Translator {
     }
 
     fn text(&self, text: &Text) -> String {
-        text.iter()
-            .map(|s| self.inline(s))
-            .collect::<Vec<_>>()
-            .join(" ")
+        let in_tag = std::mem::take(&mut self.state.borrow_mut().in_tag);
+        // punctuations are attached to the preceding references and citations
+        let mut pieces: Vec<String> = vec![];
+        for (i, inline) in text.iter().enumerate() {
+            self.state.borrow_mut().closing_single = text[i + 1..]
+                .iter()
+                .find_map(|inline| match inline {
+                    Inline::Plaintext(t) => smart::closes_single(t),
+                    _ => None,
+                })
+                .unwrap_or(false);
+            let piece = self.inline(inline);
+            let prev = text.get(i.wrapping_sub(1));
+            let attached = matches!(prev, Some(Inline::Reference(_) | Inline::Citation(..)))
+                && !spaced(prev, inline);
+            match pieces.last_mut() {
+                Some(last) if attached => *last += &piece,
+                _ => pieces.push(piece),
+            }
+        }
+        self.state.borrow_mut().in_tag = in_tag;
+        pieces.join(" ")
     }
 
     fn inline(&self, inline: &Inline) -> String {
         match inline {
-            Inline::Link(text, url) => format!("<a href=\"{}\">{}</a>", url, self.text(text)),
+            Inline::Link(text, url) => {
+                let text = self.without_abbreviations(self.skips("links"), || self.text(text));
+                format!("<a href=\"{}\">{}</a>", self.url(url), text)
+            }
+            Inline::HyperLink(target) if WikiLink::parse(target).is_some() => {
+                self.wikilink(target, &WikiLink::parse(target).unwrap())
+            }
             Inline::HyperLink(url) => {
                 if let Some(title) = WebPage::new(url.to_string()).title() {
                     format!("<a href=\"{}\">{}</a>", url, encode(&title))
@@ -211,7 +930,9 @@ impl Translator {
                     format!("<a href=\"{}\">{}</a>", url, url)
                 }
             }
-            Inline::Image(alt, image) => format!("<img src=\"{}\" alt=\"{}\" />", image, alt),
+            Inline::Image(alt, image) => {
+                format!("<img src=\"{}\" alt=\"{}\" />", self.url(image), alt)
+            }
             Inline::Code(text) => format!("<code>{}</code>", encode(text)),
             Inline::Emphasis(text) => format!("<em>{}</em>", self.text(text)),
             Inline::Strong(text) => format!("<strong>{}</strong>", self.text(text)),
@@ -219,12 +940,62 @@ impl Translator {
                 format!("<em><strong>{}</strong></em>", self.text(text))
             }
             Inline::Deleted(text) => format!("<del>{}</del>", self.text(text)),
-            Inline::Plaintext(text) => text.to_string(),
-            Inline::Newline => format!("<br />"),
+            Inline::Plaintext(text) => {
+                let in_tag = self.state.borrow().in_tag;
+                self.state.borrow_mut().in_tag = html::in_tag(text, in_tag);
+                let text = if self.smart {
+                    let lang = self.variables.get("lang").map(|lang| lang.as_str());
+                    let closing = self.state.borrow().closing_single;
+                    smart::convert(text, &smart::quotes(lang), in_tag, closing)
+                } else {
+                    text.to_string()
+                };
+                let state = self.state.borrow();
+                if state.abbr_skipped > 0 {
+                    text
+                } else {
+                    state.glossary.abbreviate(&text, in_tag)
+                }
+            }
+            Inline::Newline => "<br />".to_string(),
             Inline::Comment(text) => format!("<!--{}-->", text),
             Inline::MathJax(tex) => format!("\\({}\\)", encode(tex)),
+            Inline::Variable(name, _) => format!("${{{}}}", encode(name)),
+            Inline::Reference(label) => {
+                let location = self.location();
+                self.state.borrow_mut().crossref.refer(label, location)
+            }
+            Inline::Index(text, term) => {
+                let term = term.clone().unwrap_or_else(|| {
+                    html_escape::decode_html_entities(&text_content(text)).to_string()
+                });
+                let mut state = self.state.borrow_mut();
+                let anchor = format!("idx-{}", state.index.len() + 1);
+                state.index.push(index::Entry {
+                    term,
+                    anchor: anchor.to_string(),
+                });
+                drop(state);
+                format!(
+                    "<span id=\"{}\" class=\"index-entry\"></span>{}",
+                    anchor,
+                    self.text(text)
+                )
+            }
+            Inline::Glossary(term) => {
+                let location = self.location();
+                self.state.borrow_mut().glossary.refer(term, &location)
+            }
+            Inline::Citation(citations, bracketed) => {
+                let location = self.location();
+                match self.state.borrow_mut().bibliography.as_mut() {
+                    Some(bibliography) => bibliography.cite(citations, *bracketed, &location),
+                    // as it is without bibliography
+                    None => encode(&citation_source(citations, *bracketed)),
+                }
+            }
             Inline::Emoji(shortcode) => {
-                if let Some(emoji) = emojis::get_by_shortcode(&shortcode) {
+                if let Some(emoji) = emojis::get_by_shortcode(shortcode) {
                     emoji.to_string()
                 } else {
                     // fail-over
@@ -236,10 +1007,36 @@ impl Translator {
 }
 
 fn inner_text(block: &Block) -> String {
+    match block {
+        Block::Heading(_, label, _) => text_content(label),
+        Block::Paragraph(text) => text_content(text),
+        Block::Quoted(text) => text_content(text),
+        _ => String::new(),
+    }
+}
+
+/// Chinese, Japanese (and their punctuations); Hangul is separated by spaces
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{2E80}'..='\u{2FDF}'     // radicals
+        | '\u{3000}'..='\u{303F}'   // punctuations
+        | '\u{3040}'..='\u{30FF}'   // hiragana, katakana
+        | '\u{3100}'..='\u{312F}'   // bopomofo
+        | '\u{31F0}'..='\u{31FF}'   // katakana extension
+        | '\u{3400}'..='\u{4DBF}'   // extension A
+        | '\u{4E00}'..='\u{9FFF}'   // ideographs
+        | '\u{F900}'..='\u{FAFF}'   // compatibility ideographs
+        | '\u{FF01}'..='\u{FF60}'   // fullwidth forms
+        | '\u{FF61}'..='\u{FF9F}'   // halfwidth katakana
+        | '\u{20000}'..='\u{3FFFF}' // extensions B-
+    )
+}
+
+/// Text without tags (HTML-escaped)
+fn text_content(text: &Text) -> String {
     fn from_text(text: &Text) -> String {
         text.iter()
-            .map(from_inline)
-            .flatten()
+            .filter_map(from_inline)
             .collect::<Vec<String>>()
             .join(" ")
     }
@@ -258,13 +1055,113 @@ fn inner_text(block: &Block) -> String {
             Inline::Comment(_) => None,
             Inline::MathJax(tex) => Some(encode(tex)),
             Inline::Emoji(shortcode) => Some(shortcode.to_string()),
+            Inline::Variable(name, _) => Some(format!("${{{}}}", encode(name))),
+            Inline::Reference(label) => Some(format!("@{}", encode(label))),
+            Inline::Citation(citations, bracketed) => {
+                Some(encode(&citation_source(citations, *bracketed)))
+            }
+            Inline::Glossary(term) => Some(encode(term)),
+            Inline::Index(text, _) if text.is_empty() => None,
+            Inline::Index(text, _) => Some(from_text(text)),
         }
     }
-    match block {
-        Block::Heading(_, label) => from_text(label),
-        Block::Paragraph(text) => from_text(text),
-        Block::Quoted(text) => from_text(text),
-        _ => String::new(),
+    from_text(text)
+}
+
+/// HTML of a heading for the legacy ids; without the side effects of `Translator::inline`,
+/// e.g. index entries, citations, glossary references and wiki link reports
+fn id_html(text: &Text) -> String {
+    fn from_inline(inline: &Inline) -> String {
+        match inline {
+            Inline::Link(text, url) => format!("<a href=\"{}\">{}</a>", url, id_html(text)),
+            Inline::HyperLink(url) => format!("<a href=\"{}\">{}</a>", url, url),
+            Inline::Image(alt, image) => format!("<img src=\"{}\" alt=\"{}\" />", image, alt),
+            Inline::Code(text) => format!("<code>{}</code>", encode(text)),
+            Inline::Emphasis(text) => format!("<em>{}</em>", id_html(text)),
+            Inline::Strong(text) => format!("<strong>{}</strong>", id_html(text)),
+            Inline::EmphasisAndStrong(text) => {
+                format!("<em><strong>{}</strong></em>", id_html(text))
+            }
+            Inline::Deleted(text) => format!("<del>{}</del>", id_html(text)),
+            Inline::Plaintext(text) => text.to_string(),
+            Inline::Newline => String::from("<br />"),
+            Inline::Comment(text) => format!("<!--{}-->", text),
+            Inline::MathJax(tex) => format!("\\({}\\)", encode(tex)),
+            Inline::Emoji(shortcode) => match emojis::get_by_shortcode(shortcode) {
+                Some(emoji) => emoji.to_string(),
+                None => format!(":{}:", shortcode),
+            },
+            Inline::Index(text, _) => id_html(text),
+            _ => text_content(&vec![inline.clone()]),
+        }
+    }
+    text.iter().map(from_inline).collect::<Vec<_>>().join(" ")
+}
+
+/// `[@key, p. 33; @key2]` or `@key`
+fn citation_source(citations: &[Citation], bracketed: bool) -> String {
+    let items = citations
+        .iter()
+        .map(|c| match &c.locator {
+            Some(locator) => format!("@{}, {}", c.key, locator),
+            None => format!("@{}", c.key),
+        })
+        .collect::<Vec<_>>()
+        .join("; ");
+    if bracketed {
+        format!("[{}]", items)
+    } else {
+        items
+    }
+}
+
+/// Import target: `path?key=value&...#section-id[:content]`
+struct ImportTarget {
+    path: String,
+    query: Vec<(String, String)>,
+    section: Option<String>,
+    /// Without the heading of the section
+    content_only: bool,
+}
+
+impl ImportTarget {
+    fn new(target: &str) -> Self {
+        let (target, fragment) = match target.find('#') {
+            Some(i) => (&target[..i], Some(&target[i + 1..])),
+            None => (target, None),
+        };
+        let (section, content_only) = match fragment {
+            Some(fragment) => match fragment.strip_suffix(":content") {
+                Some(id) => (Some(id.to_string()), true),
+                None => (Some(fragment.to_string()), false),
+            },
+            None => (None, false),
+        };
+        let (path, query) = match target.find('?') {
+            Some(i) => (&target[..i], &target[i + 1..]),
+            None => (target, ""),
+        };
+        let query = query
+            .split('&')
+            .filter(|kv| !kv.is_empty())
+            .map(|kv| match kv.find('=') {
+                Some(i) => (kv[..i].to_string(), kv[i + 1..].to_string()),
+                None => (kv.to_string(), String::new()),
+            })
+            .collect();
+        Self {
+            path: path.to_string(),
+            query,
+            section,
+            content_only,
+        }
+    }
+
+    /// `?hide=code,output`
+    fn hides(&self, name: &str) -> bool {
+        self.query.iter().any(|(key, value)| {
+            key == "hide" && value.split(',').any(|v| v.trim_end_matches('s') == name)
+        })
     }
 }
 
@@ -284,13 +1181,84 @@ fn find(path: &String, filedir: &Option<String>) -> Option<String> {
     None
 }
 
-fn encode(html: &String) -> String {
-    html_escape::encode_safe(html).to_string()
+/// Relative URL path; not absolute URLs, absolute paths, anchors nor data URIs
+fn is_relative_url(url: &str) -> bool {
+    lazy_static! {
+        static ref SCHEME: Regex = Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.\-]*:").unwrap();
+    }
+    !(url.is_empty()
+        || url.starts_with('/')
+        || url.starts_with('#')
+        || url.starts_with('?')
+        || SCHEME.is_match(url))
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap().join(path)
+    };
+    normalize(&path)
+}
+
+/// Resolve `.` and `..` lexically
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) => {}
+                _ => normalized.push(".."),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
 }
 
-fn percent_encode(input: &String) -> String {
-    const CUSTOM_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_');
-    utf8_percent_encode(&input, &CUSTOM_ENCODE_SET).to_string()
+/// Relative path from the directory `from` to the directory `to`
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    for component in to[common..].iter() {
+        path.push(component);
+    }
+    path
+}
+
+/// ` id="x" class="a b" data-key="value"`; `if` conditions are excluded
+fn attributes_html(attributes: &Attributes) -> String {
+    let mut html = String::new();
+    if let Some(id) = &attributes.id {
+        html += &format!(" id=\"{}\"", encode(id));
+    }
+    if !attributes.classes.is_empty() {
+        html += &format!(" class=\"{}\"", encode(&attributes.classes.join(" ")));
+    }
+    for (key, value) in attributes.pairs.iter() {
+        if key != "if" {
+            html += &format!(" data-{}=\"{}\"", key, encode(value));
+        }
+    }
+    html
+}
+
+fn encode(html: &String) -> String {
+    html_escape::encode_safe(html).to_string()
 }
 
 #[cfg(test)]
@@ -305,4 +1273,507 @@ mod test_translator {
             "f(x) &lt; g(x) &gt; 1".to_string()
         );
     }
+
+    #[test]
+    fn test_notebook() {
+        let json = r###"{
+            "cells": [
+                {"cell_type": "markdown", "metadata": {}, "source": ["## Analysis"]},
+                {"cell_type": "code", "execution_count": 1, "metadata": {}, "source": ["x < 1"],
+                 "outputs": [
+                    {"output_type": "execute_result", "execution_count": 1, "metadata": {},
+                     "data": {"text/plain": ["True"]}},
+                    {"output_type": "display_data", "metadata": {},
+                     "data": {"image/png": "iVBOR\n", "text/plain": ["<Figure>"]}},
+                    {"output_type": "error", "ename": "E", "evalue": "v",
+                     "traceback": ["\u001b[0;31mE\u001b[0m: v"]}
+                 ]},
+                {"cell_type": "markdown", "metadata": {}, "source": []}
+            ],
+            "metadata": {"language_info": {"name": "python"}},
+            "nbformat": 4,
+            "nbformat_minor": 5
+        }"###;
+        let notebook = Notebook::from_json(json).unwrap();
+        let tr = Translator::new(None);
+        let show = |html: Html| HtmlDoc::new(String::new(), vec![html]).show(true, 0);
+        assert_eq!(
+            show(tr.notebook(&notebook, false, false)),
+            concat!(
+                "<div class=\"notebook\">",
+                "<div class=\"cell markdown\"><h2 id=\"2-Analysis\">Analysis</h2></div>",
+                "<div class=\"cell code\">",
+                "<pre><code class=\"code language-python\">x &lt; 1</code></pre>",
+                "<div class=\"output\">",
+                "<pre><samp>True</samp></pre>",
+                "<img src=\"data:image/png;base64,iVBOR\">",
+                "<pre><samp class=error>E: v</samp></pre>",
+                "</div></div>",
+                "<div class=\"cell markdown\"></div>",
+                "</div>\n"
+            )
+        );
+        // ids are unique in a document
+        assert_eq!(
+            show(tr.notebook(&notebook, true, true)),
+            concat!(
+                "<div class=\"notebook\">",
+                "<div class=\"cell markdown\"><h2 id=\"2-Analysis-1\">Analysis</h2></div>",
+                "<div class=\"cell code\"></div>",
+                "<div class=\"cell markdown\"></div>",
+                "</div>\n"
+            )
+        );
+        // executor fences in markdown cells are only shown
+        let json = r###"{
+            "cells": [
+                {"cell_type": "markdown", "metadata": {}, "source": ["```@bash\n", "echo hi\n", "```"]}
+            ],
+            "metadata": {},
+            "nbformat": 4,
+            "nbformat_minor": 5
+        }"###;
+        let notebook = Notebook::from_json(json).unwrap();
+        assert_eq!(
+            show(tr.notebook(&notebook, false, false)),
+            concat!(
+                "<div class=\"notebook\">",
+                "<div class=\"cell markdown\">",
+                "<pre><code class=\"code language-@bash\">echo hi\n</code></pre>",
+                "</div></div>\n"
+            )
+        );
+        assert!(tr.execute);
+    }
+
+    #[test]
+    fn test_rebase_url() {
+        let tr = Translator::new(None).imported("chapters/a.md");
+        assert_eq!(tr.url("img/fig1.png"), "chapters/img/fig1.png");
+        assert_eq!(tr.url("./img/fig1.png"), "chapters/img/fig1.png");
+        assert_eq!(tr.url("../other.html#sec"), "other.html#sec");
+        assert_eq!(tr.url("#sec"), "#sec");
+        assert_eq!(tr.url("/img/fig1.png"), "/img/fig1.png");
+        assert_eq!(
+            tr.url("https://example.com/a.png"),
+            "https://example.com/a.png"
+        );
+        assert_eq!(tr.url("//example.com/a.png"), "//example.com/a.png");
+        assert_eq!(
+            tr.url("data:image/png;base64,AAAA"),
+            "data:image/png;base64,AAAA"
+        );
+        assert_eq!(tr.url("mailto:me@example.com"), "mailto:me@example.com");
+        let tr = Translator::new(Some(String::from("chapters")));
+        assert_eq!(tr.url("img/fig1.png"), "img/fig1.png");
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(Path::new("/a/b"), Path::new("/a/c/d")),
+            PathBuf::from("../c/d")
+        );
+        assert_eq!(
+            relative_path(Path::new("/a"), Path::new("/a")),
+            PathBuf::new()
+        );
+        assert_eq!(normalize(Path::new("a/./b/../c")), PathBuf::from("a/c"));
+        assert_eq!(normalize(Path::new("../a")), PathBuf::from("../a"));
+    }
+
+    #[test]
+    fn test_expand_imports() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("ch")).unwrap();
+        std::fs::write(
+            dir.path().join("ch/b.md"),
+            "![a](img/a.png) [c](c.md) [web](https://example.com/)\n",
+        )
+        .unwrap();
+        let tr = Translator::new(None).for_file(Some(dir.path().to_string_lossy().to_string()));
+        assert_eq!(
+            tr.expand_imports(&vec![Block::Import(String::from("ch/b.md"))]),
+            vec![Block::Paragraph(vec![
+                Inline::Image(String::from("a"), String::from("ch/img/a.png")),
+                Inline::Link(
+                    vec![Inline::Plaintext(String::from("c"))],
+                    String::from("ch/c.md")
+                ),
+                Inline::Link(
+                    vec![Inline::Plaintext(String::from("web"))],
+                    String::from("https://example.com/")
+                ),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_import_front_matter() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("intro.md"),
+            "---\ntitle: Chapter\n---\n# Chap\n",
+        )
+        .unwrap();
+        let tr = Translator::new(None).for_file(Some(dir.path().to_string_lossy().to_string()));
+        let mkd = vec![Block::Import(String::from("intro.md"))];
+        assert_eq!(
+            tr.markdown(&mkd).show(true, 0),
+            "<div><h1 class=\"title\" id=\"1-Chap\">Chap</h1></div>\n"
+        );
+        assert_eq!(
+            tr.expand_imports(&mkd),
+            vec![Block::Heading(
+                1,
+                vec![Inline::Plaintext(String::from("Chap"))],
+                Attributes::default()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_import_target() {
+        let target = ImportTarget::new("analysis.ipynb?hide=code");
+        assert_eq!(target.path, "analysis.ipynb");
+        assert!(target.hides("code"));
+        assert!(!target.hides("output"));
+        let target = ImportTarget::new("analysis.ipynb?hide=code,outputs");
+        assert!(target.hides("code"));
+        assert!(target.hides("output"));
+        let target = ImportTarget::new("guide.md#2-Installation:content");
+        assert_eq!(target.path, "guide.md");
+        assert_eq!(target.section, Some(String::from("2-Installation")));
+        assert!(target.content_only);
+    }
+
+    #[test]
+    fn test_section() {
+        let mkd = parser::markdown(
+            "# Guide\n## Installation\ninstall\n\n### Linux\nlinux\n\n## Usage\nusage\n",
+        )
+        .unwrap();
+        let tr = Translator::new(None);
+        assert_eq!(
+            tr.section(&mkd, "2-Installation", false),
+            Ok(mkd[1..5].to_vec())
+        );
+        assert_eq!(
+            tr.section(&mkd, "2-Installation", true),
+            Ok(mkd[2..5].to_vec())
+        );
+        assert_eq!(tr.section(&mkd, "2-Usage", false), Ok(mkd[5..7].to_vec()));
+        // de-duplicated ids
+        let dup = parser::markdown("## Install\na\n\n## Install\nb\n").unwrap();
+        assert_eq!(
+            tr.section(&dup, "2-Install-1", true),
+            Ok(dup[3..4].to_vec())
+        );
+        assert_eq!(tr.section(&mkd, "1-Guide", true), Ok(mkd[1..7].to_vec()));
+        assert_eq!(
+            tr.section(&mkd, "nothing", false),
+            Err(vec![
+                String::from("1-Guide"),
+                String::from("2-Installation"),
+                String::from("3-Linux"),
+                String::from("2-Usage"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_heading_side_effects() {
+        let mkd = parser::markdown("## [nom]{.idx} Parser\n\n## [[No Such Page]]\n").unwrap();
+        let tr = Translator::new(None).with_source("a.md");
+        assert_eq!(
+            tr.markdown(&mkd).show(true, 0),
+            concat!(
+                "<h2 id=\"2-nom%20Parser\"><span id=\"idx-1\" class=\"index-entry\"></span>nom Parser</h2>",
+                "<h2 id=\"2-%3Ca%20href%3D%22No%20Such%20Page%22%3ENo%20Such%20Page%3C%2Fa%3E\">",
+                "<span class=\"wikilink-broken\">No Such Page</span></h2>\n"
+            )
+        );
+        assert_eq!(
+            tr.index(),
+            index::render(&[index::Entry {
+                term: String::from("nom"),
+                anchor: String::from("idx-1"),
+            }])
+        );
+        assert_eq!(tr.broken_links().len(), 1);
+    }
+
+    #[test]
+    fn test_number_sections() {
+        let mkd =
+            parser::markdown("# Title\n\n## A\n\n### B\n\n## Preface {.unnumbered}\n\n## C {#c}\n")
+                .unwrap();
+        let tr = Translator::new(None).with_number_sections(true);
+        let doc = tr.markdown(&mkd);
+        assert_eq!(
+            doc.show(true, 0),
+            concat!(
+                "<h1 class=\"title\" id=\"1-Title\">Title</h1>",
+                "<h2 id=\"2-A\"><span class=\"section-number\">1</span> A</h2>",
+                "<h3 id=\"3-B\"><span class=\"section-number\">1.1</span> B</h3>",
+                "<h2 class=\"unnumbered\" id=\"2-Preface\">Preface</h2>",
+                "<h2 id=\"c\"><span class=\"section-number\">2</span> C</h2>\n"
+            )
+        );
+        // continues in the other inputs
+        let other = tr.for_file(None);
+        other.markdown(&parser::markdown("### D\n").unwrap());
+        let numbers: Vec<_> = tr.headings().into_iter().map(|h| h.number).collect();
+        assert_eq!(
+            numbers,
+            vec![
+                None,
+                Some(String::from("1")),
+                Some(String::from("1.1")),
+                None,
+                Some(String::from("2")),
+                Some(String::from("2.1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_number_sections_chapters() {
+        // h1 after the title are numbered, and skipped levels are not shown as zeros
+        let mkd = parser::markdown(
+            "# Book
+
+# One
+
+## A
+
+#### B
+
+# Two
+
+### C
+",
+        )
+        .unwrap();
+        let tr = Translator::new(None).with_number_sections(true);
+        tr.markdown(&mkd);
+        let numbers: Vec<_> = tr.headings().into_iter().map(|h| h.number).collect();
+        assert_eq!(
+            numbers,
+            vec![
+                None,
+                Some(String::from("1")),
+                Some(String::from("1.1")),
+                Some(String::from("1.1.1")),
+                Some(String::from("2")),
+                Some(String::from("2.1")),
+            ]
+        );
+        let mkd = parser::markdown(
+            "## A
+
+#### B
+",
+        )
+        .unwrap();
+        let tr = Translator::new(None).with_number_sections(true);
+        tr.markdown(&mkd);
+        let numbers: Vec<_> = tr.headings().into_iter().map(|h| h.number).collect();
+        assert_eq!(
+            numbers,
+            vec![Some(String::from("1")), Some(String::from("1.1"))]
+        );
+    }
+
+    #[test]
+    fn test_slug() {
+        let mkd = parser::markdown("# A *b*\n\n## A b {#x}\n\n## A b\n\n## A&b\n").unwrap();
+        let tr = Translator::new(None).with_slug(Slug::Github);
+        assert_eq!(
+            tr.markdown(&mkd).show(true, 0),
+            concat!(
+                "<h1 class=\"title\" id=\"a-b\">A <em>b</em></h1>",
+                "<h2 id=\"x\">A b</h2>",
+                "<h2 id=\"a-b-1\">A b</h2>",
+                "<h2 id=\"ab\">A&b</h2>\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_crossref() {
+        let mkd = parser::markdown(concat!(
+            "See @fig:arch, @tbl:results and @eq:e.\n\n",
+            "## Design {#sec:design}\n\n",
+            "![Architecture](arch.png){#fig:arch}\n\n",
+            "| a |\n",
+            ": Results {#tbl:results}\n\n",
+            "$$e = mc^2$$ {#eq:e}\n\n",
+            "@sec:design @fig:none\n",
+        ))
+        .unwrap();
+        let tr = Translator::new(None)
+            .with_source("a.md")
+            .with_number_sections(true);
+        let mut doc = tr.markdown(&mkd);
+        tr.resolve(&mut doc);
+        assert_eq!(
+            doc.show(true, 0),
+            concat!(
+                "<p>See <a class=\"xref\" href=\"#fig:arch\">Figure 1</a>,",
+                " <a class=\"xref\" href=\"#tbl:results\">Table 1</a> and",
+                " <a class=\"xref\" href=\"#eq:e\">Equation 1</a>.</p>",
+                "<h2 id=\"sec:design\"><span class=\"section-number\">1</span> Design</h2>",
+                "<figure id=\"fig:arch\"><img src=\"arch.png\" alt=\"Architecture\" />",
+                "<figcaption>Figure 1: Architecture</figcaption></figure>",
+                "<figure id=\"tbl:results\"><figcaption>Table 1: Results</figcaption>",
+                "<table><tbody><tr class=odd><td align=left>a</td></tr></tbody></table></figure>",
+                "<div id=\"eq:e\" class=\"equation\">\\[e = mc^2 \\tag{1}\\]</div>",
+                "<p><a class=\"xref\" href=\"#sec:design\">Section 1</a>",
+                " <span class=\"xref-missing\">@fig:none</span></p>\n"
+            )
+        );
+        let mkd = parser::markdown("See @fig:arch, and @fig:arch.\n").unwrap();
+        let mut doc = tr.markdown(&mkd);
+        tr.resolve(&mut doc);
+        assert_eq!(
+            doc.show(true, 0),
+            concat!(
+                "<p>See <a class=\"xref\" href=\"#fig:arch\">Figure 1</a>, and",
+                " <a class=\"xref\" href=\"#fig:arch\">Figure 1</a>.</p>\n"
+            )
+        );
+        assert_eq!(
+            tr.dangling_references()
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>(),
+            vec!["Dangling reference: @fig:none in a.md (after #sec:design)"]
+        );
+        // other markups are as they are
+        let mkd = parser::markdown("`Rust` . **NOTE** : (*a* )\n").unwrap();
+        assert_eq!(
+            tr.markdown(&mkd).show(true, 0),
+            "<p><code>Rust</code> . <strong>NOTE</strong> : ( <em>a</em> )</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_cjk_lines() {
+        let tr = Translator::new(None);
+        let mkd = parser::markdown(
+            "# 日本 語\n\n東京 大阪\n名古屋と\nEnglish words\nです。\n\n> 引用の\n> 文章\n\n한국어\n문장\n",
+        )
+        .unwrap();
+        assert_eq!(
+            tr.markdown(&mkd).show(true, 0),
+            concat!(
+                "<h1 class=\"title\" id=\"1-%E6%97%A5%E6%9C%AC%20%E8%AA%9E\">日本 語</h1>",
+                "<p>東京 大阪名古屋と English words です。</p>",
+                "<blockquote>引用の文章</blockquote>",
+                "<p>한국어 문장</p>\n"
+            )
+        );
+        assert_eq!(
+            inner_text(&mkd[1]),
+            "東京 大阪名古屋と English words です。"
+        );
+    }
+
+    #[test]
+    fn test_smart() {
+        let mkd = parser::markdown("<a href=\"x.html\" title=\"t\">\"link\"</a> -- \"quoted\"\n")
+            .unwrap();
+        let tr = Translator::new(None).with_smart(true);
+        assert_eq!(
+            tr.markdown(&mkd).show(true, 0),
+            "<p><a href=\"x.html\" title=\"t\">“link”</a> – “quoted”</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_smart_elision() {
+        let mkd = parser::markdown("'90s and 'tis, but 'quoted words' here\n").unwrap();
+        for (lang, quoted) in [
+            ("en", "‘quoted words’"),
+            ("ja", "『quoted words』"),
+            ("fr", "‹\u{a0}quoted words\u{a0}›"),
+        ] {
+            let variables = HashMap::from([(String::from("lang"), String::from(lang))]);
+            let tr = Translator::new(None)
+                .with_smart(true)
+                .with_variables(variables, false);
+            assert_eq!(
+                tr.markdown(&mkd).show(true, 0),
+                format!("<p>’90s and ’tis, but {} here</p>\n", quoted)
+            );
+        }
+    }
+
+    #[test]
+    fn test_abbreviation() {
+        let mkd = parser::markdown(
+            "# HTML\n\nHTML [HTML](a.html) XHTML\n\n*[HTML]: HyperText Markup Language\n",
+        )
+        .unwrap();
+        let tr = Translator::new(None).with_abbr_skip(vec![String::from("links")]);
+        assert_eq!(
+            tr.markdown(&mkd).show(true, 0),
+            concat!(
+                "<h1 class=\"title\" id=\"1-HTML\"><abbr title=\"HyperText Markup Language\">HTML</abbr></h1>",
+                "<p><abbr title=\"HyperText Markup Language\">HTML</abbr> <a href=\"a.html\">HTML</a> XHTML</p>\n"
+            )
+        );
+        let mkd = parser::markdown(
+            "<a title=\"HTML page\">HTML</a>\n\n*[HTML]: HyperText Markup Language\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Translator::new(None).markdown(&mkd).show(true, 0),
+            "<p><a title=\"HTML page\"><abbr title=\"HyperText Markup Language\">HTML</abbr></a></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_wikilink() {
+        let mkd = parser::markdown("[[No Such Page|label]]\n").unwrap();
+        let tr = Translator::new(None).with_source("a.md");
+        assert_eq!(
+            tr.markdown(&mkd).show(true, 0),
+            "<p><span class=\"wikilink-broken\">label</span></p>\n"
+        );
+        assert_eq!(
+            tr.broken_links(),
+            vec![BrokenLink {
+                target: String::from("No Such Page|label"),
+                location: String::from("a.md"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_index() {
+        let mkd = parser::markdown("[Parser]{.idx} and {.index term=\"parser!nom\"}\n").unwrap();
+        let tr = Translator::new(None);
+        assert_eq!(
+            tr.markdown(&mkd).show(true, 0),
+            concat!(
+                "<p><span id=\"idx-1\" class=\"index-entry\"></span>Parser and ",
+                "<span id=\"idx-2\" class=\"index-entry\"></span></p>\n"
+            )
+        );
+        assert_eq!(
+            tr.index(),
+            index::render(&[
+                index::Entry {
+                    term: String::from("Parser"),
+                    anchor: String::from("idx-1"),
+                },
+                index::Entry {
+                    term: String::from("parser!nom"),
+                    anchor: String::from("idx-2"),
+                },
+            ])
+        );
+    }
 }
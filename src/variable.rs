@@ -0,0 +1,247 @@
+use crate::entity::markdown::{try_map_text, Inline, Markdown, Text};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndefinedVariable(String);
+
+impl std::fmt::Display for UndefinedVariable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Undefined variable: {}", self.0)
+    }
+}
+impl std::error::Error for UndefinedVariable {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+/// Value of a variable; `variables` (-V and the front matter) first, then the environment
+pub fn lookup(name: &str, variables: &HashMap<String, String>) -> Option<String> {
+    variables
+        .get(name)
+        .cloned()
+        .or_else(|| std::env::var(name).ok())
+}
+
+/// Substitute `${name}` and `${name:-default}` in the source, except in code and `\${...}`,
+/// so that the values join the surrounding text, e.g. `v${version}.`.
+/// The values are escaped, and never make markups nor raw HTML.
+/// Undefined variables are left for `interpolate` (after the conditional blocks are evaluated).
+pub fn substitute(input: &str, variables: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut fenced = false;
+    for line in input.split_inclusive('\n') {
+        if fenced || line.trim_start().starts_with("```") {
+            // the closing fence may follow the opening one on the same line
+            let fences = line.matches("```").count();
+            fenced = (fenced as usize + fences) % 2 == 1;
+            out += line;
+            continue;
+        }
+        let mut rest = line;
+        while let Some(i) = rest.find(['`', '\\', '$']) {
+            out += &rest[..i];
+            rest = &rest[i..];
+            if rest.starts_with('`') {
+                // code span
+                let end = rest[1..].find('`').map(|j| j + 2).unwrap_or(1);
+                out += &rest[..end];
+                rest = &rest[end..];
+            } else if rest.starts_with('\\') {
+                let end = rest[1..].chars().next().map_or(1, |c| 1 + c.len_utf8());
+                out += &rest[..end];
+                rest = &rest[end..];
+            } else if let Some((name, default, end)) = reference(rest) {
+                match lookup(name, variables).or(default.map(String::from)) {
+                    Some(value) => out += &escape(&value),
+                    None => out += &rest[..end],
+                }
+                rest = &rest[end..];
+            } else {
+                out += "$";
+                rest = &rest[1..];
+            }
+        }
+        out += rest;
+    }
+    out
+}
+
+/// Value as a plaintext of the source
+fn escape(value: &str) -> String {
+    let mut out = String::new();
+    for c in value.chars() {
+        match c {
+            '<' => out += "&lt;",
+            '>' => out += "&gt;",
+            '!' | '#' | '$' | '%' | '*' | '+' | '-' | '.' | ':' | '@' | '[' | '\\' | ']' | '_'
+            | '`' | '{' | '|' | '}' | '~' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// `${name}` or `${name:-default}` at the beginning: (name, default, length)
+fn reference(input: &str) -> Option<(&str, Option<&str>, usize)> {
+    let inner = input.strip_prefix("${")?;
+    let close = inner.find(['}', '\n'])?;
+    if !inner[close..].starts_with('}') {
+        return None;
+    }
+    let (name, default) = match inner[..close].find(":-") {
+        Some(i) => (&inner[..i], Some(&inner[i + 2..close])),
+        None => (&inner[..close], None),
+    };
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-' || c == '.';
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        return None;
+    }
+    Some((name, default, close + 3))
+}
+
+/// Substitute the `${name}` and `${name:-default}` inlines, e.g. of the JSON AST.
+/// Undefined variables are left as they are, or errors when `strict`.
+pub fn interpolate(
+    mkd: &Markdown,
+    variables: &HashMap<String, String>,
+    strict: bool,
+) -> Result<Markdown, UndefinedVariable> {
+    fn from_text(
+        text: &Text,
+        variables: &HashMap<String, String>,
+        strict: bool,
+    ) -> Result<Text, UndefinedVariable> {
+        text.iter()
+            .map(|inline| from_inline(inline, variables, strict))
+            .collect()
+    }
+    fn from_inline(
+        inline: &Inline,
+        variables: &HashMap<String, String>,
+        strict: bool,
+    ) -> Result<Inline, UndefinedVariable> {
+        Ok(match inline {
+            Inline::Variable(name, default) => match lookup(name, variables) {
+                Some(value) => Inline::Plaintext(value),
+                None => match default {
+                    Some(default) => Inline::Plaintext(default.to_string()),
+                    None if strict => return Err(UndefinedVariable(name.to_string())),
+                    None => inline.clone(),
+                },
+            },
+            Inline::Link(t, url) => Inline::Link(from_text(t, variables, strict)?, url.to_string()),
+            Inline::Emphasis(t) => Inline::Emphasis(from_text(t, variables, strict)?),
+            Inline::Strong(t) => Inline::Strong(from_text(t, variables, strict)?),
+            Inline::EmphasisAndStrong(t) => {
+                Inline::EmphasisAndStrong(from_text(t, variables, strict)?)
+            }
+            Inline::Deleted(t) => Inline::Deleted(from_text(t, variables, strict)?),
+            _ => inline.clone(),
+        })
+    }
+    try_map_text(mkd, &mut |t: &Text| from_text(t, variables, strict))
+}
+
+#[cfg(test)]
+mod test_variable {
+    use crate::entity::markdown::*;
+    use crate::parser;
+    use crate::translator::Translator;
+    use crate::variable::*;
+
+    #[test]
+    fn test_interpolate() {
+        let mut variables = HashMap::new();
+        variables.insert(String::from("product"), String::from("unidoc"));
+        let mkd = parser::markdown(
+            "# ${product} ${version:-dev}\n\n- **${product}**\n\n| ${undefined} |\n",
+        )
+        .unwrap();
+        assert_eq!(
+            interpolate(&mkd, &variables, false),
+            Ok(vec![
+                Block::Heading(
+                    1,
+                    vec![
+                        Inline::Plaintext(String::from("unidoc")),
+                        Inline::Plaintext(String::from("dev")),
+                    ],
+                    Attributes::default()
+                ),
+                Block::ListBlock(List::new(
+                    ListOrderType::Unordered,
+                    vec![ListItem::new(
+                        None,
+                        vec![Inline::Strong(vec![Inline::Plaintext(String::from(
+                            "unidoc"
+                        ))])],
+                        None
+                    )]
+                )),
+                Block::Table(
+                    vec![Align::Left],
+                    vec![vec![vec![Inline::Variable(
+                        String::from("undefined"),
+                        None
+                    )]]],
+                    false
+                ),
+            ])
+        );
+        assert_eq!(
+            interpolate(&mkd, &variables, true),
+            Err(UndefinedVariable(String::from("undefined")))
+        );
+    }
+
+    #[test]
+    fn test_substitute() {
+        let variables = HashMap::from([(String::from("x"), String::from("1.0"))]);
+        let input = concat!(
+            "# v${x}.\n\n",
+            "`${x}` \\${x} ${y:-dev}, ${y}\n\n",
+            "```\n${x}\n```\n"
+        );
+        let content = substitute(input, &variables);
+        assert_eq!(
+            content,
+            concat!(
+                "# v1\\.0.\n\n",
+                "`${x}` \\${x} dev, ${y}\n\n",
+                "```\n${x}\n```\n"
+            )
+        );
+        let mkd = parser::markdown(&content).unwrap();
+        assert_eq!(
+            Translator::new(None).markdown(&mkd).show(true, 0),
+            concat!(
+                "<h1 class=\"title\" id=\"1-v1%2E0%2E\">v1.0.</h1>",
+                "<p><code>${x}</code> ${x} dev, ${y}</p>",
+                "<pre><code class=\"code\">${x}\n</code></pre>\n"
+            )
+        );
+        // values are plaintexts, and do not change the surrounding markups
+        let variables = HashMap::from([(String::from("name"), String::from("*bold* <b> $1"))]);
+        let content = substitute("Cost $5 and ${name}.\n", &variables);
+        let mkd = parser::markdown(&content).unwrap();
+        assert_eq!(
+            Translator::new(None).markdown(&mkd).show(true, 0),
+            "<p>Cost $5 and *bold* &lt;b&gt; $1.</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_lookup() {
+        let variables = HashMap::new();
+        std::env::set_var("UNIDOC_TEST_VARIABLE", "env");
+        assert_eq!(
+            lookup("UNIDOC_TEST_VARIABLE", &variables),
+            Some(String::from("env"))
+        );
+        assert_eq!(lookup("UNIDOC_TEST_UNDEFINED", &variables), None);
+    }
+}
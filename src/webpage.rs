@@ -19,14 +19,13 @@ impl WebPage {
             .get(&url)
             .send()
             .ok()
-            .map(|response| response.text().ok())
-            .flatten();
+            .and_then(|response| response.text().ok());
         Self { url, content }
     }
     pub fn title(&self) -> Option<String> {
         if let Some(content) = self.content.as_ref() {
             let p = Pattern::new("<title>{{title}}</title>").unwrap();
-            let ms = p.matches(&content);
+            let ms = p.matches(content);
             if ms.is_empty() {
                 None
             } else {
@@ -38,14 +37,14 @@ impl WebPage {
     }
     pub fn meta(&self, property: &str) -> Option<String> {
         if let Some(content) = self.content.as_ref() {
-            let attributes = vec!["property", "name"];
+            let attributes = ["property", "name"];
             for attr in attributes.iter() {
                 let p = Pattern::new(&format!(
                     "<meta {}={} content={{{{content}}}} />",
                     attr, property
                 ))
                 .unwrap();
-                let ms = p.matches(&content);
+                let ms = p.matches(content);
                 if !ms.is_empty() {
                     return ms[0].get("content").cloned();
                 }
@@ -0,0 +1,135 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+/// `[[Other Page#Section|label]]` to a local document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WikiLink {
+    pub page: String,
+    pub section: Option<String>,
+    pub label: Option<String>,
+}
+
+impl WikiLink {
+    /// `None` for URLs (including hosts, e.g. `example.com`), which are links with the titles of the web pages
+    pub fn parse(target: &str) -> Option<Self> {
+        let target = target.trim();
+        if target.contains("://")
+            || target.starts_with("www.")
+            || is_host(target)
+            || target.is_empty()
+        {
+            return None;
+        }
+        let (target, label) = match target.split_once('|') {
+            Some((target, label)) => (target.trim(), Some(label.trim().to_string())),
+            None => (target, None),
+        };
+        let (page, section) = match target.split_once('#') {
+            Some((page, section)) => (page.trim(), Some(section.trim().to_string())),
+            None => (target, None),
+        };
+        Some(WikiLink {
+            page: page.to_string(),
+            section,
+            label,
+        })
+    }
+
+    /// Files of the page: `Other Page.md` then `other-page.md`
+    pub fn candidates(&self) -> Vec<String> {
+        let page = self.page.trim_end_matches(".md");
+        let kebab = page
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-")
+            .to_lowercase();
+        let mut files = vec![format!("{}.md", page)];
+        if kebab != page {
+            files.push(format!("{}.md", kebab));
+        }
+        files
+    }
+}
+
+/// `example.com` or `example.com/path`; not pages such as `notes.md` or `v1.0`
+fn is_host(target: &str) -> bool {
+    let host = target.split('/').next().unwrap_or_default();
+    match host.rsplit_once('.') {
+        Some((name, tld)) => {
+            !name.is_empty()
+                && tld != "md"
+                && tld.len() >= 2
+                && tld.chars().all(|c| c.is_ascii_alphabetic())
+                && host
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        }
+        None => false,
+    }
+}
+
+/// `.html` of the page file, percent-encoded, e.g. `Other%20Page.html`
+pub fn href(file: &str) -> String {
+    const ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+        .remove(b'-')
+        .remove(b'_')
+        .remove(b'.')
+        .remove(b'~')
+        .remove(b'/');
+    let page = file.trim_end_matches(".md");
+    format!("{}.html", utf8_percent_encode(page, ENCODE_SET))
+}
+
+/// `[[Page]]` to no documents, or to no sections of the document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    pub target: String,
+    pub location: String,
+}
+
+impl std::fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Broken link: [[{}]] in {}", self.target, self.location)
+    }
+}
+impl std::error::Error for BrokenLink {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test_wikilink {
+    use crate::wikilink::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(WikiLink::parse("http://example.com/"), None);
+        assert_eq!(WikiLink::parse("example.com"), None);
+        assert_eq!(WikiLink::parse("docs.rs/nom"), None);
+        assert_eq!(WikiLink::parse("notes.md").unwrap().page, "notes.md");
+        assert_eq!(WikiLink::parse("v1.0").unwrap().page, "v1.0");
+        assert_eq!(
+            WikiLink::parse("Other Page#Setup|the setup"),
+            Some(WikiLink {
+                page: String::from("Other Page"),
+                section: Some(String::from("Setup")),
+                label: Some(String::from("the setup")),
+            })
+        );
+        assert_eq!(
+            WikiLink::parse("Other Page").unwrap().candidates(),
+            vec!["Other Page.md", "other-page.md"]
+        );
+        assert_eq!(
+            WikiLink::parse("notes").unwrap().candidates(),
+            vec!["notes.md"]
+        );
+    }
+
+    #[test]
+    fn test_href() {
+        assert_eq!(href("Other Page.md"), "Other%20Page.html");
+        assert_eq!(href("dir/Q&A #1.md"), "dir/Q%26A%20%231.html");
+        assert_eq!(href("日記.md"), "%E6%97%A5%E8%A8%98.html");
+    }
+}
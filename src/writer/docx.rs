@@ -0,0 +1,1227 @@
+use crate::entity::markdown::spaced;
+use crate::entity::markdown::{Align, Block, Inline, List, ListOrderType, Markdown, Text};
+use crate::glossary;
+use crate::wikilink::WikiLink;
+use crate::writer::package::{identify, images, timestamp, walk};
+use crate::writer::plain;
+use crate::writer::xhtml::kind;
+use html_escape::{encode_double_quoted_attribute as attr, encode_text as escape};
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Metadata of a DOCX; mostly from the front matter
+#[derive(Debug, Clone, Default)]
+pub struct Docx {
+    pub title: String,
+    pub author: Option<String>,
+    pub language: String,
+    /// `dcterms:modified`, e.g. `2024-01-01T00:00:00Z`
+    pub modified: String,
+    /// Reference `.docx` of which the styles are used
+    pub reference: Option<String>,
+    /// Glossary terms (`--glossary`) in addition to the definitions in the document
+    pub glossary: Vec<(String, String)>,
+}
+
+impl Docx {
+    /// `title`, `author` and `lang` of the variables
+    pub fn new(variables: &HashMap<String, String>, mkd: &Markdown) -> Self {
+        let title = match variables.get("title") {
+            Some(title) => title.to_string(),
+            None => mkd
+                .iter()
+                .find_map(|block| match block {
+                    Block::Heading(_, text, _) => Some(plain(text)),
+                    _ => None,
+                })
+                .unwrap_or_default(),
+        };
+        Self {
+            title,
+            author: variables.get("author").cloned(),
+            language: variables
+                .get("lang")
+                .cloned()
+                .unwrap_or_else(|| String::from("en")),
+            modified: timestamp(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+            ),
+            reference: None,
+            glossary: vec![],
+        }
+    }
+}
+
+/// Missing images or reference document, or the zip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocxError(String);
+
+impl std::fmt::Display for DocxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl std::error::Error for DocxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+const NS_W: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const NS_R: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const REL: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+/// Width of the text (6 inches) in EMU
+const TEXT_WIDTH: u64 = 5486400;
+/// Width of the text in twips
+const TEXT_TWIPS: usize = 9360;
+
+/// Styles used by the document; also added to the styles of the reference document when missing
+const STYLES: [(&str, &str); 18] = [
+    (
+        "Normal",
+        r#"<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:qFormat/></w:style>"#,
+    ),
+    (
+        "Title",
+        r#"<w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="240" w:after="240"/><w:jc w:val="center"/></w:pPr><w:rPr><w:b/><w:sz w:val="48"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading1",
+        r#"<w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="360" w:after="120"/><w:outlineLvl w:val="0"/></w:pPr><w:rPr><w:b/><w:color w:val="1F3864"/><w:sz w:val="36"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading2",
+        r#"<w:style w:type="paragraph" w:styleId="Heading2"><w:name w:val="heading 2"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="240" w:after="120"/><w:outlineLvl w:val="1"/></w:pPr><w:rPr><w:b/><w:color w:val="1F3864"/><w:sz w:val="30"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading3",
+        r#"<w:style w:type="paragraph" w:styleId="Heading3"><w:name w:val="heading 3"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="240" w:after="80"/><w:outlineLvl w:val="2"/></w:pPr><w:rPr><w:b/><w:color w:val="1F3864"/><w:sz w:val="26"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading4",
+        r#"<w:style w:type="paragraph" w:styleId="Heading4"><w:name w:val="heading 4"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="200" w:after="80"/><w:outlineLvl w:val="3"/></w:pPr><w:rPr><w:b/><w:i/><w:sz w:val="24"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading5",
+        r#"<w:style w:type="paragraph" w:styleId="Heading5"><w:name w:val="heading 5"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="200" w:after="80"/><w:outlineLvl w:val="4"/></w:pPr><w:rPr><w:b/></w:rPr></w:style>"#,
+    ),
+    (
+        "Quote",
+        r#"<w:style w:type="paragraph" w:styleId="Quote"><w:name w:val="Quote"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:ind w:left="720" w:right="720"/></w:pPr><w:rPr><w:i/><w:color w:val="555555"/></w:rPr></w:style>"#,
+    ),
+    (
+        "SourceCode",
+        r#"<w:style w:type="paragraph" w:styleId="SourceCode"><w:name w:val="Source Code"/><w:basedOn w:val="Normal"/><w:pPr><w:shd w:val="clear" w:color="auto" w:fill="F4F4F4"/><w:spacing w:line="240" w:lineRule="auto"/></w:pPr><w:rPr><w:rFonts w:ascii="Consolas" w:hAnsi="Consolas" w:cs="Consolas"/><w:sz w:val="20"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Caption",
+        r#"<w:style w:type="paragraph" w:styleId="Caption"><w:name w:val="caption"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:after="240"/><w:jc w:val="center"/></w:pPr><w:rPr><w:i/><w:sz w:val="20"/></w:rPr></w:style>"#,
+    ),
+    (
+        "ListParagraph",
+        r#"<w:style w:type="paragraph" w:styleId="ListParagraph"><w:name w:val="List Paragraph"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:after="60"/><w:contextualSpacing/></w:pPr></w:style>"#,
+    ),
+    (
+        "FootnoteText",
+        r#"<w:style w:type="paragraph" w:styleId="FootnoteText"><w:name w:val="footnote text"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:after="0"/></w:pPr><w:rPr><w:sz w:val="20"/></w:rPr></w:style>"#,
+    ),
+    (
+        "DefaultParagraphFont",
+        r#"<w:style w:type="character" w:default="1" w:styleId="DefaultParagraphFont"><w:name w:val="Default Paragraph Font"/><w:semiHidden/></w:style>"#,
+    ),
+    (
+        "VerbatimChar",
+        r#"<w:style w:type="character" w:styleId="VerbatimChar"><w:name w:val="Verbatim Char"/><w:basedOn w:val="DefaultParagraphFont"/><w:rPr><w:rFonts w:ascii="Consolas" w:hAnsi="Consolas" w:cs="Consolas"/><w:sz w:val="20"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Hyperlink",
+        r#"<w:style w:type="character" w:styleId="Hyperlink"><w:name w:val="Hyperlink"/><w:basedOn w:val="DefaultParagraphFont"/><w:rPr><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr></w:style>"#,
+    ),
+    (
+        "FootnoteReference",
+        r#"<w:style w:type="character" w:styleId="FootnoteReference"><w:name w:val="footnote reference"/><w:basedOn w:val="DefaultParagraphFont"/><w:rPr><w:vertAlign w:val="superscript"/></w:rPr></w:style>"#,
+    ),
+    (
+        "TableNormal",
+        r#"<w:style w:type="table" w:default="1" w:styleId="TableNormal"><w:name w:val="Normal Table"/><w:semiHidden/><w:tblPr><w:tblInd w:w="0" w:type="dxa"/><w:tblCellMar><w:top w:w="0" w:type="dxa"/><w:left w:w="108" w:type="dxa"/><w:bottom w:w="0" w:type="dxa"/><w:right w:w="108" w:type="dxa"/></w:tblCellMar></w:tblPr></w:style>"#,
+    ),
+    (
+        "Table",
+        r#"<w:style w:type="table" w:styleId="Table"><w:name w:val="Table"/><w:basedOn w:val="TableNormal"/><w:pPr><w:spacing w:after="0"/></w:pPr><w:tblPr><w:tblBorders><w:top w:val="single" w:sz="4" w:space="0" w:color="888888"/><w:left w:val="single" w:sz="4" w:space="0" w:color="888888"/><w:bottom w:val="single" w:sz="4" w:space="0" w:color="888888"/><w:right w:val="single" w:sz="4" w:space="0" w:color="888888"/><w:insideH w:val="single" w:sz="4" w:space="0" w:color="888888"/><w:insideV w:val="single" w:sz="4" w:space="0" w:color="888888"/></w:tblBorders></w:tblPr></w:style>"#,
+    ),
+];
+
+/// Formatting of the runs
+#[derive(Debug, Clone, Copy, Default)]
+struct Format {
+    bold: bool,
+    italic: bool,
+    strike: bool,
+    code: bool,
+    link: bool,
+}
+
+/// Local image in `word/media`
+struct Image {
+    rid: String,
+    /// (cx, cy) in EMU
+    size: (u64, u64),
+}
+
+/// `word/document.xml` and the parts referred from it
+#[derive(Default)]
+struct Body {
+    /// label -> text of the references, e.g. `Figure 3`
+    labels: HashMap<String, String>,
+    /// term -> definition, noted at the first reference
+    definitions: HashMap<String, String>,
+    noted: HashSet<String>,
+    images: HashMap<String, Image>,
+    /// (id, type, target, external) of `word/_rels/document.xml.rels`
+    rels: Vec<(String, &'static str, String, bool)>,
+    /// Abstract numbering (0: bullets, 1: numbers, 2: letters) and level of the lists
+    lists: Vec<(usize, usize)>,
+    footnotes: Vec<String>,
+    /// ids of the bookmarks and drawings
+    bookmarks: usize,
+    drawings: usize,
+    has_toc: bool,
+}
+
+/// DOCX (Office Open XML) of the document
+pub fn build(docx: &Docx, mkd: &Markdown) -> Result<Vec<u8>, DocxError> {
+    let mkd = identify(mkd);
+    let mut body = Body::default();
+    for rel in ["styles", "numbering", "settings", "footnotes"] {
+        body.relate(rel, &format!("{}.xml", rel), false);
+    }
+    let mut counters = HashMap::new();
+    walk(&mkd, &mut |block| match block {
+        Block::Heading(_, text, attributes) => {
+            if let Some(id) = &attributes.id {
+                body.labels.insert(id.to_string(), plain(text));
+            }
+        }
+        Block::Labeled(inner, _, attributes) => {
+            if let Some(id) = &attributes.id {
+                let kind = kind(inner);
+                let counter = counters.entry(kind).or_insert(0);
+                *counter += 1;
+                body.labels
+                    .insert(id.to_string(), format!("{} {}", kind.name(), counter));
+            }
+        }
+        _ => {}
+    });
+    body.definitions = docx
+        .glossary
+        .iter()
+        .cloned()
+        .chain(glossary::definitions(&mkd))
+        .collect();
+
+    let mut media = vec![];
+    for url in images(&mkd) {
+        if body.images.contains_key(&url) {
+            continue;
+        }
+        let ext = image_type(&url)?;
+        let data = std::fs::read(&url)
+            .map_err(|err| DocxError(format!("Cannot read {}: {}", url, err)))?;
+        let name = format!("media/image{}.{}", media.len() + 1, ext);
+        let rid = body.relate("image", &name, false);
+        let size = extent(image_size(&data).unwrap_or((400, 300)));
+        body.images.insert(url, Image { rid, size });
+        media.push((name, data));
+    }
+
+    let document = format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n",
+            "<w:document xmlns:w=\"{}\" xmlns:r=\"{}\"",
+            " xmlns:wp=\"http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing\"",
+            " xmlns:a=\"http://schemas.openxmlformats.org/drawingml/2006/main\"",
+            " xmlns:pic=\"http://schemas.openxmlformats.org/drawingml/2006/picture\">\n",
+            "<w:body>\n{}\n",
+            "<w:sectPr><w:pgSz w:w=\"12240\" w:h=\"15840\"/>",
+            "<w:pgMar w:top=\"1440\" w:right=\"1440\" w:bottom=\"1440\" w:left=\"1440\"",
+            " w:header=\"720\" w:footer=\"720\" w:gutter=\"0\"/></w:sectPr>\n",
+            "</w:body>\n</w:document>\n"
+        ),
+        NS_W,
+        NS_R,
+        body.blocks(&mkd).join("\n")
+    );
+    let styles = match &docx.reference {
+        Some(path) => reference_styles(path)?,
+        None => styles(),
+    };
+
+    let mut parts = vec![
+        (String::from("[Content_Types].xml"), content_types(&media)),
+        (String::from("_rels/.rels"), package_rels()),
+        (String::from("docProps/core.xml"), core(docx)),
+        (
+            String::from("docProps/app.xml"),
+            xml(r#"<Properties xmlns="http://schemas.openxmlformats.org/officeDocument/2006/extended-properties"><Application>unidoc</Application></Properties>"#),
+        ),
+        (String::from("word/document.xml"), document),
+        (String::from("word/_rels/document.xml.rels"), body.rels()),
+        (String::from("word/styles.xml"), styles),
+        (String::from("word/numbering.xml"), body.numbering()),
+        (String::from("word/settings.xml"), body.settings()),
+        (String::from("word/footnotes.xml"), body.footnotes()),
+    ]
+    .into_iter()
+    .map(|(name, content)| (name, content.into_bytes()))
+    .collect::<Vec<_>>();
+    for (name, data) in media {
+        parts.push((format!("word/{}", name), data));
+    }
+    package(&parts).map_err(|err| DocxError(format!("Cannot write the DOCX: {}", err)))
+}
+
+impl Body {
+    /// Relationship id of a new part or an external link
+    fn relate(&mut self, kind: &'static str, target: &str, external: bool) -> String {
+        let id = format!("rId{}", self.rels.len() + 1);
+        self.rels
+            .push((id.to_string(), kind, target.to_string(), external));
+        id
+    }
+
+    fn blocks(&mut self, mkd: &Markdown) -> Vec<String> {
+        mkd.iter().flat_map(|block| self.block(block)).collect()
+    }
+
+    fn block(&mut self, block: &Block) -> Vec<String> {
+        match block {
+            // `#` is the title, and `##`, `###`, ... are the headings
+            Block::Heading(level, text, attributes) => {
+                let style = match level {
+                    1 => String::from("Title"),
+                    _ => format!("Heading{}", (level - 1).min(5)),
+                };
+                let runs = self.runs(text, Format::default());
+                let runs = match &attributes.id {
+                    Some(id) => self.bookmark(id, &runs),
+                    None => runs,
+                };
+                vec![paragraph(&style_pr(&style), &runs)]
+            }
+            Block::ListBlock(list) => self.list(list, 0),
+            Block::Paragraph(text) => vec![paragraph("", &self.runs(text, Format::default()))],
+            Block::Quoted(text) => {
+                vec![paragraph(
+                    &style_pr("Quote"),
+                    &self.runs(text, Format::default()),
+                )]
+            }
+            Block::Code(_, code) => vec![code_block(code)],
+            Block::CodeImport(_, path) | Block::Import(path) => vec![code_block(path)],
+            Block::HorizontalRule => vec![paragraph(
+                "<w:pBdr><w:bottom w:val=\"single\" w:sz=\"6\" w:space=\"1\" w:color=\"auto\"/></w:pBdr>",
+                "",
+            )],
+            Block::Table(aligns, content, has_header) => {
+                vec![self.table(aligns, content, *has_header)]
+            }
+            Block::HyperLink(url) => {
+                let text = vec![Inline::Link(
+                    vec![Inline::Plaintext(url.to_string())],
+                    url.to_string(),
+                )];
+                vec![paragraph("", &self.runs(&text, Format::default()))]
+            }
+            // TeX as it is
+            Block::MathJax(tex) => vec![paragraph(
+                "<w:jc w:val=\"center\"/>",
+                &run(tex.trim(), Format::default()),
+            )],
+            Block::Div(_, children) => self.blocks(children),
+            Block::Toc => {
+                self.has_toc = true;
+                vec![toc()]
+            }
+            Block::Labeled(inner, caption, attributes) => {
+                let label = attributes.id.as_ref().and_then(|id| self.labels.get(id));
+                let mut runs = match label {
+                    Some(label) if caption.is_empty() => run(label, Format::default()),
+                    Some(label) => run(&format!("{}: ", label), Format::default()),
+                    None => String::new(),
+                };
+                runs += &self.runs(caption, Format::default());
+                if let Some(id) = &attributes.id {
+                    runs = self.bookmark(id, &runs);
+                }
+                let caption = paragraph(&style_pr("Caption"), &runs);
+                let mut blocks = self.block(inner);
+                // captions are above tables, and below the others
+                if matches!(inner.as_ref(), Block::Table(..)) {
+                    blocks.insert(0, caption);
+                } else {
+                    blocks.push(caption);
+                }
+                blocks
+            }
+            Block::Abbreviation(..) | Block::Index => vec![],
+        }
+    }
+
+    /// Paragraphs with the numbering of the list; nested lists are in the next levels
+    fn list(&mut self, list: &List, level: usize) -> Vec<String> {
+        let abstract_num = match list.order_type {
+            ListOrderType::Unordered => 0,
+            ListOrderType::OrderedNumbers => 1,
+            ListOrderType::OrderedAlphabets => 2,
+        };
+        let level = level.min(8);
+        self.lists.push((abstract_num, level));
+        let num = self.lists.len();
+        let mut paragraphs = vec![];
+        for item in list.items.iter() {
+            let checkbox = match item.checked {
+                Some(true) => "\u{2612} ",
+                Some(false) => "\u{2610} ",
+                None => "",
+            };
+            let runs = format!(
+                "{}{}",
+                if checkbox.is_empty() {
+                    String::new()
+                } else {
+                    run(checkbox, Format::default())
+                },
+                self.runs(&item.label, Format::default())
+            );
+            let pr = format!(
+                "<w:pStyle w:val=\"ListParagraph\"/><w:numPr><w:ilvl w:val=\"{}\"/><w:numId w:val=\"{}\"/></w:numPr>",
+                level, num
+            );
+            paragraphs.push(paragraph(&pr, &runs));
+            if let Some(children) = &item.children {
+                paragraphs.extend(self.list(children, level + 1));
+            }
+        }
+        paragraphs
+    }
+
+    /// Table with the borders; the header row is bold and repeated on each page
+    fn table(&mut self, aligns: &[Align], content: &[Vec<Text>], has_header: bool) -> String {
+        let columns = content
+            .iter()
+            .map(|row| row.len())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let width = TEXT_TWIPS / columns;
+        let mut xml = vec![
+            String::from("<w:tbl>"),
+            String::from("<w:tblPr><w:tblStyle w:val=\"Table\"/><w:tblW w:w=\"0\" w:type=\"auto\"/><w:tblLook w:val=\"0020\" w:firstRow=\"1\" w:lastRow=\"0\" w:firstColumn=\"0\" w:lastColumn=\"0\" w:noHBand=\"1\" w:noVBand=\"1\"/></w:tblPr>"),
+            format!(
+                "<w:tblGrid>{}</w:tblGrid>",
+                format!("<w:gridCol w:w=\"{}\"/>", width).repeat(columns)
+            ),
+        ];
+        for (i, row) in content.iter().enumerate() {
+            let header = i == 0 && has_header;
+            let mut cells = String::new();
+            for j in 0..columns {
+                let jc = match aligns.get(j) {
+                    Some(Align::Center) => "center",
+                    Some(Align::Right) => "right",
+                    _ => "left",
+                };
+                let format = Format {
+                    bold: header,
+                    ..Format::default()
+                };
+                let runs = match row.get(j) {
+                    Some(cell) => self.runs(cell, format),
+                    None => String::new(),
+                };
+                cells += &format!(
+                    "<w:tc><w:tcPr><w:tcW w:w=\"{}\" w:type=\"dxa\"/></w:tcPr>{}</w:tc>",
+                    width,
+                    paragraph(&format!("<w:jc w:val=\"{}\"/>", jc), &runs)
+                );
+            }
+            let pr = if header {
+                "<w:trPr><w:tblHeader/></w:trPr>"
+            } else {
+                ""
+            };
+            xml.push(format!("<w:tr>{}{}</w:tr>", pr, cells));
+        }
+        xml.push(String::from("</w:tbl>"));
+        xml.join("\n")
+    }
+
+    /// Runs of the text; spaces between the inlines as the other writers
+    fn runs(&mut self, text: &Text, format: Format) -> String {
+        let mut out = String::new();
+        let mut last: Option<char> = None;
+        for (i, inline) in text.iter().enumerate() {
+            let flat = plain(&vec![inline.clone()]);
+            let space = match (last, flat.chars().next()) {
+                (Some(_), Some(_)) => spaced(text.get(i.wrapping_sub(1)), inline),
+                _ => false,
+            };
+            match inline {
+                // the space in the same run
+                Inline::Plaintext(s) if space => out += &run(&format!(" {}", s), format),
+                _ if space => out += &(run(" ", format) + &self.inline(inline, format)),
+                _ => out += &self.inline(inline, format),
+            }
+            match inline {
+                Inline::Newline => last = None,
+                _ if !flat.is_empty() => last = flat.chars().last(),
+                _ => {}
+            }
+        }
+        out
+    }
+
+    fn inline(&mut self, inline: &Inline, format: Format) -> String {
+        match inline {
+            Inline::Link(text, url) if !format.link => self.link(text, url, format),
+            Inline::Link(text, _) => self.runs(text, format),
+            Inline::HyperLink(target) => match WikiLink::parse(target) {
+                Some(link) => run(&link.label.unwrap_or(link.page), format),
+                None => {
+                    let text = vec![Inline::Plaintext(target.to_string())];
+                    self.link(&text, target, format)
+                }
+            },
+            Inline::Image(alt, url) => match self.images.get(url) {
+                Some(image) => {
+                    self.drawings += 1;
+                    drawing(self.drawings, alt, &image.rid, image.size)
+                }
+                // images on the web are links
+                None if !format.link => {
+                    let alt = if alt.is_empty() { url } else { alt };
+                    self.link(&vec![Inline::Plaintext(alt.to_string())], url, format)
+                }
+                None => run(alt, format),
+            },
+            Inline::Code(code) => run(
+                code,
+                Format {
+                    code: true,
+                    ..format
+                },
+            ),
+            Inline::Emphasis(text) => self.runs(
+                text,
+                Format {
+                    italic: true,
+                    ..format
+                },
+            ),
+            Inline::Strong(text) => self.runs(
+                text,
+                Format {
+                    bold: true,
+                    ..format
+                },
+            ),
+            Inline::EmphasisAndStrong(text) => self.runs(
+                text,
+                Format {
+                    bold: true,
+                    italic: true,
+                    ..format
+                },
+            ),
+            Inline::Deleted(text) => self.runs(
+                text,
+                Format {
+                    strike: true,
+                    ..format
+                },
+            ),
+            Inline::Index(text, _) => self.runs(text, format),
+            Inline::Newline => String::from("<w:r><w:br/></w:r>"),
+            Inline::Comment(_) => String::new(),
+            Inline::Reference(label) if !format.link && self.labels.contains_key(label) => {
+                let text = vec![Inline::Plaintext(self.labels[label].to_string())];
+                self.link(&text, &format!("#{}", label), format)
+            }
+            // the definitions are footnotes at the first references
+            Inline::Glossary(term) => {
+                let mut runs = run(term, format);
+                if let Some(definition) = self.definitions.get(term) {
+                    if self.noted.insert(term.to_string()) {
+                        runs += &self.footnote(&definition.to_string());
+                    }
+                }
+                runs
+            }
+            Inline::Citation(_, true) => {
+                run(&format!("[{}]", plain(&vec![inline.clone()])), format)
+            }
+            _ => run(&plain(&vec![inline.clone()]), format),
+        }
+    }
+
+    /// `#id` are the bookmarks in the document, and the others are external
+    fn link(&mut self, text: &Text, url: &str, format: Format) -> String {
+        let format = Format {
+            link: true,
+            ..format
+        };
+        let runs = self.runs(text, format);
+        match url.strip_prefix('#') {
+            Some(id) => format!(
+                "<w:hyperlink w:anchor=\"{}\">{}</w:hyperlink>",
+                bookmark_name(id),
+                runs
+            ),
+            None => {
+                let rid = self.relate("hyperlink", url, true);
+                format!("<w:hyperlink r:id=\"{}\">{}</w:hyperlink>", rid, runs)
+            }
+        }
+    }
+
+    fn bookmark(&mut self, id: &str, runs: &str) -> String {
+        self.bookmarks += 1;
+        format!(
+            "<w:bookmarkStart w:id=\"{}\" w:name=\"{}\"/>{}<w:bookmarkEnd w:id=\"{}\"/>",
+            self.bookmarks,
+            bookmark_name(id),
+            runs,
+            self.bookmarks
+        )
+    }
+
+    /// Reference to a new footnote
+    fn footnote(&mut self, text: &str) -> String {
+        let id = self.footnotes.len() + 1;
+        self.footnotes.push(format!(
+            concat!(
+                "<w:footnote w:id=\"{}\"><w:p><w:pPr><w:pStyle w:val=\"FootnoteText\"/></w:pPr>",
+                "<w:r><w:rPr><w:rStyle w:val=\"FootnoteReference\"/></w:rPr><w:footnoteRef/></w:r>",
+                "{}</w:p></w:footnote>"
+            ),
+            id,
+            run(&format!(" {}", text), Format::default())
+        ));
+        format!(
+            "<w:r><w:rPr><w:rStyle w:val=\"FootnoteReference\"/></w:rPr><w:footnoteReference w:id=\"{}\"/></w:r>",
+            id
+        )
+    }
+
+    fn rels(&self) -> String {
+        let rels: Vec<String> = self
+            .rels
+            .iter()
+            .map(|(id, kind, target, external)| {
+                format!(
+                    "<Relationship Id=\"{}\" Type=\"{}/{}\" Target=\"{}\"{}/>",
+                    id,
+                    REL,
+                    kind,
+                    attr(target),
+                    if *external {
+                        " TargetMode=\"External\""
+                    } else {
+                        ""
+                    }
+                )
+            })
+            .collect();
+        xml(&format!(
+            "<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\n{}\n</Relationships>",
+            rels.join("\n")
+        ))
+    }
+
+    /// Bullets, numbers and letters in nine levels; every list restarts its numbering
+    fn numbering(&self) -> String {
+        let mut out = vec![];
+        for (abstract_num, format) in ["bullet", "decimal", "lowerLetter"].iter().enumerate() {
+            let levels: Vec<String> = (0..9)
+                .map(|level| {
+                    let text = match *format {
+                        "bullet" => ["\u{2022}", "\u{25e6}", "\u{25aa}"][level % 3].to_string(),
+                        _ => format!("%{}.", level + 1),
+                    };
+                    format!(
+                        concat!(
+                            "<w:lvl w:ilvl=\"{}\"><w:start w:val=\"1\"/><w:numFmt w:val=\"{}\"/>",
+                            "<w:lvlText w:val=\"{}\"/><w:lvlJc w:val=\"left\"/>",
+                            "<w:pPr><w:ind w:left=\"{}\" w:hanging=\"360\"/></w:pPr></w:lvl>"
+                        ),
+                        level,
+                        format,
+                        text,
+                        720 * (level + 1)
+                    )
+                })
+                .collect();
+            out.push(format!(
+                "<w:abstractNum w:abstractNumId=\"{}\"><w:multiLevelType w:val=\"multilevel\"/>{}</w:abstractNum>",
+                abstract_num,
+                levels.join("")
+            ));
+        }
+        for (i, (abstract_num, level)) in self.lists.iter().enumerate() {
+            out.push(format!(
+                concat!(
+                    "<w:num w:numId=\"{}\"><w:abstractNumId w:val=\"{}\"/>",
+                    "<w:lvlOverride w:ilvl=\"{}\"><w:startOverride w:val=\"1\"/></w:lvlOverride></w:num>"
+                ),
+                i + 1,
+                abstract_num,
+                level
+            ));
+        }
+        xml(&format!(
+            "<w:numbering xmlns:w=\"{}\">\n{}\n</w:numbering>",
+            NS_W,
+            out.join("\n")
+        ))
+    }
+
+    /// Fields are updated on open for the table of contents
+    fn settings(&self) -> String {
+        let update = if self.has_toc {
+            "<w:updateFields w:val=\"true\"/>"
+        } else {
+            ""
+        };
+        xml(&format!(
+            "<w:settings xmlns:w=\"{}\">{}<w:footnotePr><w:footnote w:id=\"-1\"/><w:footnote w:id=\"0\"/></w:footnotePr></w:settings>",
+            NS_W, update
+        ))
+    }
+
+    fn footnotes(&self) -> String {
+        xml(&format!(
+            concat!(
+                "<w:footnotes xmlns:w=\"{}\" xmlns:r=\"{}\">\n",
+                "<w:footnote w:type=\"separator\" w:id=\"-1\"><w:p><w:r><w:separator/></w:r></w:p></w:footnote>\n",
+                "<w:footnote w:type=\"continuationSeparator\" w:id=\"0\"><w:p><w:r><w:continuationSeparator/></w:r></w:p></w:footnote>\n",
+                "{}</w:footnotes>"
+            ),
+            NS_W,
+            NS_R,
+            self.footnotes
+                .iter()
+                .map(|footnote| format!("{}\n", footnote))
+                .collect::<String>()
+        ))
+    }
+}
+
+fn xml(content: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n{}\n",
+        content
+    )
+}
+
+fn paragraph(pr: &str, runs: &str) -> String {
+    if pr.is_empty() {
+        format!("<w:p>{}</w:p>", runs)
+    } else {
+        format!("<w:p><w:pPr>{}</w:pPr>{}</w:p>", pr, runs)
+    }
+}
+
+fn style_pr(style: &str) -> String {
+    format!("<w:pStyle w:val=\"{}\"/>", style)
+}
+
+fn run(text: &str, format: Format) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let mut pr = String::new();
+    if format.code {
+        pr += "<w:rStyle w:val=\"VerbatimChar\"/>";
+    } else if format.link {
+        pr += "<w:rStyle w:val=\"Hyperlink\"/>";
+    }
+    if format.bold {
+        pr += "<w:b/>";
+    }
+    if format.italic {
+        pr += "<w:i/>";
+    }
+    if format.strike {
+        pr += "<w:strike/>";
+    }
+    let pr = if pr.is_empty() {
+        pr
+    } else {
+        format!("<w:rPr>{}</w:rPr>", pr)
+    };
+    format!("<w:r>{}{}</w:r>", pr, texts(text))
+}
+
+/// `<w:t>` with the tabs and line breaks
+fn texts(text: &str) -> String {
+    let mut out = vec![];
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push(String::from("<w:br/>"));
+        }
+        for (j, piece) in line.split('\t').enumerate() {
+            if j > 0 {
+                out.push(String::from("<w:tab/>"));
+            }
+            if !piece.is_empty() {
+                // control characters are not allowed in XML
+                let piece: String = piece.chars().filter(|c| !c.is_control()).collect();
+                out.push(format!(
+                    "<w:t xml:space=\"preserve\">{}</w:t>",
+                    escape(&piece)
+                ));
+            }
+        }
+    }
+    out.concat()
+}
+
+fn code_block(code: &str) -> String {
+    paragraph(
+        &style_pr("SourceCode"),
+        &run(code.trim_end_matches('\n'), Format::default()),
+    )
+}
+
+/// TOC field of the headings; Word fills it in
+fn toc() -> String {
+    concat!(
+        "<w:p><w:r><w:fldChar w:fldCharType=\"begin\" w:dirty=\"true\"/></w:r>",
+        "<w:r><w:instrText xml:space=\"preserve\"> TOC \\o \"1-3\" \\h \\z \\u </w:instrText></w:r>",
+        "<w:r><w:fldChar w:fldCharType=\"separate\"/></w:r>",
+        "<w:r><w:t>Table of Contents</w:t></w:r>",
+        "<w:r><w:fldChar w:fldCharType=\"end\"/></w:r></w:p>"
+    )
+    .to_string()
+}
+
+/// Inline picture
+fn drawing(id: usize, alt: &str, rid: &str, (cx, cy): (u64, u64)) -> String {
+    format!(
+        concat!(
+            "<w:r><w:drawing><wp:inline distT=\"0\" distB=\"0\" distL=\"0\" distR=\"0\">",
+            "<wp:extent cx=\"{cx}\" cy=\"{cy}\"/>",
+            "<wp:docPr id=\"{id}\" name=\"Picture {id}\" descr=\"{alt}\"/>",
+            "<wp:cNvGraphicFramePr><a:graphicFrameLocks noChangeAspect=\"1\"/></wp:cNvGraphicFramePr>",
+            "<a:graphic><a:graphicData uri=\"http://schemas.openxmlformats.org/drawingml/2006/picture\">",
+            "<pic:pic><pic:nvPicPr><pic:cNvPr id=\"0\" name=\"Picture {id}\"/><pic:cNvPicPr/></pic:nvPicPr>",
+            "<pic:blipFill><a:blip r:embed=\"{rid}\"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill>",
+            "<pic:spPr><a:xfrm><a:off x=\"0\" y=\"0\"/><a:ext cx=\"{cx}\" cy=\"{cy}\"/></a:xfrm>",
+            "<a:prstGeom prst=\"rect\"><a:avLst/></a:prstGeom></pic:spPr></pic:pic>",
+            "</a:graphicData></a:graphic></wp:inline></w:drawing></w:r>"
+        ),
+        cx = cx,
+        cy = cy,
+        id = id,
+        alt = attr(alt),
+        rid = rid
+    )
+}
+
+/// Letters, digits and `_` (and at most 40 characters) as Word requires
+fn bookmark_name(id: &str) -> String {
+    let name: String = id
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let name = if name.starts_with(|c: char| c.is_alphabetic()) {
+        name
+    } else {
+        format!("x{}", name)
+    };
+    name.chars().take(40).collect()
+}
+
+/// Extension of the images Word shows
+fn image_type(path: &str) -> Result<&'static str, DocxError> {
+    let ext = Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => Ok("png"),
+        "jpg" | "jpeg" => Ok("jpeg"),
+        "gif" => Ok("gif"),
+        "bmp" => Ok("bmp"),
+        _ => Err(DocxError(format!("Unsupported image type: {}", path))),
+    }
+}
+
+/// (width, height) in pixels from the headers of PNG, GIF, BMP or JPEG
+fn image_size(data: &[u8]) -> Option<(u64, u64)> {
+    let be = |i: usize| Some(u16::from_be_bytes([*data.get(i)?, *data.get(i + 1)?]) as u64);
+    let le = |i: usize| Some(u16::from_le_bytes([*data.get(i)?, *data.get(i + 1)?]) as u64);
+    let be32 = |i: usize| Some(u32::from_be_bytes(data.get(i..i + 4)?.try_into().ok()?) as u64);
+    let le32 = |i: usize| Some(i32::from_le_bytes(data.get(i..i + 4)?.try_into().ok()?));
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some((be32(16)?, be32(20)?))
+    } else if data.starts_with(b"GIF8") {
+        Some((le(6)?, le(8)?))
+    } else if data.starts_with(b"BM") {
+        Some((
+            le32(18)?.unsigned_abs() as u64,
+            le32(22)?.unsigned_abs() as u64,
+        ))
+    } else if data.starts_with(b"\xff\xd8") {
+        // the first start-of-frame segment
+        let mut i = 2;
+        while *data.get(i)? == 0xff {
+            let marker = *data.get(i + 1)?;
+            if (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+                return Some((be(i + 7)?, be(i + 5)?));
+            }
+            i += 2 + be(i + 2)? as usize;
+        }
+        None
+    } else {
+        None
+    }
+}
+
+/// Size in EMU at 96 dpi, scaled down to the text width
+fn extent((width, height): (u64, u64)) -> (u64, u64) {
+    let (cx, cy) = (width.max(1) * 9525, height.max(1) * 9525);
+    if cx > TEXT_WIDTH {
+        (TEXT_WIDTH, cy * TEXT_WIDTH / cx)
+    } else {
+        (cx, cy)
+    }
+}
+
+fn styles() -> String {
+    let styles: Vec<&str> = STYLES.iter().map(|(_, style)| *style).collect();
+    xml(&format!(
+        concat!(
+            "<w:styles xmlns:w=\"{}\">\n",
+            "<w:docDefaults><w:rPrDefault><w:rPr><w:rFonts w:ascii=\"Calibri\" w:hAnsi=\"Calibri\" w:cs=\"Calibri\"/>",
+            "<w:sz w:val=\"22\"/><w:szCs w:val=\"22\"/></w:rPr></w:rPrDefault>",
+            "<w:pPrDefault><w:pPr><w:spacing w:after=\"160\" w:line=\"264\" w:lineRule=\"auto\"/></w:pPr></w:pPrDefault></w:docDefaults>\n",
+            "{}\n</w:styles>"
+        ),
+        NS_W,
+        styles.join("\n")
+    ))
+}
+
+/// Styles of the reference document, with the styles used here if missing
+fn reference_styles(path: &str) -> Result<String, DocxError> {
+    let error = |err: &dyn std::fmt::Display| DocxError(format!("Cannot read {}: {}", path, err));
+    let file = std::fs::File::open(path).map_err(|err| error(&err))?;
+    let mut archive = ZipArchive::new(file).map_err(|err| error(&err))?;
+    let mut styles = String::new();
+    archive
+        .by_name("word/styles.xml")
+        .map_err(|err| error(&err))?
+        .read_to_string(&mut styles)
+        .map_err(|err| error(&err))?;
+    let end = styles
+        .rfind("</w:styles>")
+        .ok_or_else(|| error(&"no </w:styles> in word/styles.xml"))?;
+    let missing: String = STYLES
+        .iter()
+        .filter(|(id, _)| !styles.contains(&format!("w:styleId=\"{}\"", id)))
+        .map(|(_, style)| format!("{}\n", style))
+        .collect();
+    styles.insert_str(end, &missing);
+    Ok(styles)
+}
+
+fn content_types(media: &[(String, Vec<u8>)]) -> String {
+    const OOXML: &str = "application/vnd.openxmlformats-officedocument";
+    let mut extensions: Vec<&str> = media
+        .iter()
+        .filter_map(|(name, _)| name.rsplit('.').next())
+        .collect();
+    extensions.sort_unstable();
+    extensions.dedup();
+    let mut types = vec![
+        String::from("<Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>"),
+        String::from("<Default Extension=\"xml\" ContentType=\"application/xml\"/>"),
+    ];
+    for ext in extensions {
+        types.push(format!(
+            "<Default Extension=\"{}\" ContentType=\"image/{}\"/>",
+            ext, ext
+        ));
+    }
+    for (part, content_type) in [
+        ("/word/document.xml", "wordprocessingml.document.main+xml"),
+        ("/word/styles.xml", "wordprocessingml.styles+xml"),
+        ("/word/numbering.xml", "wordprocessingml.numbering+xml"),
+        ("/word/settings.xml", "wordprocessingml.settings+xml"),
+        ("/word/footnotes.xml", "wordprocessingml.footnotes+xml"),
+        ("/docProps/app.xml", "extended-properties+xml"),
+    ] {
+        types.push(format!(
+            "<Override PartName=\"{}\" ContentType=\"{}.{}\"/>",
+            part, OOXML, content_type
+        ));
+    }
+    types.push(String::from("<Override PartName=\"/docProps/core.xml\" ContentType=\"application/vnd.openxmlformats-package.core-properties+xml\"/>"));
+    xml(&format!(
+        "<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\n{}\n</Types>",
+        types.join("\n")
+    ))
+}
+
+fn package_rels() -> String {
+    xml(&format!(
+        concat!(
+            "<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\n",
+            "<Relationship Id=\"rId1\" Type=\"{}/officeDocument\" Target=\"word/document.xml\"/>\n",
+            "<Relationship Id=\"rId2\" Type=\"http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties\" Target=\"docProps/core.xml\"/>\n",
+            "<Relationship Id=\"rId3\" Type=\"{}/extended-properties\" Target=\"docProps/app.xml\"/>\n",
+            "</Relationships>"
+        ),
+        REL, REL
+    ))
+}
+
+fn core(docx: &Docx) -> String {
+    let creator = match &docx.author {
+        Some(author) => format!("<dc:creator>{}</dc:creator>", escape(author)),
+        None => String::new(),
+    };
+    xml(&format!(
+        concat!(
+            "<cp:coreProperties xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\"",
+            " xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:dcterms=\"http://purl.org/dc/terms/\"",
+            " xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">",
+            "<dc:title>{}</dc:title>{}<dc:language>{}</dc:language>",
+            "<dcterms:modified xsi:type=\"dcterms:W3CDTF\">{}</dcterms:modified>",
+            "</cp:coreProperties>"
+        ),
+        escape(&docx.title),
+        creator,
+        escape(&docx.language),
+        docx.modified
+    ))
+}
+
+/// OPC zip package; `[Content_Types].xml` is the first
+fn package(parts: &[(String, Vec<u8>)]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, content) in parts.iter() {
+        zip.start_file(name, deflated)?;
+        zip.write_all(content)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod test_docx {
+    use crate::parser;
+    use crate::writer::docx::*;
+    use lazy_static::lazy_static;
+    use regex::Regex;
+    use std::collections::BTreeSet;
+
+    fn unzip(docx: &[u8]) -> Vec<(String, String)> {
+        let mut archive = ZipArchive::new(Cursor::new(docx)).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let mut file = archive.by_index(i).unwrap();
+                let mut content = vec![];
+                file.read_to_end(&mut content).unwrap();
+                (
+                    file.name().to_string(),
+                    String::from_utf8_lossy(&content).to_string(),
+                )
+            })
+            .collect()
+    }
+
+    /// Tags are balanced (or self-closing)
+    fn well_formed(xml: &str) -> bool {
+        lazy_static! {
+            static ref TAG: Regex = Regex::new(r"<(/?)([A-Za-z][\w:.-]*)[^>]*?(/?)>").unwrap();
+        }
+        let mut stack = vec![];
+        for caps in TAG.captures_iter(xml) {
+            match (&caps[1], &caps[3]) {
+                ("", "") => stack.push(caps[2].to_string()),
+                ("/", _) if stack.pop().as_deref() != Some(&caps[2]) => return false,
+                _ => {}
+            }
+        }
+        stack.is_empty()
+    }
+
+    #[test]
+    fn test_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("arch.png");
+        // 1600x800 PNG header
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(1600u32.to_be_bytes());
+        png.extend(800u32.to_be_bytes());
+        std::fs::write(&image, &png).unwrap();
+        let markdown = format!(
+            concat!(
+                "*[API]: Application Programming Interface\n\n",
+                "# Contract\n\n[TOC]\n\n",
+                "## Terms {{#terms}}\n\nThe {{API}} & `code` with [a link](https://example.com), ",
+                "see [terms](#terms) and @fig:arch. The {{API}} again.\n\n",
+                "1. first\n    - nested\n2. second\n\n",
+                "a. alpha\n\n",
+                "| left | right |\n|:-----|------:|\n| a | b |\n\n",
+                "```rust\nfn main() {{\n\tok\n}}\n```\n\n",
+                "![Architecture]({}){{#fig:arch}}\n"
+            ),
+            image.to_str().unwrap()
+        );
+        let mkd = parser::markdown(&markdown).unwrap();
+        let docx = Docx {
+            title: String::from("Contract"),
+            author: Some(String::from("cympfh")),
+            language: String::from("en"),
+            modified: timestamp(0),
+            reference: None,
+            glossary: vec![],
+        };
+        let files = unzip(&build(&docx, &mkd).unwrap());
+        let get = |name: &str| {
+            files
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, content)| content.to_string())
+                .unwrap()
+        };
+        assert_eq!(files[0].0, "[Content_Types].xml");
+        for (name, content) in files
+            .iter()
+            .filter(|(n, _)| n.ends_with(".xml") || n.ends_with(".rels"))
+        {
+            assert!(content.starts_with("<?xml"), "{}", name);
+            assert!(well_formed(content), "{}", name);
+        }
+
+        // every part has a content type, and every relationship has its part
+        let types = get("[Content_Types].xml");
+        lazy_static! {
+            static ref TARGET: Regex =
+                Regex::new(r#"<Relationship Id="([^"]+)" Type="[^"]+/(\w+)" Target="([^"]+)"( TargetMode="External")?"#).unwrap();
+        }
+        let names: BTreeSet<String> = files.iter().map(|(n, _)| n.to_string()).collect();
+        for (name, _) in files
+            .iter()
+            .filter(|(n, _)| n.ends_with(".xml") && n != "[Content_Types].xml")
+        {
+            assert!(
+                types.contains(&format!("PartName=\"/{}\"", name)),
+                "{}",
+                name
+            );
+        }
+        assert!(types.contains("<Default Extension=\"png\" ContentType=\"image/png\"/>"));
+        let rels = get("word/_rels/document.xml.rels");
+        let mut kinds = BTreeSet::new();
+        for caps in TARGET.captures_iter(&rels) {
+            kinds.insert(caps[2].to_string());
+            if caps.get(4).is_none() {
+                assert!(
+                    names.contains(&format!("word/{}", &caps[3])),
+                    "{}",
+                    &caps[3]
+                );
+            }
+        }
+        assert_eq!(
+            kinds,
+            [
+                "footnotes",
+                "hyperlink",
+                "image",
+                "numbering",
+                "settings",
+                "styles"
+            ]
+            .iter()
+            .map(|kind| kind.to_string())
+            .collect()
+        );
+        assert!(rels.contains("Target=\"https://example.com\" TargetMode=\"External\""));
+
+        let document = get("word/document.xml");
+        for xml in [
+            "<w:pStyle w:val=\"Title\"/></w:pPr><w:bookmarkStart w:id=\"1\" w:name=\"contract\"/>",
+            "<w:pStyle w:val=\"Heading1\"/></w:pPr><w:bookmarkStart w:id=\"2\" w:name=\"terms\"/>",
+            "<w:instrText xml:space=\"preserve\"> TOC ",
+            "<w:t xml:space=\"preserve\"> &amp;</w:t>",
+            "<w:rStyle w:val=\"VerbatimChar\"/></w:rPr><w:t xml:space=\"preserve\">code</w:t>",
+            "<w:hyperlink w:anchor=\"terms\"><w:r><w:rPr><w:rStyle w:val=\"Hyperlink\"/>",
+            "<w:hyperlink w:anchor=\"fig_arch\"><w:r><w:rPr><w:rStyle w:val=\"Hyperlink\"/></w:rPr><w:t xml:space=\"preserve\">Figure 1</w:t>",
+            "<w:numPr><w:ilvl w:val=\"1\"/><w:numId w:val=\"2\"/></w:numPr>",
+            "<w:jc w:val=\"right\"/></w:pPr><w:r><w:t xml:space=\"preserve\">b</w:t>",
+            "<w:trPr><w:tblHeader/></w:trPr>",
+            "<w:t xml:space=\"preserve\">fn main() {</w:t><w:br/><w:tab/><w:t xml:space=\"preserve\">ok</w:t>",
+            "<wp:extent cx=\"5486400\" cy=\"2743200\"/>",
+            "<wp:docPr id=\"1\" name=\"Picture 1\" descr=\"Architecture\"/>",
+            "<w:bookmarkStart w:id=\"3\" w:name=\"fig_arch\"/><w:r><w:t xml:space=\"preserve\">Figure 1: </w:t>",
+        ] {
+            assert!(document.contains(xml), "{}", xml);
+        }
+        // a footnote at the first reference only
+        assert_eq!(
+            document
+                .matches("<w:footnoteReference w:id=\"1\"/>")
+                .count(),
+            1
+        );
+        assert!(!document.contains("w:footnoteReference w:id=\"2\""));
+        assert!(get("word/footnotes.xml").contains("Application Programming Interface"));
+
+        // lists restart their numbering
+        let numbering = get("word/numbering.xml");
+        assert!(numbering.contains("<w:num w:numId=\"1\"><w:abstractNumId w:val=\"1\"/>"));
+        assert!(numbering.contains(
+            "<w:num w:numId=\"2\"><w:abstractNumId w:val=\"0\"/><w:lvlOverride w:ilvl=\"1\">"
+        ));
+        assert!(numbering.contains("<w:num w:numId=\"3\"><w:abstractNumId w:val=\"2\"/>"));
+        assert!(get("word/settings.xml").contains("<w:updateFields w:val=\"true\"/>"));
+        assert!(get("docProps/core.xml").contains("<dc:creator>cympfh</dc:creator>"));
+        assert!(get("word/media/image1.png").starts_with("\u{fffd}PNG"));
+    }
+
+    #[test]
+    fn test_reference() {
+        let dir = tempfile::tempdir().unwrap();
+        let reference = dir.path().join("reference.docx");
+        let styles = r#"<w:styles xmlns:w="w"><w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:rPr><w:sz w:val="80"/></w:rPr></w:style></w:styles>"#;
+        let parts = vec![(String::from("word/styles.xml"), styles.as_bytes().to_vec())];
+        std::fs::write(&reference, package(&parts).unwrap()).unwrap();
+        let styles = reference_styles(reference.to_str().unwrap()).unwrap();
+        assert!(styles.contains("<w:sz w:val=\"80\"/>"));
+        assert_eq!(styles.matches("w:styleId=\"Title\"").count(), 1);
+        assert!(styles.contains("w:styleId=\"Heading1\""));
+        assert!(reference_styles("none.docx").is_err());
+    }
+
+    #[test]
+    fn test_image_size() {
+        assert_eq!(image_size(b"GIF89a\x20\x00\x10\x00"), Some((32, 16)));
+        let jpeg = b"\xff\xd8\xff\xe0\x00\x04\x00\x00\xff\xc0\x00\x11\x08\x00\x30\x00\x40";
+        assert_eq!(image_size(jpeg), Some((64, 48)));
+        assert_eq!(image_size(b"unknown"), None);
+        assert_eq!(extent((96, 48)), (914400, 457200));
+    }
+}
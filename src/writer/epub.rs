@@ -0,0 +1,701 @@
+use crate::bibliography;
+use crate::entity::html::{Html, HtmlDoc};
+use crate::entity::markdown::{try_map_text, Block, Inline, Markdown};
+use crate::glossary;
+use crate::index;
+use crate::leaf;
+use crate::toc;
+use crate::translator::Translator;
+use crate::writer::package::{images, modified, walk};
+use crate::writer::plain;
+use crate::writer::xhtml::polyglot;
+use html_escape::{encode_double_quoted_attribute as attr, encode_text as escape};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{Cursor, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Metadata of an EPUB; mostly from the front matter
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    pub title: String,
+    pub author: Option<String>,
+    pub language: String,
+    /// `dc:identifier`, e.g. `urn:uuid:...`
+    pub identifier: String,
+    /// `dcterms:modified`, e.g. `2024-01-01T00:00:00Z`
+    pub modified: String,
+    /// Path of the cover image
+    pub cover: Option<String>,
+    /// Paths of the stylesheets
+    pub css: Vec<String>,
+    /// Headings of this level (or upper) start chapters
+    pub chapter_level: usize,
+}
+
+impl Book {
+    /// `title`, `author`, `lang`, `identifier`, `date` and `cover-image` of the variables
+    pub fn new(variables: &HashMap<String, String>, mkd: &Markdown) -> Self {
+        let title = match variables.get("title") {
+            Some(title) => title.to_string(),
+            None => mkd
+                .iter()
+                .find_map(|block| match block {
+                    Block::Heading(_, text, _) => Some(plain(text)),
+                    _ => None,
+                })
+                .unwrap_or_else(|| String::from("Untitled")),
+        };
+        let author = variables.get("author").cloned();
+        let identifier = match variables.get("identifier") {
+            Some(identifier) => identifier.to_string(),
+            None => uuid(&format!("{}\n{}", title, author.as_deref().unwrap_or(""))),
+        };
+        Self {
+            title,
+            author,
+            language: variables
+                .get("lang")
+                .cloned()
+                .unwrap_or_else(|| String::from("en")),
+            identifier,
+            modified: modified(
+                std::env::var("SOURCE_DATE_EPOCH").ok(),
+                variables.get("date").map(String::as_str),
+            ),
+            cover: variables.get("cover-image").cloned(),
+            css: vec![],
+            chapter_level: 2,
+        }
+    }
+}
+
+/// Missing images or stylesheets, or the zip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpubError(String);
+
+impl std::fmt::Display for EpubError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl std::error::Error for EpubError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+const STYLESHEET: &str = "body { line-height: 1.6; }
+pre { white-space: pre-wrap; font-size: 0.9em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #888; padding: 0.2em 0.5em; }
+figure { text-align: center; }
+img { max-width: 100%; }
+";
+
+/// File in the container with its manifest entry
+struct Item {
+    id: String,
+    href: String,
+    media_type: String,
+    properties: Option<&'static str>,
+    content: Vec<u8>,
+}
+
+impl Item {
+    fn new(id: &str, href: &str, media_type: &str, content: Vec<u8>) -> Self {
+        Self {
+            id: id.to_string(),
+            href: href.to_string(),
+            media_type: media_type.to_string(),
+            properties: None,
+            content,
+        }
+    }
+}
+
+/// EPUB 3 of the document, split into chapters; the chapters are translated in order by `tr`
+/// as the HTML (sharing the ids, numbers and cross-references), and serialized as XHTML
+pub fn build(book: &Book, mkd: &Markdown, tr: &Translator) -> Result<Vec<u8>, EpubError> {
+    let chapters = split(mkd, book.chapter_level);
+    let files: Vec<String> = (1..=chapters.len())
+        .map(|i| format!("chapter-{}.xhtml", i))
+        .collect();
+
+    let mut items = vec![];
+    let mut stylesheets = vec![String::from("styles/unidoc.css")];
+    items.push(Item::new(
+        "style-0",
+        "styles/unidoc.css",
+        "text/css",
+        STYLESHEET.as_bytes().to_vec(),
+    ));
+    for (i, path) in book.css.iter().enumerate() {
+        let href = format!("styles/{}.css", i + 1);
+        let id = format!("style-{}", i + 1);
+        items.push(Item::new(&id, &href, "text/css", load(path)?));
+        stylesheets.push(href);
+    }
+    // URL of a local image -> path in the container
+    let mut paths: HashMap<String, String> = HashMap::new();
+    for url in images(mkd) {
+        if paths.contains_key(&url) {
+            continue;
+        }
+        let n = paths.len() + 1;
+        let (ext, media_type) = media_type(&url)?;
+        let href = format!("images/{}.{}", n, ext);
+        items.push(Item::new(
+            &format!("image-{}", n),
+            &href,
+            media_type,
+            load(&url)?,
+        ));
+        paths.insert(url, href);
+    }
+    let mut spine = vec![];
+    if let Some(path) = &book.cover {
+        let (ext, media_type) = media_type(path)?;
+        let href = format!("images/cover.{}", ext);
+        let mut image = Item::new("cover-image", &href, media_type, load(path)?);
+        image.properties = Some("cover-image");
+        items.push(image);
+        let body = format!(
+            "<div class=\"cover\"><img src=\"{}\" alt=\"{}\"/></div>",
+            href,
+            attr(&book.title)
+        );
+        let page = page(book, &book.title, &stylesheets, &body);
+        items.push(Item::new(
+            "cover",
+            "cover.xhtml",
+            "application/xhtml+xml",
+            page.into_bytes(),
+        ));
+        spine.push(String::from("cover"));
+    }
+
+    // id -> file of the chapter, for the links across the chapters
+    let mut targets: HashMap<String, String> = HashMap::new();
+    let mut docs = vec![];
+    let mut nav = vec![];
+    tr.define_abbreviations(mkd);
+    for (chapter, file) in chapters.iter().zip(files.iter()) {
+        let start = tr.headings().len();
+        let doc = tr.markdown(&relink(chapter, &paths));
+        let headings = tr.headings().split_off(start);
+        let mut texts = vec![];
+        walk(chapter, &mut |block| match block {
+            Block::Heading(_, text, _) => texts.push(plain(text)),
+            Block::Labeled(_, _, attributes) | Block::Div(attributes, _) => {
+                if let Some(id) = &attributes.id {
+                    targets.insert(id.to_string(), file.to_string());
+                }
+            }
+            _ => {}
+        });
+        let title = texts
+            .first()
+            .cloned()
+            .unwrap_or_else(|| book.title.to_string());
+        let sections: Vec<NavPoint> = headings
+            .iter()
+            .zip(texts.iter())
+            .filter(|(heading, _)| heading.level == book.chapter_level + 1)
+            .map(|(heading, text)| {
+                let text = match &heading.number {
+                    Some(number) => format!("{} {}", number, text),
+                    None => text.to_string(),
+                };
+                (text, format!("{}#{}", file, heading.id))
+            })
+            .collect();
+        for heading in headings.iter() {
+            targets.insert(heading.id.to_string(), file.to_string());
+        }
+        nav.push(((title, file.to_string()), sections));
+        docs.push(doc);
+    }
+
+    // references and the glossary at their places (or the end of the book)
+    for (html, marker) in [
+        (tr.references(), bibliography::marker()),
+        (tr.glossary(), glossary::marker()),
+    ] {
+        if let Some(html) = html {
+            if !docs.iter_mut().any(|doc| doc.replace(&marker, &html)) {
+                if let Some(doc) = docs.last_mut() {
+                    doc.append(&mut HtmlDoc::new(String::new(), vec![html]));
+                }
+            }
+        }
+    }
+    let index = tr.index();
+    for (i, (doc, file)) in docs.iter_mut().zip(files.iter()).enumerate() {
+        doc.replace(&index::marker(), &index);
+        // the navigation document is the table of contents
+        doc.replace(&toc::marker(), &leaf!(""));
+        tr.resolve(doc);
+        let body = relative(&polyglot(&doc.show(false, 2)), file, &targets);
+        let title = &nav[i].0 .0;
+        let page = page(book, title, &stylesheets, &body);
+        let id = format!("chapter-{}", i + 1);
+        let mut item = Item::new(&id, file, "application/xhtml+xml", page.into_bytes());
+        if images_on_web(&chapters[i]) {
+            item.properties = Some("remote-resources");
+        }
+        items.push(item);
+        spine.push(id);
+    }
+    let mut nav_item = Item::new(
+        "nav",
+        "nav.xhtml",
+        "application/xhtml+xml",
+        navigation(book, &stylesheets, &nav).into_bytes(),
+    );
+    nav_item.properties = Some("nav");
+    items.insert(0, nav_item);
+
+    container(&package(book, &items, &spine), &items)
+        .map_err(|err| EpubError(format!("Cannot write the EPUB: {}", err)))
+}
+
+/// Local images to the paths in the container
+fn relink(mkd: &Markdown, paths: &HashMap<String, String>) -> Markdown {
+    fn relink_text(text: &[Inline], paths: &HashMap<String, String>) -> Vec<Inline> {
+        text.iter()
+            .map(|inline| match inline {
+                Inline::Image(alt, url) => {
+                    Inline::Image(alt.to_string(), paths.get(url).unwrap_or(url).to_string())
+                }
+                Inline::Link(text, url) => Inline::Link(relink_text(text, paths), url.to_string()),
+                Inline::Emphasis(text) => Inline::Emphasis(relink_text(text, paths)),
+                Inline::Strong(text) => Inline::Strong(relink_text(text, paths)),
+                Inline::EmphasisAndStrong(text) => {
+                    Inline::EmphasisAndStrong(relink_text(text, paths))
+                }
+                Inline::Deleted(text) => Inline::Deleted(relink_text(text, paths)),
+                _ => inline.clone(),
+            })
+            .collect()
+    }
+    try_map_text::<(), _>(mkd, &mut |text| Ok(relink_text(text, paths))).unwrap()
+}
+
+/// `href="#id"` to another chapter is `href="chapter-2.xhtml#id"`
+fn relative(xhtml: &str, file: &str, targets: &HashMap<String, String>) -> String {
+    lazy_static! {
+        static ref HREF: Regex = Regex::new(r##"href="#([^"]*)""##).unwrap();
+    }
+    HREF.replace_all(xhtml, |caps: &regex::Captures| {
+        let id = html_escape::decode_html_entities(&caps[1]);
+        match targets.get(id.as_ref()) {
+            Some(target) if target != file => format!("href=\"{}#{}\"", target, &caps[1]),
+            _ => caps[0].to_string(),
+        }
+    })
+    .to_string()
+}
+
+/// Chapters start at the headings of the level (or upper);
+/// a title directly followed by a chapter is in the chapter
+fn split(mkd: &Markdown, level: usize) -> Vec<Markdown> {
+    let mut chapters: Vec<Markdown> = vec![];
+    let mut chapter: Markdown = vec![];
+    for block in mkd.iter() {
+        if let Block::Heading(l, _, _) = block {
+            let titles_only = chapter
+                .iter()
+                .all(|b| matches!(b, Block::Heading(upper, _, _) if upper < l));
+            if *l <= level && !titles_only {
+                chapters.push(chapter);
+                chapter = vec![];
+            }
+        }
+        chapter.push(block.clone());
+    }
+    if !chapter.is_empty() || chapters.is_empty() {
+        chapters.push(chapter);
+    }
+    chapters
+}
+
+fn images_on_web(mkd: &Markdown) -> bool {
+    let mut found = false;
+    try_map_text::<(), _>(mkd, &mut |text| {
+        fn find(text: &[Inline]) -> bool {
+            text.iter().any(|inline| match inline {
+                Inline::Image(_, url) => url.contains("://"),
+                Inline::Link(text, _)
+                | Inline::Emphasis(text)
+                | Inline::Strong(text)
+                | Inline::EmphasisAndStrong(text)
+                | Inline::Deleted(text) => find(text),
+                _ => false,
+            })
+        }
+        found |= find(text);
+        Ok(text.clone())
+    })
+    .unwrap();
+    found
+}
+
+fn load(path: &str) -> Result<Vec<u8>, EpubError> {
+    std::fs::read(path).map_err(|err| EpubError(format!("Cannot read {}: {}", path, err)))
+}
+
+/// (extension, media type) of the core media types
+fn media_type(path: &str) -> Result<(&'static str, &'static str), EpubError> {
+    let ext = Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => Ok(("png", "image/png")),
+        "jpg" | "jpeg" => Ok(("jpg", "image/jpeg")),
+        "gif" => Ok(("gif", "image/gif")),
+        "svg" => Ok(("svg", "image/svg+xml")),
+        "webp" => Ok(("webp", "image/webp")),
+        _ => Err(EpubError(format!("Unsupported image type: {}", path))),
+    }
+}
+
+/// XHTML content document
+fn page(book: &Book, title: &str, stylesheets: &[String], body: &str) -> String {
+    let links: Vec<String> = stylesheets
+        .iter()
+        .map(|href| {
+            format!(
+                "<link rel=\"stylesheet\" type=\"text/css\" href=\"{}\"/>",
+                href
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{lang}" lang="{lang}">
+<head>
+<meta charset="UTF-8"/>
+<title>{title}</title>
+{links}
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        lang = attr(&book.language),
+        title = escape(title),
+        links = links.join("\n"),
+        body = body
+    )
+}
+
+/// (title, href) in the navigation document
+type NavPoint = (String, String);
+
+/// Navigation document; chapters and their sections
+fn navigation(
+    book: &Book,
+    stylesheets: &[String],
+    chapters: &[(NavPoint, Vec<NavPoint>)],
+) -> String {
+    let mut lines = vec![String::from("<nav epub:type=\"toc\" id=\"toc\">")];
+    lines.push(format!("<h1>{}</h1>", escape(&book.title)));
+    lines.push(String::from("<ol>"));
+    for ((title, href), sections) in chapters.iter() {
+        let link = format!("<a href=\"{}\">{}</a>", attr(href), escape(title));
+        if sections.is_empty() {
+            lines.push(format!("<li>{}</li>", link));
+        } else {
+            lines.push(format!("<li>{}", link));
+            lines.push(String::from("<ol>"));
+            for (title, href) in sections.iter() {
+                lines.push(format!(
+                    "<li><a href=\"{}\">{}</a></li>",
+                    attr(href),
+                    escape(title)
+                ));
+            }
+            lines.push(String::from("</ol>"));
+            lines.push(String::from("</li>"));
+        }
+    }
+    lines.push(String::from("</ol>"));
+    lines.push(String::from("</nav>"));
+    page(book, &book.title, stylesheets, &lines.join("\n"))
+}
+
+/// Package document (OPF)
+fn package(book: &Book, items: &[Item], spine: &[String]) -> String {
+    let mut metadata = vec![
+        format!(
+            "<dc:identifier id=\"uid\">{}</dc:identifier>",
+            escape(&book.identifier)
+        ),
+        format!("<dc:title>{}</dc:title>", escape(&book.title)),
+        format!("<dc:language>{}</dc:language>", escape(&book.language)),
+    ];
+    if let Some(author) = &book.author {
+        metadata.push(format!("<dc:creator>{}</dc:creator>", escape(author)));
+    }
+    metadata.push(format!(
+        "<meta property=\"dcterms:modified\">{}</meta>",
+        escape(&book.modified)
+    ));
+    if book.cover.is_some() {
+        metadata.push(String::from(
+            "<meta name=\"cover\" content=\"cover-image\"/>",
+        ));
+    }
+    let manifest: Vec<String> = items
+        .iter()
+        .map(|item| {
+            let properties = match item.properties {
+                Some(properties) => format!(" properties=\"{}\"", properties),
+                None => String::new(),
+            };
+            format!(
+                "<item id=\"{}\" href=\"{}\" media-type=\"{}\"{}/>",
+                item.id, item.href, item.media_type, properties
+            )
+        })
+        .collect();
+    let spine: Vec<String> = spine
+        .iter()
+        .map(|id| format!("<itemref idref=\"{}\"/>", id))
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid" xml:lang="{}">
+<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+{}
+</metadata>
+<manifest>
+{}
+</manifest>
+<spine>
+{}
+</spine>
+</package>
+"#,
+        attr(&book.language),
+        metadata.join("\n"),
+        manifest.join("\n"),
+        spine.join("\n")
+    )
+}
+
+/// OCF zip container; `mimetype` is the first and stored
+fn container(opf: &str, items: &[Item]) -> zip::result::ZipResult<Vec<u8>> {
+    const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+<rootfiles>
+<rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+</rootfiles>
+</container>
+"#;
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(opf.as_bytes())?;
+    for item in items.iter() {
+        zip.start_file(format!("OEBPS/{}", item.href), deflated)?;
+        zip.write_all(&item.content)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// `urn:uuid:` from a hash of the text; the same book has the same identifier
+fn uuid(text: &str) -> String {
+    let hash = |seed: u64| {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        text.hash(&mut hasher);
+        hasher.finish()
+    };
+    let hex = format!("{:016x}{:016x}", hash(0), hash(1));
+    format!(
+        "urn:uuid:{}-{}-4{}-8{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[13..16],
+        &hex[17..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod test_epub {
+    use crate::parser;
+    use crate::writer::epub::*;
+    use crate::writer::package::timestamp;
+    use lazy_static::lazy_static;
+    use regex::Regex;
+    use std::collections::BTreeSet;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn unzip(epub: &[u8]) -> Vec<(String, CompressionMethod, String)> {
+        let mut archive = ZipArchive::new(Cursor::new(epub)).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let mut file = archive.by_index(i).unwrap();
+                let mut content = vec![];
+                file.read_to_end(&mut content).unwrap();
+                (
+                    file.name().to_string(),
+                    file.compression(),
+                    String::from_utf8_lossy(&content).to_string(),
+                )
+            })
+            .collect()
+    }
+
+    /// Tags are balanced (or self-closing)
+    fn well_formed(xml: &str) -> bool {
+        lazy_static! {
+            static ref TAG: Regex = Regex::new(r"<(/?)([A-Za-z][\w:.-]*)[^>]*?(/?)>").unwrap();
+        }
+        let mut stack = vec![];
+        for caps in TAG.captures_iter(xml) {
+            match (&caps[1], &caps[3]) {
+                ("", "") => stack.push(caps[2].to_string()),
+                ("/", _) if stack.pop().as_deref() != Some(&caps[2]) => return false,
+                _ => {}
+            }
+        }
+        stack.is_empty()
+    }
+
+    #[test]
+    fn test_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("arch.png");
+        std::fs::write(&image, b"\x89PNG").unwrap();
+        let markdown = format!(
+            concat!(
+                "# Handbook\n\nPreface & intro.\n\n",
+                "## Setup\n\nSee @fig:arch and [usage](#usage).\n\n",
+                "![Architecture]({}){{#fig:arch}}\n\n",
+                "### Install\n\n- [x] done\n\n",
+                "## Usage {{#usage}}\n\nDone <b>now</b> &amp; [back](#2-Setup).\n"
+            ),
+            image.to_str().unwrap()
+        );
+        let mkd = parser::markdown(&markdown).unwrap();
+        let book = Book {
+            title: String::from("Handbook"),
+            author: Some(String::from("cympfh")),
+            language: String::from("en"),
+            identifier: String::from("urn:uuid:0"),
+            modified: timestamp(0),
+            cover: Some(image.to_str().unwrap().to_string()),
+            css: vec![],
+            chapter_level: 2,
+        };
+        let tr = Translator::new(None).with_execute(false);
+        let files = unzip(&build(&book, &mkd, &tr).unwrap());
+        let get = |name: &str| {
+            files
+                .iter()
+                .find(|(n, _, _)| n == name)
+                .map(|(_, _, content)| content.to_string())
+                .unwrap()
+        };
+
+        // OCF: mimetype first and uncompressed
+        assert_eq!(files[0].0, "mimetype");
+        assert_eq!(files[0].1, CompressionMethod::Stored);
+        assert_eq!(files[0].2, "application/epub+zip");
+        assert!(get("META-INF/container.xml").contains("full-path=\"OEBPS/content.opf\""));
+
+        // package: metadata, and the manifest is the files
+        let opf = get("OEBPS/content.opf");
+        assert!(well_formed(&opf));
+        for meta in [
+            "<dc:identifier id=\"uid\">urn:uuid:0</dc:identifier>",
+            "<dc:title>Handbook</dc:title>",
+            "<dc:language>en</dc:language>",
+            "<dc:creator>cympfh</dc:creator>",
+            "<meta property=\"dcterms:modified\">1970-01-01T00:00:00Z</meta>",
+        ] {
+            assert!(opf.contains(meta), "{}", meta);
+        }
+        lazy_static! {
+            static ref HREF: Regex = Regex::new(r#"<item id="([^"]+)" href="([^"]+)""#).unwrap();
+            static ref IDREF: Regex = Regex::new(r#"<itemref idref="([^"]+)""#).unwrap();
+        }
+        let manifest: BTreeSet<String> = HREF
+            .captures_iter(&opf)
+            .map(|caps| format!("OEBPS/{}", &caps[2]))
+            .collect();
+        let contents: BTreeSet<String> = files
+            .iter()
+            .map(|(name, _, _)| name.to_string())
+            .filter(|name| name.starts_with("OEBPS/") && name != "OEBPS/content.opf")
+            .collect();
+        assert_eq!(manifest, contents);
+        assert_eq!(opf.matches("properties=\"nav\"").count(), 1);
+        assert_eq!(opf.matches("properties=\"cover-image\"").count(), 1);
+        let spine: Vec<String> = IDREF
+            .captures_iter(&opf)
+            .map(|caps| caps[1].to_string())
+            .collect();
+        assert_eq!(spine, vec!["cover", "chapter-1", "chapter-2", "chapter-3"]);
+
+        // content documents
+        for (name, _, content) in files.iter().filter(|(n, _, _)| n.ends_with(".xhtml")) {
+            assert!(well_formed(content), "{}", name);
+        }
+        let nav = get("OEBPS/nav.xhtml");
+        assert!(nav.contains("<a href=\"chapter-2.xhtml\">Setup</a>"));
+        assert!(nav.contains("<a href=\"chapter-2.xhtml#3-Install\">Install</a>"));
+        assert!(nav.contains("<a href=\"chapter-3.xhtml\">Usage</a>"));
+        // the same ids as the HTML, and the links across the chapters
+        let setup = get("OEBPS/chapter-2.xhtml");
+        assert!(setup.contains("<h2 id=\"2-Setup\">Setup</h2>"));
+        assert!(setup.contains("<a href=\"chapter-3.xhtml#usage\">usage</a>"));
+        assert!(setup.contains(">Figure 1</a>"));
+        assert!(setup.contains("<img src=\"images/1.png\" alt=\"Architecture\" />"));
+        assert!(get("OEBPS/chapter-1.xhtml").contains("Preface &amp; intro."));
+        let usage = get("OEBPS/chapter-3.xhtml");
+        assert!(
+            usage.contains("Done <b>now</b> &amp; <a href=\"chapter-2.xhtml#2-Setup\">back</a> .")
+        );
+
+        // --slug
+        let tr = Translator::new(None)
+            .with_execute(false)
+            .with_slug(crate::slug::Slug::Ascii);
+        let files = unzip(&build(&book, &mkd, &tr).unwrap());
+        let nav = &files
+            .iter()
+            .find(|(n, _, _)| n == "OEBPS/nav.xhtml")
+            .unwrap()
+            .2;
+        assert!(nav.contains("<a href=\"chapter-2.xhtml#install\">Install</a>"));
+        assert_eq!(get("OEBPS/images/1.png"), "\u{fffd}PNG");
+    }
+
+    #[test]
+    fn test_split() {
+        let mkd = parser::markdown("# T\n\na\n\n## A\n\n### A.1\n\n## B\n").unwrap();
+        let chapters = split(&mkd, 2);
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[1].len(), 2);
+        assert_eq!(split(&mkd, 1).len(), 1);
+    }
+}
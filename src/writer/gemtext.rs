@@ -0,0 +1,282 @@
+use crate::entity::markdown::spaced;
+use crate::entity::markdown::{Block, Inline, List, ListOrderType, Markdown, Text};
+use crate::template::{self, Context};
+use crate::wikilink::WikiLink;
+use crate::writer::plain::grid;
+use crate::writer::xhtml::kind;
+use crate::writer::{join, plain, Writer};
+use handlebars::RenderError;
+use std::cell::RefCell;
+
+/// Gemini gemtext; inline links are moved to `=>` lines after their blocks
+pub struct Gemtext {
+    ascii: bool,
+    /// (url, text) of the links in the current block
+    links: RefCell<Vec<(String, String)>>,
+    /// (level, title) for `[TOC]`
+    headings: RefCell<Vec<(usize, String)>>,
+}
+
+impl Writer for Gemtext {
+    fn write(&self, mkd: &Markdown) -> String {
+        self.links.borrow_mut().clear();
+        *self.headings.borrow_mut() = headings(mkd);
+        let lines = self.blocks(mkd);
+        if lines.is_empty() {
+            String::new()
+        } else {
+            lines.join("\n") + "\n"
+        }
+    }
+
+    fn standalone(&self, context: Context) -> Result<String, RenderError> {
+        template::custom("{{{body}}}", context)
+    }
+}
+
+impl Gemtext {
+    pub fn new(ascii: bool) -> Self {
+        Self {
+            ascii,
+            links: RefCell::new(vec![]),
+            headings: RefCell::new(vec![]),
+        }
+    }
+
+    /// Lines of the blocks, each followed by its link lines, separated by empty lines
+    fn blocks(&self, mkd: &Markdown) -> Vec<String> {
+        let mut lines: Vec<String> = vec![];
+        for block in mkd.iter() {
+            let mut block = self.block(block);
+            for (url, text) in self.links.take() {
+                block.push(link_line(&url, &text));
+            }
+            if block.is_empty() {
+                continue;
+            }
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.extend(block);
+        }
+        lines
+    }
+
+    fn block(&self, block: &Block) -> Vec<String> {
+        match block {
+            // only three levels in gemtext
+            Block::Heading(level, text, _) => vec![format!(
+                "{} {}",
+                "#".repeat((*level).clamp(1, 3)),
+                self.line(text)
+            )],
+            Block::ListBlock(list) => self.list(list),
+            // a paragraph of images (e.g. a figure) is only the link lines
+            Block::Paragraph(text) if text.iter().all(|i| matches!(i, Inline::Image(..))) => {
+                self.text(text);
+                vec![]
+            }
+            Block::Paragraph(text) => lines(&self.text(text)),
+            Block::Quoted(text) => lines(&self.text(text))
+                .into_iter()
+                .map(|line| format!("> {}", line))
+                .collect(),
+            Block::Code(lang, code) => {
+                let alt = lang.as_deref().unwrap_or("").trim_start_matches('@');
+                preformatted(alt, code.trim_end_matches('\n').lines())
+            }
+            Block::CodeImport(_, path) | Block::Import(path) => vec![link_line(path, "")],
+            Block::HorizontalRule => vec![String::from("---")],
+            Block::Table(aligns, content, has_header) => {
+                let rows: Vec<Vec<String>> = content
+                    .iter()
+                    .map(|row| row.iter().map(|cell| self.line(cell)).collect())
+                    .collect();
+                let table = grid(aligns, &rows, *has_header, self.ascii);
+                preformatted("", table.iter().map(String::as_str))
+            }
+            Block::HyperLink(url) => vec![link_line(url, "")],
+            Block::MathJax(tex) => preformatted("math", tex.trim().lines()),
+            Block::Div(_, children) => self.blocks(children),
+            Block::Toc => self
+                .headings
+                .borrow()
+                .iter()
+                .filter(|(level, _)| *level > 1)
+                .map(|(_, title)| format!("* {}", title))
+                .collect(),
+            Block::Labeled(block, caption, _) => {
+                let mut lines = self.block(block);
+                if !caption.is_empty() {
+                    lines.push(format!("{}: {}", kind(block).name(), self.line(caption)));
+                }
+                lines
+            }
+            Block::Abbreviation(..) | Block::Index => vec![],
+        }
+    }
+
+    /// `* ` items; gemtext has no nested lists, so the children follow their parents
+    fn list(&self, list: &List) -> Vec<String> {
+        let mut lines = vec![];
+        for (i, item) in list.items.iter().enumerate() {
+            let number = match list.order_type {
+                ListOrderType::Unordered => String::new(),
+                ListOrderType::OrderedNumbers => format!("{}. ", i + 1),
+                ListOrderType::OrderedAlphabets => format!("{}. ", (b'a' + (i % 26) as u8) as char),
+            };
+            let checkbox = match item.checked {
+                Some(true) => "[x] ",
+                Some(false) => "[ ] ",
+                None => "",
+            };
+            lines.push(format!(
+                "* {}{}{}",
+                number,
+                checkbox,
+                self.line(&item.label)
+            ));
+            if let Some(children) = &item.children {
+                lines.extend(self.list(children));
+            }
+        }
+        lines
+    }
+
+    /// Text in a line, e.g. of headings and list items
+    fn line(&self, text: &Text) -> String {
+        self.text(text).replace('\n', " ")
+    }
+
+    fn text(&self, text: &Text) -> String {
+        let mut pieces: Vec<String> = vec![];
+        for (i, inline) in text.iter().enumerate() {
+            let piece = self.inline(inline);
+            match pieces.last_mut() {
+                Some(last) if !spaced(text.get(i.wrapping_sub(1)), inline) => *last += &piece,
+                _ => pieces.push(piece),
+            }
+        }
+        join(&pieces).replace(" \n", "\n").replace("\n ", "\n")
+    }
+
+    fn inline(&self, inline: &Inline) -> String {
+        match inline {
+            Inline::Link(text, url) => {
+                let text = self.text(text);
+                // no anchors in gemini
+                if !url.starts_with('#') {
+                    self.link(url, if text == *url { "" } else { &text });
+                }
+                text
+            }
+            Inline::HyperLink(target) => match WikiLink::parse(target) {
+                Some(link) => link.label.unwrap_or(link.page),
+                None => {
+                    self.link(target, "");
+                    target.to_string()
+                }
+            },
+            // the alt text in place, and the image in the link line
+            Inline::Image(alt, url) => {
+                self.link(url, alt);
+                alt.to_string()
+            }
+            Inline::Code(code) => code.to_string(),
+            Inline::Emphasis(text)
+            | Inline::Strong(text)
+            | Inline::EmphasisAndStrong(text)
+            | Inline::Deleted(text)
+            | Inline::Index(text, _) => self.text(text),
+            Inline::Newline => String::from("\n"),
+            Inline::Comment(_) => String::new(),
+            Inline::Citation(_, true) => format!("[{}]", plain(&vec![inline.clone()])),
+            _ => plain(&vec![inline.clone()]),
+        }
+    }
+
+    /// Link line after the current block; once per URL
+    fn link(&self, url: &str, text: &str) {
+        let mut links = self.links.borrow_mut();
+        if !links.iter().any(|(u, _)| u == url) {
+            links.push((url.to_string(), text.to_string()));
+        }
+    }
+}
+
+fn headings(mkd: &Markdown) -> Vec<(usize, String)> {
+    let mut headings = vec![];
+    for block in mkd.iter() {
+        match block {
+            Block::Heading(level, text, _) => headings.push((*level, plain(text))),
+            Block::Div(_, children) => headings.extend(self::headings(children)),
+            _ => {}
+        }
+    }
+    headings
+}
+
+/// Lines of a paragraph
+fn lines(text: &str) -> Vec<String> {
+    if text.trim().is_empty() {
+        vec![]
+    } else {
+        text.lines().map(|line| line.to_string()).collect()
+    }
+}
+
+/// `=> URL text`
+fn link_line(url: &str, text: &str) -> String {
+    if text.is_empty() {
+        format!("=> {}", url)
+    } else {
+        format!("=> {} {}", url, text)
+    }
+}
+
+/// ```` ``` ```` toggled lines with the alt text
+fn preformatted<'a>(alt: &str, lines: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut out = vec![format!("```{}", alt)];
+    out.extend(lines.map(|line| line.to_string()));
+    out.push(String::from("```"));
+    out
+}
+
+#[cfg(test)]
+mod test_gemtext {
+    use crate::parser;
+    use crate::writer::gemtext::*;
+
+    #[test]
+    fn test_write() {
+        let mkd = parser::markdown(concat!(
+            "# Capsule\n\n",
+            "#### Deep *heading*\n\n",
+            "See **the** [docs](gemini://example.org/docs) and [[https://a.com]], [top](#top).\n\n",
+            "![Logo](logo.png)\n\n",
+            "See ![the site](site.png) and [![a badge](badge.svg)](ci.html).\n\n",
+            "- one\n    1. [nested](nested.gmi)\n\n",
+            "```rust\nfn main() {}\n```\n\n",
+            "|a|b|\n|:-|-:|\n|long|1|\n"
+        ))
+        .unwrap();
+        assert_eq!(
+            Gemtext::new(true).write(&mkd),
+            concat!(
+                "# Capsule\n\n",
+                "### Deep heading\n\n",
+                "See the docs and https://a.com, top.\n",
+                "=> gemini://example.org/docs docs\n",
+                "=> https://a.com\n\n",
+                "=> logo.png Logo\n\n",
+                "See the site and a badge.\n",
+                "=> site.png the site\n",
+                "=> badge.svg a badge\n",
+                "=> ci.html a badge\n\n",
+                "* one\n* 1. nested\n=> nested.gmi nested\n\n",
+                "```rust\nfn main() {}\n```\n\n",
+                "```\n+------+---+\n| a    | b |\n+======+===+\n| long | 1 |\n+------+---+\n```\n"
+            )
+        );
+    }
+}
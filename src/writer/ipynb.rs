@@ -0,0 +1,292 @@
+use crate::entity::markdown::{Block, Markdown};
+use crate::executor::{ExecuteResult, Executor};
+use crate::notebook::{Cell, Notebook, Output, Source};
+use crate::writer::markdown::MarkdownWriter;
+use crate::writer::package::walk;
+use crate::writer::Writer;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Kernel of the notebook (`metadata.kernelspec`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kernel {
+    pub name: String,
+    pub display_name: String,
+    /// Code blocks of this language are the code cells
+    pub language: String,
+}
+
+impl Kernel {
+    /// `kernel`, `kernel-display-name` and `kernel-language` (default: `python`) in the variables
+    pub fn new(variables: &HashMap<String, String>) -> Self {
+        let language = variables
+            .get("kernel-language")
+            .cloned()
+            .unwrap_or_else(|| String::from("python"));
+        Self::with_language(variables, &language)
+    }
+
+    /// The language overrides `kernel-language`
+    pub fn with_language(variables: &HashMap<String, String>, language: &str) -> Self {
+        let (name, display_name) = match language {
+            "python" => ("python3", "Python 3"),
+            "bash" => ("bash", "Bash"),
+            _ => (language, language),
+        };
+        let get = |key: &str, default: &str| {
+            variables
+                .get(key)
+                .cloned()
+                .unwrap_or_else(|| default.to_string())
+        };
+        let name = get("kernel", name);
+        let display_name = match variables.get("kernel-display-name") {
+            Some(display_name) => display_name.to_string(),
+            None if variables.contains_key("kernel") => name.to_string(),
+            None => display_name.to_string(),
+        };
+        Self {
+            name,
+            display_name,
+            language: language.to_string(),
+        }
+    }
+}
+
+/// Notebook of the document; `outputs` runs the executors (`@bash` etc.) of the code cells
+pub fn build(kernel: &Kernel, mkd: &Markdown, outputs: bool) -> Notebook {
+    let mut cells = Cells {
+        kernel,
+        outputs,
+        cells: vec![],
+        markdown: vec![],
+        execution_count: 0,
+    };
+    cells.blocks(mkd);
+    cells.flush();
+    Notebook {
+        cells: cells.cells,
+        metadata: json!({
+            "kernelspec": {
+                "name": kernel.name,
+                "display_name": kernel.display_name,
+                "language": kernel.language,
+            },
+            "language_info": {"name": kernel.language},
+        }),
+        nbformat: 4,
+        // cell ids are required since 4.5
+        nbformat_minor: 4,
+    }
+}
+
+/// Cells in the making; the markdowns between the code cells are merged
+struct Cells<'a> {
+    kernel: &'a Kernel,
+    outputs: bool,
+    cells: Vec<Cell>,
+    markdown: Markdown,
+    execution_count: u64,
+}
+
+impl Cells<'_> {
+    /// Code cells also in divs (whose fences are dropped) and captioned listings
+    /// (whose captions follow as paragraphs)
+    fn blocks(&mut self, mkd: &Markdown) {
+        for block in mkd.iter() {
+            match block {
+                Block::Code(Some(lang), code) if self.is_code(lang) => self.code(lang, code),
+                Block::Labeled(inner, caption, _) => match inner.as_ref() {
+                    Block::Code(Some(lang), code) if self.is_code(lang) => {
+                        self.code(lang, code);
+                        if !caption.is_empty() {
+                            self.markdown.push(Block::Paragraph(caption.clone()));
+                        }
+                    }
+                    _ => self.markdown.push(block.clone()),
+                },
+                Block::Div(_, children) if self.has_code(children) => self.blocks(children),
+                _ => self.markdown.push(block.clone()),
+            }
+        }
+    }
+
+    fn is_code(&self, lang: &str) -> bool {
+        lang.trim_start_matches('@') == self.kernel.language
+    }
+
+    fn has_code(&self, mkd: &Markdown) -> bool {
+        let mut found = false;
+        walk(mkd, &mut |block| {
+            found |= matches!(block, Block::Code(Some(lang), _) if self.is_code(lang));
+        });
+        found
+    }
+
+    fn code(&mut self, lang: &str, code: &String) {
+        self.flush();
+        let outputs = if self.outputs && lang.starts_with('@') {
+            execute(lang, code)
+        } else {
+            None
+        };
+        let execution_count = outputs.as_ref().map(|_| {
+            self.execution_count += 1;
+            self.execution_count
+        });
+        self.cells.push(Cell::Code {
+            metadata: json!({}),
+            source: lines(code.trim_end_matches('\n')),
+            outputs: outputs.unwrap_or_default(),
+            execution_count,
+        });
+    }
+
+    fn flush(&mut self) {
+        if !self.markdown.is_empty() {
+            self.cells
+                .push(markdown_cell(&std::mem::take(&mut self.markdown)));
+        }
+    }
+}
+
+/// JSON indented by a space, as Jupyter does
+pub fn write(notebook: &Notebook) -> String {
+    let mut buf = vec![];
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    notebook.serialize(&mut serializer).unwrap();
+    String::from_utf8(buf).unwrap() + "\n"
+}
+
+fn markdown_cell(mkd: &Markdown) -> Cell {
+    Cell::Markdown {
+        metadata: json!({}),
+        source: lines(MarkdownWriter.write(mkd).trim_end_matches('\n')),
+    }
+}
+
+/// Outputs of the executors; `None` for the languages without executors
+fn execute(lang: &str, code: &String) -> Option<Vec<Output>> {
+    let display = |mime: &str, data: String| Output::DisplayData {
+        data: BTreeMap::from([(mime.to_string(), Value::String(data))]),
+        metadata: json!({}),
+    };
+    let stream = |res: ExecuteResult| match res {
+        ExecuteResult::Ok(stdout) => Output::Stream {
+            name: String::from("stdout"),
+            text: lines(&stdout),
+        },
+        ExecuteResult::Err(stderr) => Output::Stream {
+            name: String::from("stderr"),
+            text: lines(&stderr),
+        },
+    };
+    let output = match lang {
+        "@bash" => stream(Executor::bash(code)),
+        "@dot" | "@graphviz" => match Executor::dot(code) {
+            ExecuteResult::Ok(url) => display(
+                "image/png",
+                url.trim_start_matches("data:image/png;base64,").to_string(),
+            ),
+            res => stream(res),
+        },
+        "@gnuplot" => match Executor::gnuplot(code) {
+            ExecuteResult::Ok(svg) => display("image/svg+xml", svg),
+            res => stream(res),
+        },
+        _ => return None,
+    };
+    Some(vec![output])
+}
+
+/// Lines with the newlines, as Jupyter does
+fn lines(text: &str) -> Source {
+    Source::Lines(text.split_inclusive('\n').map(String::from).collect())
+}
+
+#[cfg(test)]
+mod test_ipynb {
+    use crate::parser;
+    use crate::writer::ipynb::*;
+
+    #[test]
+    fn test_build() {
+        let mkd = parser::markdown(concat!(
+            "# Tutorial\n\nFirst, *print*.\n\n",
+            "```python\nprint(1)\nprint(2)\n```\n\n",
+            "```rust\nfn main() {}\n```\n\n",
+            "```python\nx = 1\n```\n\n",
+            "::: note\nIn a div.\n\n```python\ny = 2\n```\n:::\n\n",
+            "```python\nz = 3\n```\n: Listing of z {#lst:z}\n\n",
+            "::: note\nNo code.\n:::\n"
+        ))
+        .unwrap();
+        let kernel = Kernel::new(&HashMap::new());
+        let notebook = build(&kernel, &mkd, false);
+        assert_eq!(notebook.language(), Some(String::from("python")));
+        assert_eq!(
+            notebook.metadata["kernelspec"]["display_name"],
+            json!("Python 3")
+        );
+        let sources: Vec<(&str, String)> = notebook
+            .cells
+            .iter()
+            .map(|cell| match cell {
+                Cell::Markdown { source, .. } => ("markdown", source.text()),
+                Cell::Code { source, .. } => ("code", source.text()),
+                Cell::Raw { source, .. } => ("raw", source.text()),
+            })
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                ("markdown", String::from("# Tutorial\n\nFirst, *print*.")),
+                ("code", String::from("print(1)\nprint(2)")),
+                ("markdown", String::from("```rust\nfn main() {}\n```")),
+                ("code", String::from("x = 1")),
+                ("markdown", String::from("In a div.")),
+                ("code", String::from("y = 2")),
+                ("code", String::from("z = 3")),
+                (
+                    "markdown",
+                    String::from("Listing of z\n\n::: note\n\nNo code.\n\n:::")
+                ),
+            ]
+        );
+        assert_eq!(Notebook::from_json(&write(&notebook)).unwrap(), notebook);
+    }
+
+    #[test]
+    fn test_outputs() {
+        let mkd = parser::markdown("```@bash\necho hi\n```\n\n```bash\necho no\n```\n").unwrap();
+        let variables = HashMap::from([(String::from("kernel"), String::from("bash"))]);
+        let kernel = Kernel::with_language(&variables, "bash");
+        assert_eq!(kernel.display_name, "bash");
+        let notebook = build(&kernel, &mkd, true);
+        assert_eq!(
+            notebook.cells[0],
+            Cell::Code {
+                metadata: json!({}),
+                source: Source::Lines(vec![String::from("echo hi")]),
+                outputs: vec![Output::Stream {
+                    name: String::from("stdout"),
+                    text: Source::Lines(vec![String::from("hi\n")]),
+                }],
+                execution_count: Some(1),
+            }
+        );
+        if let Cell::Code {
+            outputs,
+            execution_count,
+            ..
+        } = &notebook.cells[1]
+        {
+            assert!(outputs.is_empty());
+            assert_eq!(*execution_count, None);
+        } else {
+            panic!("code cell expected");
+        }
+    }
+}
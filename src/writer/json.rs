@@ -0,0 +1,163 @@
+use crate::entity::markdown::{FrontMatter, Markdown};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Version of the JSON AST; bumped on incompatible changes of `entity::markdown`
+pub const VERSION: u64 = 1;
+
+/// Whole document in the JSON AST (`--to json`, `--from json`); see `schema/ast.schema.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub version: u64,
+    /// Front matters of the inputs
+    #[serde(default)]
+    pub meta: FrontMatter,
+    pub blocks: Markdown,
+}
+
+impl Document {
+    pub fn new(meta: FrontMatter, blocks: Markdown) -> Self {
+        Self {
+            version: VERSION,
+            meta,
+            blocks,
+        }
+    }
+}
+
+/// Not a JSON AST of this version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonError(String);
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl std::error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+/// Pretty-printed JSON AST
+pub fn write(doc: &Document) -> String {
+    serde_json::to_string_pretty(doc).unwrap() + "\n"
+}
+
+/// JSON AST; the version is checked before the blocks
+pub fn read(json: &str) -> Result<Document, JsonError> {
+    let value: Value =
+        serde_json::from_str(json).map_err(|err| JsonError(format!("Invalid JSON: {}", err)))?;
+    match value.get("version").and_then(Value::as_u64) {
+        Some(VERSION) => {}
+        Some(version) => {
+            return Err(JsonError(format!(
+                "Unsupported JSON AST version {} (expected {})",
+                version, VERSION
+            )))
+        }
+        None => return Err(JsonError(String::from("Not a JSON AST of unidoc"))),
+    }
+    serde_json::from_value(value).map_err(|err| JsonError(format!("Invalid JSON AST: {}", err)))
+}
+
+#[cfg(test)]
+mod test_json {
+    use crate::parser;
+    use crate::writer::json::*;
+    use std::collections::BTreeSet;
+
+    /// Every block and inline (`Newline` by the two trailing spaces)
+    const SAMPLE: &str = concat!(
+        r#"# Title {#top}
+
+[TOC]
+
+*[HTML]: HyperText Markup Language
+
+Plain *em* **strong** ***both*** ~~del~~ `code` $x$ :joy: <!-- hidden -->
+[link](http://example.com) [[http://example.com]] ![alt](a.png) ${name:-x}
+@fig:a [@knuth, p. 3] {HTML} [nom]{.idx term="parser!nom"}"#,
+        "  \n",
+        r#"next
+
+- [x] item
+    1. nested
+
+> quoted
+
+```rust
+fn main() {}
+```
+
+@[rust](sample.rs)
+
+---
+
+| a | b |
+|:-:|--:|
+| 1 | 2 |
+
+@(other.md)
+
+{{ https://example.com }}
+
+$$e = mc^2$$ {#eq:e}
+
+::: note
+![Figure](fig.png){#fig:a}
+:::
+
+[INDEX]
+"#
+    );
+
+    /// Values of `"t"` anywhere in the JSON
+    fn tags(value: &Value, tags: &mut BTreeSet<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(tag)) = map.get("t") {
+                    tags.insert(tag.to_string());
+                }
+                map.values().for_each(|value| self::tags(value, tags));
+            }
+            Value::Array(values) => values.iter().for_each(|value| self::tags(value, tags)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mkd = parser::markdown(SAMPLE).unwrap();
+        let doc = Document::new(vec![(String::from("title"), String::from("T"))], mkd);
+        assert_eq!(read(&write(&doc)), Ok(doc));
+        assert!(read(r#"{"version": 2, "blocks": []}"#).is_err());
+        assert!(read(r#"{"blocks": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_schema() {
+        let schema: Value =
+            serde_json::from_str(include_str!("../../schema/ast.schema.json")).unwrap();
+        let mut documented = BTreeSet::new();
+        for def in ["Block", "Inline"] {
+            for variant in schema["$defs"][def]["oneOf"].as_array().unwrap() {
+                documented.insert(
+                    variant["properties"]["t"]["const"]
+                        .as_str()
+                        .unwrap()
+                        .to_string(),
+                );
+            }
+        }
+        let mkd = parser::markdown(SAMPLE).unwrap();
+        let mut used = BTreeSet::new();
+        tags(
+            &serde_json::to_value(Document::new(vec![], mkd)).unwrap(),
+            &mut used,
+        );
+        assert_eq!(used, documented);
+        assert_eq!(schema["properties"]["version"]["const"], VERSION);
+    }
+}
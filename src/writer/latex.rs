@@ -0,0 +1,539 @@
+use crate::bibliography::{Bibliography, Entry, Style};
+use crate::entity::markdown::spaced;
+use crate::entity::markdown::{
+    Align, Attributes, Block, Inline, List, ListOrderType, Markdown, Text,
+};
+use crate::template::{self, Context};
+use crate::wikilink::WikiLink;
+use crate::writer::{join, Writer};
+use handlebars::RenderError;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+/// LaTeX (for `article`); `\documentclass` by the variable `documentclass`
+pub struct Latex {
+    /// Sections are unnumbered (`\section*`) unless `--number-sections`
+    number_sections: bool,
+    /// `thebibliography` of the cited entries
+    bibliography: Option<RefCell<Bibliography>>,
+    /// `\title` is written, by the front matter or the first h1
+    titled: Cell<bool>,
+    /// The other h1 are `\chapter` (`book` and `report`) or `\section`
+    chapters: Cell<bool>,
+    /// Label -> title of the unnumbered headings, referred by `\hyperref`
+    unnumbered: RefCell<HashMap<String, String>>,
+}
+
+const TEMPLATE: &str = r#"\documentclass{ {{~#if variable.documentclass}}{{variable.documentclass}}{{else}}article{{/if~}} }
+\usepackage{amsmath,amssymb}
+\usepackage{graphicx}
+\usepackage{hyperref}
+\usepackage{listings}
+\usepackage{enumitem}
+\usepackage[normalem]{ulem}
+\usepackage{natbib}
+\usepackage{makeidx}
+\makeindex
+\lstset{basicstyle=\ttfamily\small,breaklines=true,frame=single}
+{{#each headers}}{{{this}}}
+{{/each}}
+\begin{document}
+{{#each befores}}{{{this}}}
+{{/each}}
+{{{body}}}
+{{#each afters}}{{{this}}}
+{{/each}}
+\end{document}
+"#;
+
+impl Writer for Latex {
+    fn write(&self, mkd: &Markdown) -> String {
+        self.scan(mkd, &mut self.titled.get());
+        mkd.iter()
+            .map(|block| self.block(block))
+            .filter(|s| !s.is_empty())
+            .map(|s| s + "\n")
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn write_with_variables(&self, mkd: &Markdown, variables: &HashMap<String, String>) -> String {
+        self.chapters.set(matches!(
+            variables.get("documentclass").map(String::as_str),
+            Some("book" | "report")
+        ));
+        match variables.get("title") {
+            Some(title) if !self.titled.replace(true) => format!(
+                "\\title{{{}}}\n\\maketitle\n\n{}",
+                escape(title),
+                self.write(mkd)
+            ),
+            _ => self.write(mkd),
+        }
+    }
+
+    fn finish(&self) -> String {
+        let Some(bibliography) = &self.bibliography else {
+            return String::new();
+        };
+        let bibliography = bibliography.borrow();
+        let entries = bibliography.cited();
+        if entries.is_empty() {
+            return String::new();
+        }
+        let mut lines = vec![format!("\\begin{{thebibliography}}{{{}}}", entries.len())];
+        for entry in entries {
+            // `Knuth et al.(1984)` for the author-year citations of natbib
+            let label = match bibliography.style() {
+                Style::AuthorYear => format!(
+                    "[{}({})]",
+                    escape(&entry.author_label()),
+                    escape(&entry.year_label())
+                ),
+                Style::Numeric => String::new(),
+            };
+            lines.push(format!(
+                "\\bibitem{}{{{}}} {}",
+                label,
+                entry.key,
+                reference(entry)
+            ));
+        }
+        lines.push(String::from("\\end{thebibliography}"));
+        lines.join("\n") + "\n"
+    }
+
+    fn standalone(&self, context: Context) -> Result<String, RenderError> {
+        template::custom(TEMPLATE, context)
+    }
+}
+
+impl Latex {
+    pub fn new(number_sections: bool, bibliography: Option<Bibliography>) -> Self {
+        Self {
+            number_sections,
+            bibliography: bibliography.map(RefCell::new),
+            titled: Cell::new(false),
+            chapters: Cell::new(false),
+            unnumbered: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Titles of the unnumbered headings with the labels
+    fn scan(&self, mkd: &Markdown, titled: &mut bool) {
+        for block in mkd.iter() {
+            match block {
+                Block::Heading(level, text, attributes) => {
+                    let title = *level == 1 && !std::mem::replace(titled, true);
+                    let numbered = self.number_sections && !attributes.has_class("unnumbered");
+                    if let (Some(id), true) = (&attributes.id, title || !numbered) {
+                        self.unnumbered
+                            .borrow_mut()
+                            .insert(id.to_string(), self.text(text));
+                    }
+                }
+                Block::Div(_, children) => self.scan(children, titled),
+                _ => {}
+            }
+        }
+    }
+
+    fn block(&self, block: &Block) -> String {
+        match block {
+            Block::Heading(1, text, attributes) if !self.titled.replace(true) => {
+                format!(
+                    "\\title{{{}}}{}\n\\maketitle",
+                    self.text(text),
+                    label(attributes)
+                )
+            }
+            Block::Heading(level, text, attributes) => {
+                let command = match level {
+                    1 if self.chapters.get() => "chapter",
+                    1 | 2 => "section",
+                    3 => "subsection",
+                    4 => "subsubsection",
+                    5 => "paragraph",
+                    _ => "subparagraph",
+                };
+                let star = if self.number_sections && !attributes.has_class("unnumbered") {
+                    ""
+                } else {
+                    "*"
+                };
+                format!(
+                    "\\{}{}{{{}}}{}",
+                    command,
+                    star,
+                    self.text(text),
+                    label(attributes)
+                )
+            }
+            Block::ListBlock(list) => self.list(list),
+            Block::Paragraph(text) => self.text(text),
+            Block::Quoted(text) => {
+                format!("\\begin{{quote}}\n{}\n\\end{{quote}}", self.text(text))
+            }
+            Block::Code(lang, code) => listing(lang, code, &[]),
+            Block::CodeImport(lang, path) => match lang {
+                Some(lang) => format!("\\lstinputlisting[language={}]{{{}}}", lang, path),
+                None => format!("\\lstinputlisting{{{}}}", path),
+            },
+            Block::HorizontalRule => String::from("\\noindent\\rule{\\linewidth}{0.4pt}"),
+            Block::Table(aligns, content, has_header) => self.table(aligns, content, *has_header),
+            // notebooks; markdowns are inlined before
+            Block::Import(path) => format!("% @({})", path),
+            Block::HyperLink(url) => format!("\\url{{{}}}", url),
+            Block::MathJax(tex) => format!("\\[\n{}\n\\]", tex.trim()),
+            Block::Div(_, children) => self.write(children).trim_end().to_string(),
+            Block::Toc => String::from("\\tableofcontents"),
+            Block::Labeled(block, caption, attributes) => self.labeled(block, caption, attributes),
+            Block::Abbreviation(..) => String::new(),
+            Block::Index => String::from("\\printindex"),
+        }
+    }
+
+    fn labeled(&self, block: &Block, caption: &Text, attributes: &Attributes) -> String {
+        let caption = self.text(caption);
+        match block {
+            Block::MathJax(tex) => format!(
+                "\\begin{{equation}}\n{}{}\n\\end{{equation}}",
+                tex.trim(),
+                label(attributes)
+            ),
+            Block::Code(lang, code) => {
+                let mut options = vec![format!("caption={{{}}}", caption)];
+                if let Some(id) = &attributes.id {
+                    options.push(format!("label={{{}}}", id));
+                }
+                listing(lang, code, &options)
+            }
+            Block::Table(..) => format!(
+                "\\begin{{table}}[htbp]\n\\centering\n\\caption{{{}}}{}\n{}\n\\end{{table}}",
+                caption,
+                label(attributes),
+                self.block(block)
+            ),
+            _ => format!(
+                "\\begin{{figure}}[htbp]\n\\centering\n{}\n\\caption{{{}}}{}\n\\end{{figure}}",
+                self.block(block),
+                caption,
+                label(attributes)
+            ),
+        }
+    }
+
+    fn list(&self, list: &List) -> String {
+        let (begin, end) = match list.order_type {
+            ListOrderType::Unordered => ("\\begin{itemize}", "\\end{itemize}"),
+            ListOrderType::OrderedNumbers => ("\\begin{enumerate}", "\\end{enumerate}"),
+            ListOrderType::OrderedAlphabets => {
+                ("\\begin{enumerate}[label=\\alph*.]", "\\end{enumerate}")
+            }
+        };
+        let mut lines = vec![begin.to_string()];
+        for item in list.items.iter() {
+            let mark = match item.checked {
+                Some(true) => "[$\\boxtimes$]",
+                Some(false) => "[$\\square$]",
+                None => "",
+            };
+            lines.push(format!("\\item{} {}", mark, self.text(&item.label)));
+            if let Some(children) = &item.children {
+                lines.push(self.list(children));
+            }
+        }
+        lines.push(end.to_string());
+        lines.join("\n")
+    }
+
+    fn table(&self, aligns: &[Align], content: &[Vec<Text>], has_header: bool) -> String {
+        let spec: String = aligns
+            .iter()
+            .map(|align| match align {
+                Align::Left => 'l',
+                Align::Center => 'c',
+                Align::Right => 'r',
+            })
+            .collect();
+        let mut lines = vec![
+            format!("\\begin{{tabular}}{{{}}}", spec),
+            String::from("\\hline"),
+        ];
+        for (i, row) in content.iter().enumerate() {
+            let cells: Vec<String> = row.iter().map(|cell| self.text(cell)).collect();
+            lines.push(format!("{} \\\\", cells.join(" & ")));
+            if i == 0 && has_header {
+                lines.push(String::from("\\hline"));
+            }
+        }
+        lines.push(String::from("\\hline"));
+        lines.push(String::from("\\end{tabular}"));
+        lines.join("\n")
+    }
+
+    fn text(&self, text: &Text) -> String {
+        let mut pieces: Vec<String> = vec![];
+        for (i, inline) in text.iter().enumerate() {
+            let piece = self.inline(inline);
+            match pieces.last_mut() {
+                Some(last) if !spaced(text.get(i.wrapping_sub(1)), inline) => *last += &piece,
+                _ => pieces.push(piece),
+            }
+        }
+        join(&pieces)
+    }
+
+    fn inline(&self, inline: &Inline) -> String {
+        match inline {
+            Inline::Link(text, url) => {
+                format!("\\href{{{}}}{{{}}}", url_escape(url), self.text(text))
+            }
+            Inline::HyperLink(target) => match WikiLink::parse(target) {
+                Some(link) => escape(link.label.as_ref().unwrap_or(&link.page)),
+                None => format!("\\url{{{}}}", url_escape(target)),
+            },
+            Inline::Image(_, url) => format!(
+                "\\includegraphics[width=\\linewidth]{{{}}}",
+                url_escape(url)
+            ),
+            Inline::Code(code) => format!("\\texttt{{{}}}", escape(code)),
+            Inline::Emphasis(text) => format!("\\emph{{{}}}", self.text(text)),
+            Inline::Strong(text) => format!("\\textbf{{{}}}", self.text(text)),
+            Inline::EmphasisAndStrong(text) => format!("\\textbf{{\\emph{{{}}}}}", self.text(text)),
+            Inline::Deleted(text) => format!("\\sout{{{}}}", self.text(text)),
+            Inline::Plaintext(text) => escape(text),
+            Inline::Newline => String::from("\\\\"),
+            Inline::Comment(_) => String::new(),
+            Inline::MathJax(tex) => format!("${}$", tex),
+            Inline::Emoji(shortcode) => match emojis::get_by_shortcode(shortcode) {
+                Some(emoji) => emoji.to_string(),
+                None => escape(&format!(":{}:", shortcode)),
+            },
+            Inline::Variable(name, _) => escape(&format!("${{{}}}", name)),
+            Inline::Reference(label) => {
+                if let Some(title) = self.unnumbered.borrow().get(label) {
+                    return format!("\\hyperref[{}]{{{}}}", label, title);
+                }
+                let (kind, command) = match label.split(':').next() {
+                    Some("fig") => ("Figure", "ref"),
+                    Some("tbl") => ("Table", "ref"),
+                    Some("lst") => ("Listing", "ref"),
+                    Some("eq") => ("Equation", "eqref"),
+                    _ => ("Section", "ref"),
+                };
+                format!("{}~\\{}{{{}}}", kind, command, label)
+            }
+            Inline::Citation(citations, bracketed) => {
+                if let Some(bibliography) = &self.bibliography {
+                    bibliography.borrow_mut().register(citations);
+                }
+                let command = if *bracketed { "citep" } else { "citet" };
+                if citations.iter().all(|c| c.locator.is_none()) {
+                    let keys: Vec<&str> = citations.iter().map(|c| c.key.as_str()).collect();
+                    format!("\\{}{{{}}}", command, keys.join(","))
+                } else {
+                    citations
+                        .iter()
+                        .map(|c| match &c.locator {
+                            Some(locator) => {
+                                format!("\\{}[{}]{{{}}}", command, escape(locator), c.key)
+                            }
+                            None => format!("\\{}{{{}}}", command, c.key),
+                        })
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                }
+            }
+            Inline::Glossary(term) => escape(term),
+            Inline::Index(text, term) => {
+                let term = match term {
+                    Some(term) => term.to_string(),
+                    None => crate::writer::plain(text),
+                };
+                format!("{}\\index{{{}}}", self.text(text), escape(&term))
+            }
+        }
+    }
+}
+
+/// Code block as `lstlisting` (with the language) or `verbatim`
+fn listing(lang: &Option<String>, code: &str, options: &[String]) -> String {
+    let code = code.trim_end_matches('\n');
+    let mut options = options.to_vec();
+    if let Some(lang) = lang {
+        options.insert(0, format!("language={}", lang));
+    }
+    if options.is_empty() {
+        format!("\\begin{{verbatim}}\n{}\n\\end{{verbatim}}", code)
+    } else {
+        format!(
+            "\\begin{{lstlisting}}[{}]\n{}\n\\end{{lstlisting}}",
+            options.join(", "),
+            code
+        )
+    }
+}
+
+fn label(attributes: &Attributes) -> String {
+    match &attributes.id {
+        Some(id) => format!("\\label{{{}}}", id),
+        None => String::new(),
+    }
+}
+
+/// Special characters of LaTeX
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => out += "\\textbackslash{}",
+            '~' => out += "\\textasciitilde{}",
+            '^' => out += "\\textasciicircum{}",
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// `Knuth, Donald (1984). Title. \emph{Container}. \url{URL}`
+fn reference(entry: &Entry) -> String {
+    let authors = entry
+        .authors
+        .iter()
+        .map(|name| {
+            if name.given.is_empty() {
+                escape(&name.family)
+            } else {
+                escape(&format!("{}, {}", name.family, name.given))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" and ");
+    let mut tex = format!(
+        "{} ({}). {}.",
+        authors,
+        escape(&entry.year_label()),
+        escape(&entry.title)
+    );
+    if let Some(container) = &entry.container {
+        tex += &format!(" \\emph{{{}}}.", escape(container));
+    }
+    if let Some(url) = &entry.url {
+        tex += &format!(" \\url{{{}}}", url_escape(url));
+    }
+    tex.trim_start().to_string()
+}
+
+/// `%` and `#` in `\href`, `\url` and `\includegraphics`
+fn url_escape(url: &str) -> String {
+    url.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('#', "\\#")
+}
+
+#[cfg(test)]
+mod test_latex {
+    use crate::parser;
+    use crate::writer::latex::*;
+
+    fn latex(markdown: &str) -> String {
+        let mkd = parser::markdown(markdown).unwrap();
+        Latex::new(true, None).write(&mkd)
+    }
+
+    #[test]
+    fn test_write() {
+        assert_eq!(
+            latex("# Title\n\n## Intro {#sec:intro}\n\n50% of *a_b* [link](http://x.com/#a)\n"),
+            concat!(
+                "\\title{Title}\n\\maketitle\n\n",
+                "\\section{Intro}\\label{sec:intro}\n\n",
+                "50\\% of \\emph{a\\_b} \\href{http://x.com/\\#a}{link}\n"
+            )
+        );
+        assert_eq!(
+            latex("- a\n- [x] b\n    1. c\n"),
+            concat!(
+                "\\begin{itemize}\n\\item a\n\\item[$\\boxtimes$] b\n",
+                "\\begin{enumerate}\n\\item c\n\\end{enumerate}\n\\end{itemize}\n"
+            )
+        );
+        assert_eq!(
+            latex("|a|b|\n|:-|-:|\n|1|2|\n"),
+            "\\begin{tabular}{lr}\n\\hline\na & b \\\\\n\\hline\n1 & 2 \\\\\n\\hline\n\\end{tabular}\n"
+        );
+        assert_eq!(
+            latex("```rust\nfn main() {}\n```\n\n$$\nE = mc^2\n$$ {#eq:e}\n\n![Arch](a.png){#fig:a}\n"),
+            concat!(
+                "\\begin{lstlisting}[language=rust]\nfn main() {}\n\\end{lstlisting}\n\n",
+                "\\begin{equation}\nE = mc^2\\label{eq:e}\n\\end{equation}\n\n",
+                "\\begin{figure}[htbp]\n\\centering\n\\includegraphics[width=\\linewidth]{a.png}\n",
+                "\\caption{Arch}\\label{fig:a}\n\\end{figure}\n"
+            )
+        );
+        assert_eq!(
+            latex("See @fig:a and $x^2$.\n"),
+            "See Figure~\\ref{fig:a} and $x^2$.\n"
+        );
+    }
+
+    #[test]
+    fn test_headings() {
+        let mkd =
+            parser::markdown("# Title\n\n## Intro {#sec:intro}\n\n# Part\n\nSee @sec:intro.\n")
+                .unwrap();
+        assert_eq!(
+            Latex::new(false, None).write(&mkd),
+            concat!(
+                "\\title{Title}\n\\maketitle\n\n",
+                "\\section*{Intro}\\label{sec:intro}\n\n",
+                "\\section*{Part}\n\n",
+                "See \\hyperref[sec:intro]{Intro}.\n"
+            )
+        );
+        let variables = HashMap::from([
+            (String::from("title"), String::from("A & B")),
+            (String::from("documentclass"), String::from("book")),
+        ]);
+        assert_eq!(
+            Latex::new(true, None).write_with_variables(&mkd, &variables),
+            concat!(
+                "\\title{A \\& B}\n\\maketitle\n\n",
+                "\\chapter{Title}\n\n",
+                "\\section{Intro}\\label{sec:intro}\n\n",
+                "\\chapter{Part}\n\n",
+                "See Section~\\ref{sec:intro}.\n"
+            )
+        );
+        assert_eq!(
+            latex("![a](my figure#1%.png)\n"),
+            "\\includegraphics[width=\\linewidth]{my figure\\#1\\%.png}\n"
+        );
+    }
+
+    #[test]
+    fn test_bibliography() {
+        let entries = crate::bibliography::from_bibtex(
+            "@book{k, author={Knuth, Donald}, title={T}, year={1984}}
+             @book{l, author={A and B and C}, title={U}}",
+        )
+        .unwrap();
+        let mkd = parser::markdown("See @k and [@k; @x].\n").unwrap();
+        let writer = Latex::new(false, Some(Bibliography::new(entries, Style::AuthorYear)));
+        assert_eq!(writer.write(&mkd), "See \\citet{k} and \\citep{k,x}.\n");
+        assert_eq!(
+            writer.finish(),
+            concat!(
+                "\\begin{thebibliography}{1}\n",
+                "\\bibitem[Knuth(1984)]{k} Knuth, Donald (1984). T.\n",
+                "\\end{thebibliography}\n"
+            )
+        );
+        assert_eq!(Latex::new(false, None).finish(), "");
+    }
+}
@@ -0,0 +1,331 @@
+use crate::entity::markdown::spaced;
+use crate::entity::markdown::{Align, Block, Inline, List, ListOrderType, Markdown, Text};
+use crate::template::{self, Context};
+use crate::wikilink::WikiLink;
+use crate::writer::{join, plain, Writer};
+use handlebars::RenderError;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// man page (roff with the `man` macros)
+pub struct Man;
+
+impl Writer for Man {
+    fn write(&self, mkd: &Markdown) -> String {
+        self.write_with_variables(mkd, &HashMap::new())
+    }
+
+    /// `.TH` by the title (`%` or `#`) and `name`, `section`, `date`, `source` and `manual`
+    fn write_with_variables(&self, mkd: &Markdown, variables: &HashMap<String, String>) -> String {
+        let title = mkd
+            .iter()
+            .position(|block| matches!(block, Block::Heading(1, ..)));
+        let mut lines = vec![];
+        if mkd.iter().any(has_table) {
+            // preprocessed by tbl
+            lines.push(String::from("'\\\" t"));
+        }
+        if let Some(i) = title {
+            if let Block::Heading(_, text, _) = &mkd[i] {
+                lines.push(th(&plain(text), variables));
+            }
+        } else if variables.contains_key("name") || variables.contains_key("title") {
+            lines.push(th("", variables));
+        }
+        let mut after_heading = false;
+        for (i, block) in mkd.iter().enumerate() {
+            if Some(i) == title {
+                continue;
+            }
+            let roff = self.block(block, after_heading);
+            if !roff.is_empty() {
+                lines.push(roff);
+                after_heading = matches!(block, Block::Heading(..));
+            }
+        }
+        if lines.is_empty() {
+            String::new()
+        } else {
+            lines.join("\n") + "\n"
+        }
+    }
+
+    fn standalone(&self, context: Context) -> Result<String, RenderError> {
+        template::custom("{{{body}}}", context)
+    }
+}
+
+impl Man {
+    /// `after_heading`: `.SH` and `.SS` start a paragraph themselves
+    fn block(&self, block: &Block, after_heading: bool) -> String {
+        let pp = if after_heading { "" } else { ".PP\n" };
+        match block {
+            Block::Heading(1 | 2, text, _) => {
+                format!(".SH \"{}\"", quoted(&plain(text).to_uppercase()))
+            }
+            Block::Heading(3, text, _) => format!(".SS \"{}\"", quoted(&plain(text))),
+            Block::Heading(_, text, _) => format!(".PP\n\\fB{}\\fP", self.text(text)),
+            Block::ListBlock(list) => self.list(list),
+            Block::Paragraph(text) => format!("{}{}", pp, self.text(text)),
+            Block::Quoted(text) => format!(".RS\n.PP\n{}\n.RE", self.text(text)),
+            Block::Code(_, code) | Block::MathJax(code) => format!(
+                "{}.RS 4\n.nf\n{}\n.fi\n.RE",
+                pp,
+                code.trim_end_matches('\n')
+                    .lines()
+                    .map(escape_line)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            Block::CodeImport(_, path) | Block::Import(path) => format!(".\\\" @({})", path),
+            Block::HorizontalRule => String::from(".sp"),
+            Block::Table(aligns, content, has_header) => self.table(aligns, content, *has_header),
+            Block::HyperLink(url) => format!("{}.UR {}\n.UE", pp, url),
+            Block::Div(_, children) => children
+                .iter()
+                .map(|block| self.block(block, false))
+                .filter(|roff| !roff.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Block::Labeled(block, caption, _) if !caption.is_empty() => format!(
+                "{}\n.PP\n\\fI{}\\fP",
+                self.block(block, after_heading),
+                self.text(caption)
+            ),
+            Block::Labeled(block, _, _) => self.block(block, after_heading),
+            Block::Toc | Block::Abbreviation(..) | Block::Index => String::new(),
+        }
+    }
+
+    /// `.TP` for `- **term**: description` (or with a code), otherwise `.IP`
+    fn list(&self, list: &List) -> String {
+        let mut lines = vec![];
+        for (i, item) in list.items.iter().enumerate() {
+            let (tag, width) = match list.order_type {
+                ListOrderType::Unordered => (String::from("\\(bu"), 2),
+                ListOrderType::OrderedNumbers => (format!("{}.", i + 1), 4),
+                ListOrderType::OrderedAlphabets => {
+                    (format!("{}.", (b'a' + (i % 26) as u8) as char), 4)
+                }
+            };
+            let checkbox = match item.checked {
+                Some(true) => "[x] ",
+                Some(false) => "[ ] ",
+                None => "",
+            };
+            match (list.order_type, term(&item.label)) {
+                (ListOrderType::Unordered, Some((term, description))) => {
+                    lines.push(String::from(".TP"));
+                    lines.push(self.text(&term));
+                    lines.push(self.text(&description));
+                }
+                _ => {
+                    lines.push(format!(".IP {} {}", tag, width));
+                    lines.push(format!("{}{}", checkbox, self.text(&item.label)));
+                }
+            }
+            if let Some(children) = &item.children {
+                lines.push(format!(".RS {}", width));
+                lines.push(self.list(children));
+                lines.push(String::from(".RE"));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// `tbl` table
+    fn table(&self, aligns: &[Align], content: &[Vec<Text>], has_header: bool) -> String {
+        let columns = content.iter().map(|row| row.len()).max().unwrap_or(0);
+        let format: Vec<&str> = (0..columns)
+            .map(|j| match aligns.get(j) {
+                Some(Align::Center) => "c",
+                Some(Align::Right) => "r",
+                _ => "l",
+            })
+            .collect();
+        let mut lines = vec![
+            String::from(".PP"),
+            String::from(".TS"),
+            String::from("allbox;"),
+        ];
+        if has_header {
+            lines.push(vec!["cb"; columns].join(" "));
+        }
+        lines.push(format!("{}.", format.join(" ")));
+        for row in content.iter() {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|cell| self.text(cell).replace(['\t', '\n'], " "))
+                .collect();
+            lines.push(cells.join("\t"));
+        }
+        lines.push(String::from(".TE"));
+        lines.join("\n")
+    }
+
+    fn text(&self, text: &Text) -> String {
+        let mut pieces: Vec<String> = vec![];
+        for (i, inline) in text.iter().enumerate() {
+            let piece = self.inline(inline);
+            match pieces.last_mut() {
+                Some(last) if !spaced(text.get(i.wrapping_sub(1)), inline) => *last += &piece,
+                _ => pieces.push(piece),
+            }
+        }
+        join(&pieces).replace(" \n", "\n").replace("\n ", "\n")
+    }
+
+    fn inline(&self, inline: &Inline) -> String {
+        match inline {
+            Inline::Link(text, url) => {
+                let text = self.text(text);
+                if url.starts_with('#') || text == escape(url) {
+                    text
+                } else {
+                    format!("{} <{}>", text, escape(url))
+                }
+            }
+            Inline::HyperLink(target) => match WikiLink::parse(target) {
+                Some(link) => escape(&link.label.unwrap_or(link.page)),
+                None => escape(target),
+            },
+            Inline::Image(alt, _) => format!("[{}]", escape(alt)),
+            Inline::Code(code) => format!("\\fB{}\\fP", escape(code)),
+            Inline::Emphasis(text) => format!("\\fI{}\\fP", self.text(text)),
+            Inline::Strong(text) => format!("\\fB{}\\fP", self.text(text)),
+            Inline::EmphasisAndStrong(text) => format!("\\f(BI{}\\fP", self.text(text)),
+            Inline::Deleted(text) => self.text(text),
+            Inline::Newline => String::from("\n.br\n"),
+            Inline::Comment(_) => String::new(),
+            Inline::Citation(_, true) => format!("[{}]", escape(&plain(&vec![inline.clone()]))),
+            _ => escape(&plain(&vec![inline.clone()])),
+        }
+    }
+}
+
+/// `.TH NAME SECTION DATE SOURCE MANUAL`; the title can be `NAME(SECTION)`
+fn th(title: &str, variables: &HashMap<String, String>) -> String {
+    lazy_static! {
+        static ref NAME_SECTION: Regex = Regex::new(r"^(\S+)\((\w+)\)").unwrap();
+    }
+    let (name, section) = match NAME_SECTION.captures(title.trim()) {
+        Some(caps) => (caps[1].to_string(), caps[2].to_string()),
+        None => (title.trim().to_string(), String::from("1")),
+    };
+    let get = |key: &str, default: &str| {
+        variables
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| default.to_string())
+    };
+    let name = match variables.get("name") {
+        Some(name) => name.to_string(),
+        None if name.is_empty() => get("title", ""),
+        None => name,
+    };
+    let fields = [
+        name.to_uppercase(),
+        get("section", &section),
+        get("date", ""),
+        get("source", ""),
+        get("manual", ""),
+    ];
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| format!("\"{}\"", quoted(field)))
+        .collect();
+    format!(".TH {}", fields.join(" "))
+}
+
+/// `- **term**: description` into (term, description); the term can be `` `-o`, `--output` ``
+fn term(label: &Text) -> Option<(Text, Text)> {
+    if !matches!(
+        label.first(),
+        Some(Inline::Code(_) | Inline::Strong(_) | Inline::Emphasis(_))
+    ) {
+        return None;
+    }
+    let colon = label
+        .iter()
+        .position(|inline| matches!(inline, Inline::Plaintext(text) if text.starts_with(':')))?;
+    let mut description = vec![];
+    if let Inline::Plaintext(text) = &label[colon] {
+        if text.len() > 1 {
+            description.push(Inline::Plaintext(text[1..].to_string()));
+        }
+    }
+    description.extend(label[colon + 1..].iter().cloned());
+    Some((label[..colon].to_vec(), description))
+}
+
+fn has_table(block: &Block) -> bool {
+    match block {
+        Block::Table(..) => true,
+        Block::Div(_, children) => children.iter().any(has_table),
+        Block::Labeled(block, _, _) => has_table(block),
+        _ => false,
+    }
+}
+
+/// Backslashes and hyphens (as minus signs, for options); `.` and `'` are not requests
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    if text.starts_with(['.', '\'']) {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
+
+/// A line of code blocks; kept as it is except the escapes
+fn escape_line(line: &str) -> String {
+    let line = line.replace('\\', "\\e");
+    if line.starts_with(['.', '\'']) {
+        format!("\\&{}", line)
+    } else {
+        line
+    }
+}
+
+/// In `"..."` of requests
+fn quoted(text: &str) -> String {
+    escape(text).replace('"', "\\(dq")
+}
+
+#[cfg(test)]
+mod test_man {
+    use crate::parser;
+    use crate::writer::man::*;
+
+    #[test]
+    fn test_write() {
+        let mkd = parser::markdown(concat!(
+            "% unidoc(1)\n\n",
+            "## Name\n\nunidoc - unite all markdown\n\n",
+            "## Options\n\n",
+            "- `--output`: the *output* file\n",
+            "- **-s**, **--standalone**: standalone\n\n",
+            "### Example\n\n",
+            "```bash\n.hidden \\n\n```\n\n",
+            "1. one\n",
+            "    - nested\n"
+        ))
+        .unwrap();
+        let variables = HashMap::from([(String::from("date"), String::from("2024-01-01"))]);
+        assert_eq!(
+            Man.write_with_variables(&mkd, &variables),
+            concat!(
+                ".TH \"UNIDOC\" \"1\" \"2024\\-01\\-01\" \"\" \"\"\n",
+                ".SH \"NAME\"\n",
+                "unidoc \\- unite all markdown\n",
+                ".SH \"OPTIONS\"\n",
+                ".TP\n\\fB\\-\\-output\\fP\nthe \\fIoutput\\fP file\n",
+                ".TP\n\\fB\\-s\\fP, \\fB\\-\\-standalone\\fP\nstandalone\n",
+                ".SS \"Example\"\n",
+                ".RS 4\n.nf\n\\&.hidden \\en\n.fi\n.RE\n",
+                ".IP 1. 4\none\n.RS 4\n.IP \\(bu 2\nnested\n.RE\n"
+            )
+        );
+    }
+}
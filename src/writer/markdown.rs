@@ -0,0 +1,437 @@
+use crate::entity::markdown::{
+    spaced, Align, Attributes, Block, Inline, List, ListOrderType, Markdown, Text,
+};
+use crate::parser;
+use crate::template::{self, Context};
+use crate::writer::Writer;
+use handlebars::RenderError;
+use lazy_static::lazy_static;
+use regex::Regex;
+use unicode_width::UnicodeWidthStr;
+
+/// Canonical unidoc markdown (`--to markdown`, `unidoc fmt`)
+pub struct MarkdownWriter;
+
+impl Writer for MarkdownWriter {
+    fn write(&self, mkd: &Markdown) -> String {
+        let blocks: Vec<String> = mkd.iter().map(|block| self.block(block)).collect();
+        if blocks.is_empty() {
+            String::new()
+        } else {
+            blocks.join("\n\n") + "\n"
+        }
+    }
+
+    fn standalone(&self, context: Context) -> Result<String, RenderError> {
+        template::custom("{{{body}}}", context)
+    }
+}
+
+/// The formatted document could not be parsed back to the same markdown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError(String);
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl std::error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+/// Format a markdown source; the front matter is kept as it is
+pub fn format(content: &str) -> Result<String, FormatError> {
+    let (_, body) = parser::front_matter(content);
+    let front_matter = &content[..content.len() - body.len()];
+    let mkd = parser::markdown(body)
+        .map_err(|_| FormatError(String::from("Cannot parse the markdown")))?;
+    let formatted = MarkdownWriter.write(&mkd);
+    match parser::markdown(&formatted) {
+        Ok(reparsed) if reparsed == mkd => Ok(format!("{}{}", front_matter, formatted)),
+        _ => Err(FormatError(String::from(
+            "Cannot be formatted without changing the document",
+        ))),
+    }
+}
+
+impl MarkdownWriter {
+    fn block(&self, block: &Block) -> String {
+        match block {
+            Block::Heading(level, text, attributes) => format!(
+                "{} {}{}",
+                "#".repeat(*level),
+                self.text(text),
+                trailing(attributes)
+            ),
+            Block::ListBlock(list) => self.list(list, 0),
+            Block::Paragraph(text) => self.lines(text, ""),
+            Block::Quoted(text) => self.lines(text, "> "),
+            Block::Code(lang, code) => {
+                format!("```{}\n{}```", lang.as_deref().unwrap_or(""), code)
+            }
+            Block::CodeImport(lang, path) => {
+                format!("@[{}]({})", lang.as_deref().unwrap_or(""), path)
+            }
+            Block::HorizontalRule => String::from("---"),
+            Block::Table(aligns, content, has_header) => self.table(aligns, content, *has_header),
+            Block::Import(path) => format!("@({})", path),
+            Block::HyperLink(url) => format!("{{{{ {} }}}}", url),
+            Block::MathJax(tex) => format!("$${}$$", tex),
+            Block::Div(attributes, children) => {
+                let open = match attributes {
+                    Attributes {
+                        id: None,
+                        classes,
+                        pairs,
+                    } if classes.len() == 1 && pairs.is_empty() => {
+                        format!("::: {}", classes[0])
+                    }
+                    _ => format!("::: {}", self::attributes(attributes)),
+                };
+                let children = self.write(children);
+                if children.is_empty() {
+                    format!("{}\n:::", open)
+                } else {
+                    format!("{}\n\n{}\n:::", open, children)
+                }
+            }
+            Block::Toc => String::from("[TOC]"),
+            Block::Labeled(block, caption, attributes) => match block.as_ref() {
+                Block::Paragraph(text) if matches!(text.as_slice(), [Inline::Image(..)]) => {
+                    format!("{}{}", self.text(text), self::attributes(attributes))
+                }
+                Block::MathJax(_) => format!("{}{}", self.block(block), trailing(attributes)),
+                _ => format!(
+                    "{}\n: {}{}",
+                    self.block(block),
+                    self.text(caption),
+                    trailing(attributes)
+                ),
+            },
+            Block::Abbreviation(term, definition) => format!("*[{}]: {}", term, definition),
+            Block::Index => String::from("[INDEX]"),
+        }
+    }
+
+    /// Lines of a paragraph (or quote) broken at the ends of the sentences and `Newline`s
+    fn lines(&self, text: &Text, prefix: &str) -> String {
+        lazy_static! {
+            // list markers and fences are not continuations of paragraphs
+            static ref BLOCK_START: Regex = Regex::new(r"^([-+]|\d+\.|[a-zA-Z]+\.|:::.*)$").unwrap();
+            // e.g. i.e.
+            static ref ABBREVIATION: Regex = Regex::new(r"^\W*(\pL\.)+$").unwrap();
+        }
+        let pieces: Vec<String> = text.iter().map(|inline| self.inline(inline)).collect();
+        let mut lines = vec![];
+        let mut line = String::new();
+        for (i, (inline, piece)) in text.iter().zip(pieces.iter()).enumerate() {
+            if *inline == Inline::Newline {
+                lines.push(format!("{}{}  ", prefix, line));
+                line.clear();
+                continue;
+            }
+            // a piece of only punctuations, or at the start of the line, ends no sentence
+            let leading = line.is_empty();
+            if !line.is_empty() && spaced(text.get(i.wrapping_sub(1)), inline) {
+                line.push(' ');
+            }
+            line += piece;
+            let sentence_end = matches!(inline, Inline::Plaintext(_))
+                && !leading
+                && piece.chars().any(char::is_alphanumeric)
+                && piece.ends_with(['.', '!', '?', '。', '！', '？'])
+                && !ABBREVIATION.is_match(piece);
+            let next = pieces.get(i + 1);
+            if sentence_end
+                && next.is_some_and(|next| next != "  " && !BLOCK_START.is_match(next))
+                && text.get(i + 1) != Some(&Inline::Newline)
+            {
+                lines.push(format!("{}{}", prefix, line));
+                line.clear();
+            }
+        }
+        if !line.is_empty() {
+            lines.push(format!("{}{}", prefix, line));
+        }
+        lines.join("\n")
+    }
+
+    fn list(&self, list: &List, indent: usize) -> String {
+        let mut lines = vec![];
+        for (i, item) in list.items.iter().enumerate() {
+            let bullet = match list.order_type {
+                ListOrderType::Unordered => String::from("-"),
+                ListOrderType::OrderedNumbers => format!("{}.", i + 1),
+                ListOrderType::OrderedAlphabets => format!("{}.", alphabet(i)),
+            };
+            let checkbox = match item.checked {
+                Some(true) => "[x] ",
+                Some(false) => "[ ] ",
+                None => "",
+            };
+            lines.push(format!(
+                "{}{} {}{}",
+                " ".repeat(indent),
+                bullet,
+                checkbox,
+                self.text(&item.label)
+            ));
+            if let Some(children) = &item.children {
+                lines.push(self.list(children, indent + 4));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Pipe table padded to the column widths
+    fn table(&self, aligns: &[Align], content: &[Vec<Text>], has_header: bool) -> String {
+        let rows: Vec<Vec<String>> = content
+            .iter()
+            .map(|row| row.iter().map(|cell| self.text(cell)).collect())
+            .collect();
+        let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|j| {
+                rows.iter()
+                    .filter_map(|row| row.get(j))
+                    .map(|cell| cell.width())
+                    .max()
+                    .unwrap_or(0)
+                    .max(3)
+            })
+            .collect();
+        let align = |j: usize| aligns.get(j).copied().unwrap_or(Align::Left);
+        let row = |cells: &[String]| {
+            let cells: Vec<String> = cells
+                .iter()
+                .enumerate()
+                .map(|(j, cell)| {
+                    let padding = " ".repeat(widths[j] - cell.width());
+                    match align(j) {
+                        Align::Right if has_header => format!("{}{}", padding, cell),
+                        _ => format!("{}{}", cell, padding),
+                    }
+                })
+                .collect();
+            format!("| {} |", cells.join(" | "))
+        };
+        let mut lines = vec![];
+        for (i, cells) in rows.iter().enumerate() {
+            lines.push(row(cells));
+            if i == 0 && has_header {
+                let rules: Vec<String> = (0..aligns.len().min(columns))
+                    .map(|j| match align(j) {
+                        Align::Left => "-".repeat(widths[j]),
+                        Align::Center => format!(":{}:", "-".repeat(widths[j] - 2)),
+                        Align::Right => format!("{}:", "-".repeat(widths[j] - 1)),
+                    })
+                    .collect();
+                lines.push(format!("| {} |", rules.join(" | ")));
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn text(&self, text: &Text) -> String {
+        let mut out = String::new();
+        for (i, inline) in text.iter().enumerate() {
+            match inline {
+                Inline::Newline => out += "  ",
+                _ => {
+                    if !out.is_empty() && spaced(text.get(i.wrapping_sub(1)), inline) {
+                        out.push(' ');
+                    }
+                    out += &self.inline(inline);
+                }
+            }
+        }
+        out
+    }
+
+    fn inline(&self, inline: &Inline) -> String {
+        match inline {
+            Inline::Link(text, url) => format!("[{}]({})", self.text(text), url),
+            Inline::HyperLink(url) => format!("[[{}]]", url),
+            Inline::Image(alt, url) => format!("![{}]({})", alt, url),
+            Inline::Code(code) => format!("`{}`", code),
+            Inline::Emphasis(text) => {
+                let inner = self.text(text);
+                if inner.contains('*') && !inner.contains('_') {
+                    format!("_{}_", inner)
+                } else {
+                    format!("*{}*", inner)
+                }
+            }
+            Inline::Strong(text) => {
+                let inner = self.text(text);
+                if inner.contains("**") && !inner.contains("__") {
+                    format!("__{}__", inner)
+                } else {
+                    format!("**{}**", inner)
+                }
+            }
+            Inline::EmphasisAndStrong(text) => format!("***{}***", self.text(text)),
+            Inline::Deleted(text) => format!("~~{}~~", self.text(text)),
+            Inline::Plaintext(text) => escape(text),
+            Inline::Newline => String::from("  "),
+            Inline::Comment(text) => format!("<!--{}-->", text),
+            Inline::MathJax(tex) => format!("${}$", tex),
+            Inline::Emoji(shortcode) => format!(":{}:", shortcode),
+            Inline::Variable(name, None) => format!("${{{}}}", name),
+            Inline::Variable(name, Some(default)) => format!("${{{}:-{}}}", name, default),
+            Inline::Reference(label) => format!("@{}", label),
+            Inline::Citation(citations, bracketed) => {
+                let items: Vec<String> = citations
+                    .iter()
+                    .map(|c| match &c.locator {
+                        Some(locator) => format!("@{}, {}", c.key, locator),
+                        None => format!("@{}", c.key),
+                    })
+                    .collect();
+                if *bracketed {
+                    format!("[{}]", items.join("; "))
+                } else {
+                    items.join("; ")
+                }
+            }
+            Inline::Glossary(term) => format!("{{{}}}", term),
+            Inline::Index(text, term) if text.is_empty() => {
+                format!("{{.index term={}}}", quote(term.as_deref().unwrap_or("")))
+            }
+            Inline::Index(text, None) => format!("[{}]{{.idx}}", self.text(text)),
+            Inline::Index(text, Some(term)) => {
+                format!("[{}]{{.idx term={}}}", self.text(text), quote(term))
+            }
+        }
+    }
+}
+
+/// Escape the characters which are not plaintexts
+fn escape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let escaped = match c {
+            ' ' | '$' | '*' | '[' | '\\' | ']' | '`' | '|' => true,
+            '~' => chars.get(i + 1) == Some(&'~'),
+            '<' => chars[i..].starts_with(&['<', '!', '-', '-']),
+            // `:shortcode:`
+            ':' => i == 0 && chars[1..].contains(&':'),
+            _ => false,
+        };
+        if escaped {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// ` {#id .class key=value}` after a heading or a caption
+fn trailing(attributes: &Attributes) -> String {
+    if *attributes == Attributes::default() {
+        String::new()
+    } else {
+        format!(" {}", self::attributes(attributes))
+    }
+}
+
+fn attributes(attributes: &Attributes) -> String {
+    let mut items = vec![];
+    if let Some(id) = &attributes.id {
+        items.push(format!("#{}", id));
+    }
+    for class in attributes.classes.iter() {
+        items.push(format!(".{}", class));
+    }
+    for (key, value) in attributes.pairs.iter() {
+        items.push(format!("{}={}", key, quote(value)));
+    }
+    format!("{{{}}}", items.join(" "))
+}
+
+/// Attribute value; quoted unless it is a single word
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains([' ', '\t', '}', '"', '\'']) {
+        value.to_string()
+    } else if value.contains('"') {
+        format!("'{}'", value)
+    } else {
+        format!("\"{}\"", value)
+    }
+}
+
+/// a, b, ..., z, aa, ab, ...
+fn alphabet(i: usize) -> String {
+    let c = (b'a' + (i % 26) as u8) as char;
+    if i < 26 {
+        c.to_string()
+    } else {
+        format!("{}{}", alphabet(i / 26 - 1), c)
+    }
+}
+
+#[cfg(test)]
+mod test_markdown {
+    use crate::writer::markdown::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            format("Title\n=====\n").is_err(),
+            parser::markdown("Title\n=====\n").is_err()
+        );
+        assert_eq!(
+            format("---\ntitle: x\n---\n#  Hello   *world*\n* a\n* [X] b\n   + c\n").unwrap(),
+            "---\ntitle: x\n---\n# Hello *world*\n\n- a\n- [x] b\n    - c\n"
+        );
+        assert_eq!(
+            format("First sentence. Second\nline  \nbreak\n").unwrap(),
+            "First sentence.\nSecond line  \nbreak\n"
+        );
+        assert_eq!(
+            format(". leading dot line\n\nSee x . and more. Done ! End.\n").unwrap(),
+            ". leading dot line\n\nSee x . and more.\nDone ! End.\n"
+        );
+        assert_eq!(
+            format("|a|long header|\n|:-:|--:|\n|1|2|\n").unwrap(),
+            concat!(
+                "| a   | long header |\n",
+                "| :-: | ----------: |\n",
+                "| 1   |           2 |\n"
+            )
+        );
+        assert_eq!(
+            format("\\*not emphasis\\* and \\[x\\] \\$1\n").unwrap(),
+            "\\*not emphasis\\* and \\[x\\] \\$1\n"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let source = concat!(
+            "% Page Title\n",
+            "## Section {#sec:a .unnumbered}\n",
+            "Text with **strong**, *em*, ***both***, ~~del~~, `code`, $x^2$, :joy:,\n",
+            "[link](http://example.com), [[http://example.com]], [[Other Page|label]], ![img](a.png),\n",
+            "${name:-default} @fig:a [@knuth, p. 3; @lamport] @knuth {HTML} <!-- comment -->\n",
+            "[nom]{.idx term=\"parser!nom\"} {.index term=\"crate\"} 日本語。\n",
+            "1. one\n2. two\n    a. nested\n\n",
+            "> quoted\n> lines\n\n",
+            "```python {exec}\nprint(1)\n```\n: Listing {#lst:a}\n\n",
+            "@[rust](src/main.rs)\n@(another.md)\n{{ https://example.com }}\n",
+            "$$\nE = mc^2\n$$ {#eq:e}\n\n",
+            "![Figure](a.png){#fig:a}\n",
+            "::: {if=\"lang == ja\"}\n::: note\nInside\n:::\n:::\n",
+            "[TOC]\n[INDEX]\n---\n*[HTML]: HyperText Markup Language\n",
+            "|a|b|\n|1||\n",
+        );
+        let mkd = parser::markdown(source).unwrap();
+        let formatted = MarkdownWriter.write(&mkd);
+        assert_eq!(parser::markdown(&formatted), Ok(mkd));
+        assert_eq!(format(&formatted), Ok(formatted));
+    }
+}
@@ -0,0 +1,183 @@
+pub mod docx;
+pub mod epub;
+pub mod gemtext;
+pub mod ipynb;
+pub mod json;
+pub mod latex;
+pub mod man;
+pub mod markdown;
+pub mod package;
+pub mod plain;
+pub mod xhtml;
+
+use crate::bibliography::{self, Bibliography};
+use crate::entity::markdown::{spaced, Inline, Markdown, Text};
+use crate::template::Context;
+use handlebars::RenderError;
+use std::collections::HashMap;
+
+/// Output format (`--to`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    Docx,
+    Epub,
+    Gemtext,
+    #[default]
+    Html,
+    Ipynb,
+    Json,
+    Latex,
+    Man,
+    Markdown,
+    Plain,
+    Slides,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "docx" => Ok(Format::Docx),
+            "epub" => Ok(Format::Epub),
+            "gemtext" => Ok(Format::Gemtext),
+            "html" => Ok(Format::Html),
+            "ipynb" => Ok(Format::Ipynb),
+            "json" => Ok(Format::Json),
+            "latex" => Ok(Format::Latex),
+            "man" => Ok(Format::Man),
+            "markdown" => Ok(Format::Markdown),
+            "plain" => Ok(Format::Plain),
+            "slides" => Ok(Format::Slides),
+            _ => Err(format!("Unknown format: {}", s)),
+        }
+    }
+}
+
+/// Backend of the formats other than HTML (which is by `Translator`)
+pub trait Writer {
+    /// Body of the document
+    fn write(&self, mkd: &Markdown) -> String;
+    /// Body with the variables (the front matter and `-V`), e.g. for the header of man pages
+    fn write_with_variables(&self, mkd: &Markdown, _variables: &HashMap<String, String>) -> String {
+        self.write(mkd)
+    }
+    /// Last part after the bodies of all the inputs, e.g. the bibliography of LaTeX
+    fn finish(&self) -> String {
+        String::new()
+    }
+    /// Whole document with the body (`--standalone`)
+    fn standalone(&self, context: Context) -> Result<String, RenderError>;
+}
+
+/// Options for the writers
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// `--number-sections`
+    pub number_sections: bool,
+    /// `--columns`; line width of the plain text
+    pub columns: usize,
+    /// `--ascii`; ASCII tables instead of box-drawing characters (plain text and gemtext)
+    pub ascii: bool,
+    /// `--bibliography` (LaTeX)
+    pub bibliography: Vec<bibliography::Entry>,
+    /// `--citation-style`
+    pub citation_style: bibliography::Style,
+}
+
+/// Writer of the format; `None` for HTML (and slides), JSON, EPUB, DOCX and notebooks
+pub fn new(format: Format, options: &Options) -> Option<Box<dyn Writer>> {
+    match format {
+        Format::Docx
+        | Format::Epub
+        | Format::Html
+        | Format::Ipynb
+        | Format::Json
+        | Format::Slides => None,
+        Format::Gemtext => Some(Box::new(gemtext::Gemtext::new(options.ascii))),
+        Format::Latex => {
+            let bibliography = (!options.bibliography.is_empty())
+                .then(|| Bibliography::new(options.bibliography.clone(), options.citation_style));
+            Some(Box::new(latex::Latex::new(
+                options.number_sections,
+                bibliography,
+            )))
+        }
+        Format::Man => Some(Box::new(man::Man)),
+        Format::Markdown => Some(Box::new(markdown::MarkdownWriter)),
+        Format::Plain => Some(Box::new(plain::Plain::new(options.columns, options.ascii))),
+    }
+}
+
+/// Join inline pieces with spaces, skipping the empty ones
+pub fn join(pieces: &[String]) -> String {
+    let mut out = String::new();
+    for piece in pieces.iter().filter(|piece| !piece.is_empty()) {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out += piece;
+    }
+    out
+}
+
+/// Text without any markups, e.g. for titles
+pub fn plain(text: &Text) -> String {
+    let mut pieces: Vec<String> = vec![];
+    for (i, inline) in text.iter().enumerate() {
+        let piece = match inline {
+            Inline::Link(text, _)
+            | Inline::Emphasis(text)
+            | Inline::Strong(text)
+            | Inline::EmphasisAndStrong(text)
+            | Inline::Deleted(text)
+            | Inline::Index(text, _) => plain(text),
+            Inline::HyperLink(url) => url.to_string(),
+            Inline::Image(alt, _) => alt.to_string(),
+            Inline::Code(code) => code.to_string(),
+            Inline::Plaintext(text) => text.to_string(),
+            Inline::Newline | Inline::Comment(_) => String::new(),
+            Inline::MathJax(tex) => tex.to_string(),
+            Inline::Emoji(shortcode) => match emojis::get_by_shortcode(shortcode) {
+                Some(emoji) => emoji.to_string(),
+                None => format!(":{}:", shortcode),
+            },
+            Inline::Variable(name, _) => format!("${{{}}}", name),
+            Inline::Reference(label) => format!("@{}", label),
+            Inline::Citation(citations, _) => citations
+                .iter()
+                .map(|c| format!("@{}", c.key))
+                .collect::<Vec<_>>()
+                .join("; "),
+            Inline::Glossary(term) => term.to_string(),
+        };
+        match pieces.last_mut() {
+            Some(last) if !spaced(text.get(i.wrapping_sub(1)), inline) => *last += &piece,
+            _ => pieces.push(piece),
+        }
+    }
+    join(&pieces)
+}
+
+#[cfg(test)]
+mod test_writer {
+    use crate::parser;
+    use crate::writer::*;
+
+    #[test]
+    fn test_plain() {
+        let mkd = parser::markdown("# Hello **world** `x` 日本 語\n").unwrap();
+        match &mkd[0] {
+            crate::entity::markdown::Block::Heading(_, text, _) => {
+                assert_eq!(plain(text), "Hello world x 日本 語")
+            }
+            _ => unreachable!(),
+        }
+        let mkd = parser::markdown("# See @fig:a, and *b*.\n").unwrap();
+        match &mkd[0] {
+            crate::entity::markdown::Block::Heading(_, text, _) => {
+                assert_eq!(plain(text), "See @fig:a, and b.")
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::entity::markdown::{try_map_text, Block, Inline, Markdown};
+use crate::slug::{Ids, Slug};
+use crate::writer::plain;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::path::Path;
+
+/// Relative image paths of a document in `dir` are rebased to the current directory
+pub fn rebase(mkd: &Markdown, dir: Option<&str>) -> Markdown {
+    let dir = match dir {
+        Some(dir) if !dir.is_empty() => dir,
+        _ => return mkd.clone(),
+    };
+    fn rebase_text(text: &[Inline], dir: &str) -> Vec<Inline> {
+        text.iter()
+            .map(|inline| match inline {
+                Inline::Image(alt, url) if is_local(url) && !url.starts_with('/') => {
+                    let url = Path::new(dir).join(url).to_string_lossy().to_string();
+                    Inline::Image(alt.to_string(), url)
+                }
+                Inline::Link(text, url) => Inline::Link(rebase_text(text, dir), url.to_string()),
+                _ => inline.clone(),
+            })
+            .collect()
+    }
+    try_map_text::<(), _>(mkd, &mut |text| Ok(rebase_text(text, dir))).unwrap()
+}
+
+/// Not a remote URL nor a data URL
+fn is_local(url: &str) -> bool {
+    !url.contains("://") && !url.starts_with("//") && !url.starts_with("data:")
+}
+
+/// Every heading has an id
+pub fn identify(mkd: &Markdown) -> Markdown {
+    fn reserve(mkd: &Markdown, ids: &mut Ids) {
+        walk(mkd, &mut |block| match block {
+            Block::Heading(_, _, attributes)
+            | Block::Labeled(_, _, attributes)
+            | Block::Div(attributes, _) => {
+                if let Some(id) = &attributes.id {
+                    ids.reserve(id);
+                }
+            }
+            _ => {}
+        });
+    }
+    fn assign(mkd: &Markdown, ids: &mut Ids) -> Markdown {
+        mkd.iter()
+            .map(|block| match block {
+                Block::Heading(level, text, attributes) if attributes.id.is_none() => {
+                    let title = plain(text);
+                    let mut attributes = attributes.clone();
+                    attributes.id = Some(ids.unique(&Slug::Ascii.make(*level, &title, &title)));
+                    Block::Heading(*level, text.clone(), attributes)
+                }
+                Block::Div(attributes, children) => {
+                    Block::Div(attributes.clone(), assign(children, ids))
+                }
+                _ => block.clone(),
+            })
+            .collect()
+    }
+    let mut ids = Ids::default();
+    reserve(mkd, &mut ids);
+    assign(mkd, &mut ids)
+}
+
+/// Every block in the document order (including the children of divs)
+pub fn walk<F: FnMut(&Block)>(mkd: &Markdown, f: &mut F) {
+    for block in mkd.iter() {
+        f(block);
+        match block {
+            Block::Div(_, children) => walk(children, f),
+            Block::Labeled(inner, _, _) => f(inner),
+            _ => {}
+        }
+    }
+}
+
+/// URLs of the local images
+pub fn images(mkd: &Markdown) -> Vec<String> {
+    let mut urls = vec![];
+    try_map_text::<(), _>(mkd, &mut |text| {
+        fn collect(text: &[Inline], urls: &mut Vec<String>) {
+            for inline in text.iter() {
+                match inline {
+                    Inline::Image(_, url) if is_local(url) => urls.push(url.to_string()),
+                    Inline::Link(text, _)
+                    | Inline::Emphasis(text)
+                    | Inline::Strong(text)
+                    | Inline::EmphasisAndStrong(text)
+                    | Inline::Deleted(text) => collect(text, urls),
+                    _ => {}
+                }
+            }
+        }
+        collect(text, &mut urls);
+        Ok(text.clone())
+    })
+    .unwrap();
+    urls
+}
+
+/// `dcterms:modified` by `SOURCE_DATE_EPOCH`, the `date` of the front matter
+/// (`YYYY-MM-DD` or `YYYY-MM-DDThh:mm:ss`, in UTC), or the current time
+pub fn modified(source_date_epoch: Option<String>, date: Option<&str>) -> String {
+    lazy_static! {
+        static ref DATE: Regex =
+            Regex::new(r"^(\d{4})-(\d{2})-(\d{2})(?:[T ](\d{2}):(\d{2})(?::(\d{2}))?)?").unwrap();
+    }
+    if let Some(secs) = source_date_epoch.and_then(|secs| secs.trim().parse().ok()) {
+        return timestamp(secs);
+    }
+    if let Some(caps) = date.and_then(|date| DATE.captures(date.trim())) {
+        let part = |i: usize| caps.get(i).map_or("00", |m| m.as_str());
+        return format!(
+            "{}-{}-{}T{}:{}:{}Z",
+            part(1),
+            part(2),
+            part(3),
+            part(4),
+            part(5),
+            part(6)
+        );
+    }
+    timestamp(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    )
+}
+
+/// `YYYY-MM-DDThh:mm:ssZ` of the UNIX time
+pub fn timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    // days to the civil date (proleptic Gregorian)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod test_package {
+    use crate::parser;
+    use crate::writer::package::*;
+
+    #[test]
+    fn test_rebase() {
+        let mkd = parser::markdown("![a](img/a.png) [![b](b.png)](b.html) ![c](/c.png)\n").unwrap();
+        assert_eq!(
+            images(&rebase(&mkd, Some("docs"))),
+            vec!["docs/img/a.png", "docs/b.png", "/c.png"]
+        );
+        assert_eq!(rebase(&mkd, None), mkd);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(timestamp(1709251199), "2024-02-29T23:59:59Z");
+        assert_eq!(
+            modified(Some(String::from("0")), Some("2024-01-02")),
+            "1970-01-01T00:00:00Z"
+        );
+        assert_eq!(modified(None, Some("2024-01-02")), "2024-01-02T00:00:00Z");
+        assert_eq!(
+            modified(Some(String::from("x")), Some("2024-01-02 03:04")),
+            "2024-01-02T03:04:00Z"
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use crate::crossref::Kind;
+use crate::entity::markdown::spaced;
+use crate::entity::markdown::{Align, Block, Inline, List, ListOrderType, Markdown, Text};
+use crate::template::{self, Context};
+use crate::translator::is_cjk;
+use crate::wikilink::WikiLink;
+use crate::writer::{join, plain, Writer};
+use handlebars::RenderError;
+use std::cell::RefCell;
+use unicode_width::UnicodeWidthStr;
+
+/// Plain text wrapped at the columns; URLs of the links are listed at the end
+pub struct Plain {
+    width: usize,
+    ascii: bool,
+    /// URLs referred as `[n]`
+    links: RefCell<Vec<String>>,
+    /// (level, title) for `[TOC]`
+    headings: RefCell<Vec<(usize, String)>>,
+}
+
+impl Writer for Plain {
+    fn write(&self, mkd: &Markdown) -> String {
+        self.links.borrow_mut().clear();
+        *self.headings.borrow_mut() = headings(mkd);
+        let mut out = self.blocks(mkd, self.width).join("\n");
+        let links = self.links.borrow();
+        if !links.is_empty() {
+            out += "\n\n";
+            for (i, url) in links.iter().enumerate() {
+                out += &format!("[{}] {}\n", i + 1, url);
+            }
+        } else if !out.is_empty() {
+            out += "\n";
+        }
+        out
+    }
+
+    fn standalone(&self, context: Context) -> Result<String, RenderError> {
+        template::custom("{{{body}}}", context)
+    }
+}
+
+impl Plain {
+    pub fn new(width: usize, ascii: bool) -> Self {
+        Self {
+            width: width.max(1),
+            ascii,
+            links: RefCell::new(vec![]),
+            headings: RefCell::new(vec![]),
+        }
+    }
+
+    /// Lines of the blocks separated by empty lines
+    fn blocks(&self, mkd: &Markdown, width: usize) -> Vec<String> {
+        let mut lines: Vec<String> = vec![];
+        for block in mkd.iter() {
+            let block = self.block(block, width);
+            if block.is_empty() {
+                continue;
+            }
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.extend(block);
+        }
+        lines
+    }
+
+    fn block(&self, block: &Block, width: usize) -> Vec<String> {
+        match block {
+            Block::Heading(level, text, _) => {
+                let title = self.text(text).replace('\n', " ");
+                match level {
+                    1 => vec![title.to_string(), "=".repeat(title.width())],
+                    2 => vec![title.to_string(), "-".repeat(title.width())],
+                    _ => vec![title],
+                }
+            }
+            Block::ListBlock(list) => self.list(list, width),
+            Block::Paragraph(text) => wrap(&self.text(text), width),
+            Block::Quoted(text) => wrap(&self.text(text), width.saturating_sub(2))
+                .into_iter()
+                .map(|line| format!("> {}", line))
+                .collect(),
+            Block::Code(_, code) => indent(code.trim_end_matches('\n')),
+            Block::CodeImport(_, path) | Block::Import(path) => vec![format!("[{}]", path)],
+            Block::HorizontalRule => vec!["-".repeat(width)],
+            Block::Table(aligns, content, has_header) => self.table(aligns, content, *has_header),
+            Block::HyperLink(url) => vec![url.to_string()],
+            Block::MathJax(tex) => indent(tex.trim()),
+            Block::Div(_, children) => self.blocks(children, width),
+            Block::Toc => self
+                .headings
+                .borrow()
+                .iter()
+                .filter(|(level, _)| *level > 1)
+                .map(|(level, title)| format!("{}- {}", "  ".repeat(level - 2), title))
+                .collect(),
+            Block::Labeled(block, caption, _) => {
+                let kind = match block.as_ref() {
+                    Block::Table(..) => Kind::Table,
+                    Block::Code(..) | Block::CodeImport(..) => Kind::Listing,
+                    Block::MathJax(..) => Kind::Equation,
+                    _ => Kind::Figure,
+                };
+                let mut lines = self.block(block, width);
+                if !caption.is_empty() {
+                    let caption = format!("{}: {}", kind.name(), self.text(caption));
+                    lines.extend(wrap(&caption, width));
+                }
+                lines
+            }
+            Block::Abbreviation(..) | Block::Index => vec![],
+        }
+    }
+
+    /// Items with the bullets (or numbers); the continuation lines are indented
+    fn list(&self, list: &List, width: usize) -> Vec<String> {
+        let mut lines = vec![];
+        for (i, item) in list.items.iter().enumerate() {
+            let bullet = match list.order_type {
+                ListOrderType::Unordered => String::from("- "),
+                ListOrderType::OrderedNumbers => format!("{}. ", i + 1),
+                ListOrderType::OrderedAlphabets => format!("{}. ", (b'a' + (i % 26) as u8) as char),
+            };
+            let checkbox = match item.checked {
+                Some(true) => "[x] ",
+                Some(false) => "[ ] ",
+                None => "",
+            };
+            let hanging = " ".repeat(bullet.width());
+            let label = format!("{}{}", checkbox, self.text(&item.label));
+            for (j, line) in wrap(&label, width.saturating_sub(bullet.width()))
+                .into_iter()
+                .enumerate()
+            {
+                let prefix = if j == 0 { &bullet } else { &hanging };
+                lines.push(format!("{}{}", prefix, line));
+            }
+            if let Some(children) = &item.children {
+                for line in self.list(children, width.saturating_sub(hanging.len())) {
+                    lines.push(format!("{}{}", hanging, line));
+                }
+            }
+        }
+        lines
+    }
+
+    fn table(&self, aligns: &[Align], content: &[Vec<Text>], has_header: bool) -> Vec<String> {
+        let rows: Vec<Vec<String>> = content
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| self.text(cell).replace('\n', " "))
+                    .collect()
+            })
+            .collect();
+        grid(aligns, &rows, has_header, self.ascii)
+    }
+
+    fn text(&self, text: &Text) -> String {
+        let mut pieces: Vec<String> = vec![];
+        for (i, inline) in text.iter().enumerate() {
+            let piece = self.inline(inline);
+            match pieces.last_mut() {
+                Some(last) if !spaced(text.get(i.wrapping_sub(1)), inline) => *last += &piece,
+                _ => pieces.push(piece),
+            }
+        }
+        join(&pieces)
+    }
+
+    fn inline(&self, inline: &Inline) -> String {
+        match inline {
+            Inline::Link(text, url) => {
+                let text = self.text(text);
+                if text == *url || url.starts_with('#') {
+                    text
+                } else {
+                    format!("{} [{}]", text, self.link(url))
+                }
+            }
+            Inline::HyperLink(target) => match WikiLink::parse(target) {
+                Some(link) => link.label.unwrap_or(link.page),
+                None => target.to_string(),
+            },
+            Inline::Image(alt, url) => format!("[{}] [{}]", alt, self.link(url)),
+            Inline::Code(code) => code.to_string(),
+            Inline::Emphasis(text)
+            | Inline::Strong(text)
+            | Inline::EmphasisAndStrong(text)
+            | Inline::Deleted(text)
+            | Inline::Index(text, _) => self.text(text),
+            Inline::Newline => String::from("\n"),
+            Inline::Comment(_) => String::new(),
+            Inline::Citation(_, true) => format!("[{}]", plain(&vec![inline.clone()])),
+            _ => plain(&vec![inline.clone()]),
+        }
+    }
+
+    /// Number of the URL in the references
+    fn link(&self, url: &str) -> usize {
+        let mut links = self.links.borrow_mut();
+        match links.iter().position(|u| u == url) {
+            Some(i) => i + 1,
+            None => {
+                links.push(url.to_string());
+                links.len()
+            }
+        }
+    }
+}
+
+/// Table with box-drawing (or ASCII) characters; cells are aligned
+pub fn grid(aligns: &[Align], rows: &[Vec<String>], has_header: bool, ascii: bool) -> Vec<String> {
+    // vertical, horizontal, header rule, and corners/junctions of top, middle, bottom
+    let (v, h, hh, top, mid, bottom) = if ascii {
+        (
+            '|',
+            '-',
+            '=',
+            ['+', '+', '+'],
+            ['+', '+', '+'],
+            ['+', '+', '+'],
+        )
+    } else {
+        (
+            '│',
+            '─',
+            '─',
+            ['┌', '┬', '┐'],
+            ['├', '┼', '┤'],
+            ['└', '┴', '┘'],
+        )
+    };
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|j| {
+            rows.iter()
+                .filter_map(|row| row.get(j))
+                .map(|cell| cell.width())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let rule = |fill: char, [left, junction, right]: [char; 3]| {
+        let cells: Vec<String> = widths
+            .iter()
+            .map(|w| fill.to_string().repeat(w + 2))
+            .collect();
+        format!("{}{}{}", left, cells.join(&junction.to_string()), right)
+    };
+    let row = |cells: &Vec<String>| {
+        let cells: Vec<String> = (0..columns)
+            .map(|j| {
+                let cell = cells.get(j).map(|c| c.as_str()).unwrap_or("");
+                let space = widths[j] - cell.width();
+                let left = match aligns.get(j) {
+                    Some(Align::Right) => space,
+                    Some(Align::Center) => space / 2,
+                    _ => 0,
+                };
+                format!(" {}{}{} ", " ".repeat(left), cell, " ".repeat(space - left))
+            })
+            .collect();
+        format!("{}{}{}", v, cells.join(&v.to_string()), v)
+    };
+    let mut lines = vec![rule(h, top)];
+    for (i, cells) in rows.iter().enumerate() {
+        lines.push(row(cells));
+        if i == 0 && has_header && rows.len() > 1 {
+            lines.push(rule(hh, mid));
+        }
+    }
+    lines.push(rule(h, bottom));
+    lines
+}
+
+fn headings(mkd: &Markdown) -> Vec<(usize, String)> {
+    let mut headings = vec![];
+    for block in mkd.iter() {
+        match block {
+            Block::Heading(level, text, _) => headings.push((*level, plain(text))),
+            Block::Div(_, children) => headings.extend(self::headings(children)),
+            _ => {}
+        }
+    }
+    headings
+}
+
+fn indent(text: &str) -> Vec<String> {
+    text.lines().map(|line| format!("    {}", line)).collect()
+}
+
+/// Words (and CJK characters) separated by spaces or not
+fn words(text: &str) -> Vec<(String, bool)> {
+    let mut words: Vec<(String, bool)> = vec![];
+    let mut word = String::new();
+    let mut spaced = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !word.is_empty() {
+                words.push((word.to_string(), spaced));
+                word.clear();
+            }
+            spaced = true;
+        } else if is_cjk(c) {
+            if !word.is_empty() {
+                words.push((word.to_string(), spaced));
+                word.clear();
+                spaced = false;
+            }
+            words.push((c.to_string(), spaced));
+            spaced = false;
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        words.push((word, spaced));
+    }
+    words
+}
+
+/// Wrap a text at the width (by the display widths); `\n` is a line break
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    for segment in text.split('\n') {
+        let mut line = String::new();
+        for (word, spaced) in words(segment) {
+            let separator = if spaced && !line.is_empty() { " " } else { "" };
+            if !line.is_empty() && line.width() + separator.len() + word.width() > width {
+                lines.push(line);
+                line = word;
+            } else {
+                line += separator;
+                line += &word;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test_plain {
+    use crate::parser;
+    use crate::writer::plain::*;
+
+    fn plain(markdown: &str, width: usize, ascii: bool) -> String {
+        let mkd = parser::markdown(markdown).unwrap();
+        Plain::new(width, ascii).write(&mkd)
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(
+            wrap("The quick brown fox jumps over the lazy dog", 16),
+            vec!["The quick brown", "fox jumps over", "the lazy dog"]
+        );
+        assert_eq!(
+            wrap("日本語の文章は単語の区切りがない", 10),
+            vec!["日本語の文", "章は単語の", "区切りがな", "い"]
+        );
+        assert_eq!(wrap("Rust で書く", 6), vec!["Rust", "で書く"]);
+    }
+
+    #[test]
+    fn test_write() {
+        assert_eq!(
+            plain(
+                "# Release 1.0\n\nSee [the docs](https://example.com/docs) and [[http://a.com]].\n",
+                72,
+                false
+            ),
+            concat!(
+                "Release 1.0\n===========\n\n",
+                "See the docs [1] and http://a.com.\n\n",
+                "[1] https://example.com/docs\n"
+            )
+        );
+        assert_eq!(
+            plain(
+                "- first item\n    1. nested\n- [x] done\n\n```\nfn main() {}\n```\n",
+                72,
+                false
+            ),
+            "- first item\n  1. nested\n- [x] done\n\n    fn main() {}\n"
+        );
+        assert_eq!(
+            plain("|a|b|\n|:-|-:|\n|long|1|\n", 72, false),
+            concat!(
+                "┌──────┬───┐\n",
+                "│ a    │ b │\n",
+                "├──────┼───┤\n",
+                "│ long │ 1 │\n",
+                "└──────┴───┘\n"
+            )
+        );
+        assert_eq!(
+            plain("|a|b|\n|:-:|-|\n|long|1|\n", 72, true),
+            concat!(
+                "+------+---+\n",
+                "|  a   | b |\n",
+                "+======+===+\n",
+                "| long | 1 |\n",
+                "+------+---+\n"
+            )
+        );
+    }
+}
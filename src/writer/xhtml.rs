@@ -0,0 +1,140 @@
+use crate::crossref::Kind;
+use crate::entity::markdown::Block;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Elements without the end tags, which are self-closed in XHTML
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// HTML of the `Translator` (and the raw HTML in it) as polyglot XHTML for the chapters of an EPUB;
+/// attribute values are quoted, void elements are self-closed, and the entities are of XML
+pub fn polyglot(html: &str) -> String {
+    lazy_static! {
+        static ref COMMENT: Regex = Regex::new(r"^<!--(?s:.*?)-->").unwrap();
+        static ref END_TAG: Regex = Regex::new(r"^</[A-Za-z][\w:-]*\s*>").unwrap();
+        static ref START_TAG: Regex = Regex::new(
+            r#"^<([A-Za-z][\w:-]*)((?:\s+[^\s"'=<>/]+(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'=<>`]+))?)*)\s*/?>"#
+        )
+        .unwrap();
+        static ref ATTRIBUTE: Regex =
+            Regex::new(r#"([^\s"'=<>/]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+)))?"#)
+                .unwrap();
+    }
+    let mut out = String::new();
+    let mut i = 0;
+    while let Some(c) = html[i..].chars().next() {
+        let rest = &html[i..];
+        if c == '<' {
+            if let Some(m) = COMMENT.find(rest).or_else(|| END_TAG.find(rest)) {
+                out += m.as_str();
+                i += m.end();
+                continue;
+            }
+            if let Some(caps) = START_TAG.captures(rest) {
+                let name = &caps[1];
+                out += &format!("<{}", name);
+                for attribute in ATTRIBUTE.captures_iter(&caps[2]) {
+                    let value = attribute
+                        .get(2)
+                        .or_else(|| attribute.get(3))
+                        .or_else(|| attribute.get(4))
+                        .map_or(&attribute[1], |value| value.as_str());
+                    let value = entities(value).replace('"', "&quot;");
+                    out += &format!(" {}=\"{}\"", &attribute[1], value);
+                }
+                out += if VOID.contains(&name.to_lowercase().as_str()) {
+                    " />"
+                } else {
+                    ">"
+                };
+                i += caps[0].len();
+                continue;
+            }
+            out += "&lt;";
+            i += 1;
+        } else {
+            let end = rest[1..].find(['<']).map_or(rest.len(), |j| j + 1);
+            out += &entities(&rest[..end]);
+            i += end;
+        }
+    }
+    out
+}
+
+/// Kind of a labeled block
+pub fn kind(block: &Block) -> Kind {
+    match block {
+        Block::Table(..) => Kind::Table,
+        Block::Code(..) | Block::CodeImport(..) => Kind::Listing,
+        Block::MathJax(..) => Kind::Equation,
+        _ => Kind::Figure,
+    }
+}
+
+/// Entities of XML; a bare `&` is `&amp;` and the named ones of HTML are the characters
+fn entities(text: &str) -> String {
+    lazy_static! {
+        static ref ENTITY: Regex =
+            Regex::new(r"^&(#[0-9]+|#[xX][0-9a-fA-F]+|[A-Za-z][A-Za-z0-9]*);").unwrap();
+    }
+    let mut out = String::new();
+    let mut i = 0;
+    while let Some(j) = text[i..].find('&') {
+        out += &text[i..i + j];
+        let rest = &text[i + j..];
+        match ENTITY.captures(rest) {
+            Some(caps) if caps[1].starts_with('#') || XML_ENTITIES.contains(&&caps[1]) => {
+                out += &caps[0];
+                i += j + caps[0].len();
+            }
+            Some(caps) => {
+                let decoded = html_escape::decode_html_entities(&caps[0]);
+                if decoded == caps[0] {
+                    // unknown names are the text
+                    out += "&amp;";
+                    i += j + 1;
+                } else {
+                    out += &html_escape::encode_text(&decoded);
+                    i += j + caps[0].len();
+                }
+            }
+            None => {
+                out += "&amp;";
+                i += j + 1;
+            }
+        }
+    }
+    out += &text[i..];
+    out
+}
+
+const XML_ENTITIES: &[&str] = &["amp", "lt", "gt", "quot", "apos"];
+
+#[cfg(test)]
+mod test_xhtml {
+    use crate::writer::xhtml::*;
+
+    #[test]
+    fn test_polyglot() {
+        assert_eq!(
+            polyglot("<p>A & B &amp; C&nbsp;D &#x3042; 1 < 2<br><!-- <x> --></p>"),
+            "<p>A &amp; B &amp; C\u{a0}D &#x3042; 1 &lt; 2<br /><!-- <x> --></p>"
+        );
+        assert_eq!(
+            polyglot(
+                "<input type=checkbox checked disabled=disabled><img src='a.png' alt=\"a\" />"
+            ),
+            concat!(
+                "<input type=\"checkbox\" checked=\"checked\" disabled=\"disabled\" />",
+                "<img src=\"a.png\" alt=\"a\" />"
+            )
+        );
+        assert_eq!(
+            polyglot("<a href=\"?a=1&b=2\" title='say \"hi\"'>x</a>"),
+            "<a href=\"?a=1&amp;b=2\" title=\"say &quot;hi&quot;\">x</a>"
+        );
+    }
+}